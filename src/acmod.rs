@@ -0,0 +1,168 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::dsp::{CH_FC, DSP_CHANNELS};
+
+/// Bit per 5.1 channel (FL,FR,FC,LFE,SL,SR) in a channel mask.
+pub const ALL_CHANNELS_MASK: u32 = (1 << DSP_CHANNELS) - 1;
+/// FL and FR only.
+pub const FRONT_PAIR_MASK: u32 = 0b11;
+
+/// Per-sample level below which a channel counts as silent (-60 dBFS).
+const ACTIVITY_THRESHOLD: f32 = 0.001;
+
+/// Channel layout (AC-3 `acmod`) of the encoded stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeLayout {
+    /// 3/2 with LFE.
+    Surround51,
+    /// 2/0, so the receiver shows "Dolby Digital 2.0" and may apply its own upmixer.
+    Stereo,
+}
+
+impl EncodeLayout {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Surround51 => "5.1",
+            Self::Stereo => "2.0",
+        }
+    }
+
+    /// Channels fed to ffmpeg.
+    pub fn channels(self) -> usize {
+        match self {
+            Self::Surround51 => DSP_CHANNELS,
+            Self::Stereo => 2,
+        }
+    }
+}
+
+/// How the encoded channel layout is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelLayoutMode {
+    #[default]
+    Surround51,
+    Stereo,
+    /// Follow the capture source; see [`AutoLayout`].
+    Auto,
+}
+
+impl ChannelLayoutMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Surround51 => "5.1",
+            Self::Stereo => "2.0",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// Layout the encoder starts with.
+    pub fn initial_layout(self) -> EncodeLayout {
+        match self {
+            Self::Stereo => EncodeLayout::Stereo,
+            Self::Surround51 | Self::Auto => EncodeLayout::Surround51,
+        }
+    }
+}
+
+impl std::str::FromStr for ChannelLayoutMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "5.1" => Ok(Self::Surround51),
+            "2.0" | "stereo" => Ok(Self::Stereo),
+            "auto" => Ok(Self::Auto),
+            other => Err(format!(
+                "invalid channel layout '{other}' (expected 5.1, 2.0 or auto)"
+            )),
+        }
+    }
+}
+
+/// Settings of the automatic layout mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoLayoutConfig {
+    /// Also require FC/LFE/SL/SR to carry signal, not just to be connected.
+    /// On by default: `pw-ac3-live-input` is a 5.1 sink, so PipeWire connects
+    /// every channel of it and the capture mask alone never looks stereo.
+    pub use_activity: bool,
+    /// How long the source must look stereo before switching to 2.0.
+    pub hold_ms: u32,
+}
+
+impl Default for AutoLayoutConfig {
+    fn default() -> Self {
+        Self {
+            use_activity: true,
+            hold_ms: 4_000,
+        }
+    }
+}
+
+/// Decides between 5.1 and 2.0 from the negotiated capture channels and,
+/// unless disabled, the measured activity of the non-front channels.
+///
+/// Switching to 2.0 needs the source to look stereo for `hold_ms`; any surround
+/// content switches back to 5.1 immediately, so short silences never flap.
+#[derive(Debug, Clone)]
+pub struct AutoLayout {
+    config: AutoLayoutConfig,
+    layout: EncodeLayout,
+    stereo_ms: f64,
+}
+
+impl AutoLayout {
+    pub fn new(config: AutoLayoutConfig) -> Self {
+        Self {
+            config,
+            layout: EncodeLayout::Surround51,
+            stereo_ms: 0.0,
+        }
+    }
+
+    pub fn layout(&self) -> EncodeLayout {
+        self.layout
+    }
+
+    /// Feeds one chunk of interleaved 5.1 `samples` at `sample_rate_hz`, with
+    /// `channel_mask` the 5.1 channels the capture source is connected to.
+    pub fn update(
+        &mut self,
+        samples: &[f32],
+        sample_rate_hz: u32,
+        channel_mask: u32,
+    ) -> EncodeLayout {
+        let mut surround = channel_mask & !FRONT_PAIR_MASK != 0;
+        if surround && self.config.use_activity {
+            surround = samples.chunks_exact(DSP_CHANNELS).any(|frame| {
+                frame[2..]
+                    .iter()
+                    .any(|sample| sample.abs() > ACTIVITY_THRESHOLD)
+            });
+        }
+
+        if surround {
+            self.stereo_ms = 0.0;
+            self.layout = EncodeLayout::Surround51;
+        } else {
+            let frames = samples.len() / DSP_CHANNELS;
+            self.stereo_ms += frames as f64 * 1000.0 / f64::from(sample_rate_hz.max(1));
+            if self.stereo_ms >= f64::from(self.config.hold_ms) {
+                self.layout = EncodeLayout::Stereo;
+            }
+        }
+        self.layout
+    }
+}
+
+/// Folds interleaved 5.1 into stereo for a 2.0 stream: FC and the surrounds at
+/// -3 dB into their side, LFE dropped. Replaces the contents of `out`.
+pub fn downmix_to_stereo(input: &[f32], out: &mut Vec<f32>) {
+    out.clear();
+    out.reserve(input.len() / DSP_CHANNELS * 2);
+    for frame in input.chunks_exact(DSP_CHANNELS) {
+        let center = frame[CH_FC] * FRAC_1_SQRT_2;
+        out.push(frame[0] + center + frame[4] * FRAC_1_SQRT_2);
+        out.push(frame[1] + center + frame[5] * FRAC_1_SQRT_2);
+    }
+}
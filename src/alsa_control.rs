@@ -1,4 +1,7 @@
 use log::{info, warn};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
 
 #[cfg(target_os = "linux")]
 use std::process::Command;
@@ -11,6 +14,8 @@ use std::process::Command;
 pub struct DirectAlsaHardwareGuard {
     iec_card: String,
     iec_index: String,
+    /// IEC958 channel-status rate; must match the encoder output rate.
+    sample_rate_hz: u32,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -24,29 +29,49 @@ impl DirectAlsaHardwareGuard {
     /// Configures IEC958/mixer state for direct ALSA mode.
     ///
     /// Typical Steam Deck values are `iec_card=0` and `iec_index=2`.
-    pub fn setup(iec_card: String, iec_index: String) -> Self {
+    pub fn setup(iec_card: String, iec_index: String, sample_rate_hz: u32) -> Self {
         let guard = Self {
             iec_card,
             iec_index,
+            sample_rate_hz,
         };
 
         guard.apply_commands(guard.startup_commands());
         guard
     }
 
+    /// Flags the IEC958 stream as PCM audio (`pcm = true`) or as non-audio
+    /// IEC61937 data, e.g. around the encoder's stereo PCM bypass.
+    pub fn set_pcm_audio(&self, pcm: bool) {
+        self.apply_commands(vec![self.iec958_status_command(pcm)]);
+    }
+
     fn apply_commands(&self, commands: Vec<CommandSpec>) {
         for command in commands {
             run_command_best_effort(command.program, &command.args, command.context);
         }
     }
 
-    fn startup_commands(&self) -> Vec<CommandSpec> {
-        vec![
+    fn iec958_status_command(&self, pcm: bool) -> CommandSpec {
+        let rate = self.sample_rate_hz.to_string();
+        if pcm {
             CommandSpec {
                 program: "iecset",
-                args: self.iecset_args(&["audio", "off", "rate", "48000"]),
+                args: self.iecset_args(&["audio", "on", "rate", &rate]),
+                context: "Set IEC958 to PCM audio mode",
+            }
+        } else {
+            CommandSpec {
+                program: "iecset",
+                args: self.iecset_args(&["audio", "off", "rate", &rate]),
                 context: "Set IEC958 to non-audio mode",
-            },
+            }
+        }
+    }
+
+    fn startup_commands(&self) -> Vec<CommandSpec> {
+        vec![
+            self.iec958_status_command(false),
             CommandSpec {
                 program: "amixer",
                 args: self.amixer_master_args(),
@@ -123,6 +148,86 @@ impl Drop for DirectAlsaHardwareGuard {
     }
 }
 
+/// Hand-off between the encoder, which asks for the IEC958 audio flag at every
+/// switch between PCM and IEC61937, and the status follower that applies it.
+#[derive(Debug, Default)]
+pub struct Iec958StatusSwitch {
+    state: Mutex<Iec958StatusState>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Iec958StatusState {
+    /// Startup leaves the stream flagged as non-audio, so both start `false`.
+    requested: bool,
+    applied: bool,
+    closed: bool,
+}
+
+impl Iec958StatusSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks for the audio (`pcm`) or non-audio flag and waits up to `timeout`
+    /// for the follower to apply it. Returns whether it was applied.
+    pub fn request(&self, pcm: bool, timeout: Duration) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        state.requested = pcm;
+        self.changed.notify_all();
+        self.changed
+            .wait_timeout_while(state, timeout, |state| {
+                !state.closed && state.applied != pcm
+            })
+            .is_ok_and(|(state, _)| state.applied == pcm)
+    }
+
+    /// Follower side: blocks until a flag other than the applied one is
+    /// requested. `None` once the switch is closed.
+    pub fn next_request(&self) -> Option<bool> {
+        let state = self.state.lock().ok()?;
+        let state = self
+            .changed
+            .wait_while(state, |state| {
+                !state.closed && state.requested == state.applied
+            })
+            .ok()?;
+        (!state.closed).then_some(state.requested)
+    }
+
+    /// Follower side: reports that `pcm` is now the flag on the wire.
+    pub fn set_applied(&self, pcm: bool) {
+        if let Ok(mut state) = self.state.lock() {
+            state.applied = pcm;
+            self.changed.notify_all();
+        }
+    }
+
+    /// Stops the follower; requests no longer wait for it.
+    pub fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+            self.changed.notify_all();
+        }
+    }
+}
+
+/// Applies every flag requested through `switch` with `iecset` until the
+/// switch is closed.
+pub fn spawn_iec958_status_follower(
+    guard: Arc<DirectAlsaHardwareGuard>,
+    switch: Arc<Iec958StatusSwitch>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        while let Some(pcm) = switch.next_request() {
+            guard.set_pcm_audio(pcm);
+            switch.set_applied(pcm);
+        }
+    })
+}
+
 #[cfg(target_os = "linux")]
 fn run_command_best_effort(program: &str, args: &[String], context: &str) {
     match Command::new(program).args(args).output() {
@@ -0,0 +1,228 @@
+use std::str::FromStr;
+
+use crate::dsp::{db_to_gain, time_coefficient, CH_FC, CH_LFE, DSP_CHANNELS};
+
+/// Output ceiling enforced by the safety limiter after makeup gain (-1 dBFS).
+const LIMITER_CEILING: f32 = 0.891_250_9;
+const LIMITER_RELEASE_MS: f32 = 50.0;
+/// Detector floor so silence does not produce `-inf` levels.
+const MIN_LEVEL: f32 = 1.0e-9;
+
+/// Night-mode presets selectable from the CLI or the runtime control file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NightModePreset {
+    #[default]
+    Off,
+    Light,
+    Night,
+}
+
+impl NightModePreset {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Light => "light",
+            Self::Night => "night",
+        }
+    }
+
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            Self::Off => 0,
+            Self::Light => 1,
+            Self::Night => 2,
+        }
+    }
+
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Light,
+            2 => Self::Night,
+            _ => Self::Off,
+        }
+    }
+
+    fn params(self) -> Option<CompressorParams> {
+        match self {
+            Self::Off => None,
+            Self::Light => Some(CompressorParams {
+                threshold_db: -20.0,
+                ratio: 2.0,
+                attack_ms: 10.0,
+                release_ms: 250.0,
+                makeup_db: 3.0,
+                center_reduction_scale: 0.5,
+            }),
+            Self::Night => Some(CompressorParams {
+                threshold_db: -30.0,
+                ratio: 4.0,
+                attack_ms: 5.0,
+                release_ms: 400.0,
+                makeup_db: 9.0,
+                center_reduction_scale: 0.35,
+            }),
+        }
+    }
+}
+
+impl FromStr for NightModePreset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "light" => Ok(Self::Light),
+            "night" => Ok(Self::Night),
+            other => Err(format!(
+                "unknown night mode preset '{other}' (expected off, light or night)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CompressorParams {
+    threshold_db: f32,
+    ratio: f32,
+    attack_ms: f32,
+    release_ms: f32,
+    makeup_db: f32,
+    /// Fraction of the main gain reduction (in dB) applied to FC, so dialogue
+    /// stays forward while effects are squashed.
+    center_reduction_scale: f32,
+}
+
+impl CompressorParams {
+    fn gain_reduction_db(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        if over <= 0.0 {
+            0.0
+        } else {
+            over * (1.0 - 1.0 / self.ratio)
+        }
+    }
+}
+
+/// Dialogue-aware downward compressor for the 6-channel (FL,FR,FC,LFE,SL,SR) stream.
+///
+/// The main detector is linked across FL/FR/FC/SL/SR so the surround image does not
+/// shift. FC only receives part of that reduction to keep dialogue intelligible, and
+/// LFE has its own detector so bass is tamed without being pumped by speech peaks.
+/// A linked safety limiter keeps the result below -1 dBFS after makeup gain.
+#[derive(Debug, Clone)]
+pub struct NightModeCompressor {
+    sample_rate: f32,
+    preset: NightModePreset,
+    params: Option<CompressorParams>,
+    attack_coef: f32,
+    release_coef: f32,
+    limiter_release_coef: f32,
+    main_reduction_db: f32,
+    lfe_reduction_db: f32,
+    limiter_gain: f32,
+}
+
+impl NightModeCompressor {
+    pub fn new(sample_rate: u32, preset: NightModePreset) -> Self {
+        let sample_rate = sample_rate.max(1) as f32;
+        let mut compressor = Self {
+            sample_rate,
+            preset: NightModePreset::Off,
+            params: None,
+            attack_coef: 0.0,
+            release_coef: 0.0,
+            limiter_release_coef: time_coefficient(LIMITER_RELEASE_MS, sample_rate),
+            main_reduction_db: 0.0,
+            lfe_reduction_db: 0.0,
+            limiter_gain: 1.0,
+        };
+        compressor.set_preset(preset);
+        compressor
+    }
+
+    pub fn preset(&self) -> NightModePreset {
+        self.preset
+    }
+
+    pub fn set_preset(&mut self, preset: NightModePreset) {
+        self.preset = preset;
+        self.params = preset.params();
+        if let Some(params) = self.params {
+            self.attack_coef = time_coefficient(params.attack_ms, self.sample_rate);
+            self.release_coef = time_coefficient(params.release_ms, self.sample_rate);
+        } else {
+            self.reset();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.main_reduction_db = 0.0;
+        self.lfe_reduction_db = 0.0;
+        self.limiter_gain = 1.0;
+    }
+
+    /// Processes interleaved 6-channel frames in place. A trailing partial frame is
+    /// left untouched.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let Some(params) = self.params else {
+            return;
+        };
+
+        for frame in samples.chunks_exact_mut(DSP_CHANNELS) {
+            let mut main_peak = MIN_LEVEL;
+            for (ch, sample) in frame.iter().enumerate() {
+                if ch != CH_LFE {
+                    main_peak = main_peak.max(sample.abs());
+                }
+            }
+            let lfe_peak = frame[CH_LFE].abs().max(MIN_LEVEL);
+
+            let main_target = params.gain_reduction_db(20.0 * main_peak.log10());
+            let lfe_target = params.gain_reduction_db(20.0 * lfe_peak.log10());
+            self.main_reduction_db = self.smooth(self.main_reduction_db, main_target);
+            self.lfe_reduction_db = self.smooth(self.lfe_reduction_db, lfe_target);
+
+            let main_gain = db_to_gain(params.makeup_db - self.main_reduction_db);
+            let center_gain = db_to_gain(
+                params.makeup_db - self.main_reduction_db * params.center_reduction_scale,
+            );
+            let lfe_gain = db_to_gain(params.makeup_db - self.lfe_reduction_db);
+
+            let mut output_peak = 0.0f32;
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                *sample *= match ch {
+                    CH_FC => center_gain,
+                    CH_LFE => lfe_gain,
+                    _ => main_gain,
+                };
+                output_peak = output_peak.max(sample.abs());
+            }
+
+            // Instant attack, smooth release: never let makeup gain push us into clipping.
+            let required = if output_peak > LIMITER_CEILING {
+                LIMITER_CEILING / output_peak
+            } else {
+                1.0
+            };
+            self.limiter_gain = if required < self.limiter_gain {
+                required
+            } else {
+                required + self.limiter_release_coef * (self.limiter_gain - required)
+            };
+            if self.limiter_gain < 1.0 {
+                for sample in frame.iter_mut() {
+                    *sample *= self.limiter_gain;
+                }
+            }
+        }
+    }
+
+    fn smooth(&self, current_db: f32, target_db: f32) -> f32 {
+        let coef = if target_db > current_db {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        target_db + coef * (current_db - target_db)
+    }
+}
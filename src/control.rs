@@ -0,0 +1,71 @@
+use log::{info, warn};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::dsp::DspControls;
+
+const CONTROL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Applies one `key = value` line from the runtime control file.
+///
+/// Blank lines and `#` comments are accepted and ignored.
+pub fn apply_control_line(line: &str, controls: &DspControls) -> Result<(), String> {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    if line.is_empty() {
+        return Ok(());
+    }
+
+    let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| format!("expected 'key = value', got '{line}'"))?;
+    let value = value.trim();
+
+    match key.trim() {
+        "night-mode" => controls.set_night_mode(value.parse()?),
+        "dialogue-enhance" => controls.set_dialogue_enhance(value.parse()?),
+        other => return Err(format!("unknown control key '{other}'")),
+    }
+
+    Ok(())
+}
+
+/// Applies every line of `contents`, logging (but skipping) invalid entries.
+pub fn apply_control_file_contents(contents: &str, controls: &DspControls) {
+    for (index, line) in contents.lines().enumerate() {
+        if let Err(e) = apply_control_line(line, controls) {
+            warn!("Control file line {}: {}", index + 1, e);
+        }
+    }
+}
+
+/// Polls `path` and re-applies it whenever its modification time changes.
+///
+/// This is the runtime control channel, e.g.
+/// `echo "night-mode = night" > "$XDG_RUNTIME_DIR/pw-ac3-live.ctl"`.
+/// A missing file is not an error; settings simply stay at their current values.
+pub fn spawn_control_file_watcher(
+    path: PathBuf,
+    controls: Arc<DspControls>,
+    running: Arc<AtomicBool>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        info!("Watching runtime control file: {}", path.display());
+        let mut last_modified: Option<SystemTime> = None;
+
+        while running.load(Ordering::Relaxed) {
+            let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            if modified.is_some() && modified != last_modified {
+                last_modified = modified;
+                match fs::read_to_string(&path) {
+                    Ok(contents) => apply_control_file_contents(&contents, &controls),
+                    Err(e) => warn!("Failed to read control file {}: {}", path.display(), e),
+                }
+            }
+            thread::sleep(CONTROL_POLL_INTERVAL);
+        }
+    })
+}
@@ -0,0 +1,332 @@
+use anyhow::{anyhow, Result};
+use std::f64::consts::PI;
+use std::path::Path;
+
+use crate::dsp::{parse_channel_selection, DSP_CHANNELS};
+use crate::wav::read_wav;
+
+/// Default partition size in frames (~5.3 ms at 48 kHz).
+pub const DEFAULT_FIR_PARTITION_FRAMES: usize = 256;
+const MIN_FIR_PARTITION_FRAMES: usize = 16;
+
+/// Per-channel FIR filters, indexed like the DSP stream (FL,FR,FC,LFE,SL,SR).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirConfig {
+    /// Partition (block) size of the convolution engine; also its added latency.
+    pub partition_frames: usize,
+    pub filters: [Option<Vec<f32>>; DSP_CHANNELS],
+}
+
+impl FirConfig {
+    pub fn new(partition_frames: usize) -> Self {
+        Self {
+            partition_frames,
+            filters: Default::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.iter().all(Option::is_none)
+    }
+}
+
+/// Builds a [`FirConfig`] from `CHANNELS=path.wav` specs.
+///
+/// `CHANNELS` uses the same names as the EQ file (`L`, `R`, `C`, `SUB`, `SL`, `SR`,
+/// `1`-`6`, `all`, comma separated). A mono WAV is applied to every selected channel;
+/// a multichannel WAV must provide exactly one channel per selected channel, in order.
+/// Impulse responses must already be at `sample_rate`.
+pub fn load_fir_config(
+    specs: &[String],
+    partition_frames: usize,
+    sample_rate: u32,
+) -> Result<FirConfig> {
+    if partition_frames < MIN_FIR_PARTITION_FRAMES || !partition_frames.is_power_of_two() {
+        return Err(anyhow!(
+            "FIR partition size must be a power of two >= {MIN_FIR_PARTITION_FRAMES}, got {partition_frames}"
+        ));
+    }
+
+    let mut config = FirConfig::new(partition_frames);
+    for spec in specs {
+        let (channels, path) = spec
+            .split_once('=')
+            .ok_or_else(|| anyhow!("FIR spec '{spec}' must look like CHANNELS=path.wav"))?;
+        let selected =
+            parse_channel_selection(channels).map_err(|e| anyhow!("FIR spec '{spec}': {e}"))?;
+        let wav = read_wav(Path::new(path.trim()))?;
+        if wav.sample_rate != sample_rate {
+            return Err(anyhow!(
+                "Impulse response {} is {} Hz but the encoder runs at {} Hz; resample it first",
+                path.trim(),
+                wav.sample_rate,
+                sample_rate
+            ));
+        }
+
+        let targets: Vec<usize> = (0..DSP_CHANNELS).filter(|ch| selected[*ch]).collect();
+        if wav.channels.len() == 1 {
+            for ch in targets {
+                config.filters[ch] = Some(wav.channels[0].clone());
+            }
+        } else if wav.channels.len() == targets.len() {
+            for (ch, ir) in targets.into_iter().zip(wav.channels) {
+                config.filters[ch] = Some(ir);
+            }
+        } else {
+            return Err(anyhow!(
+                "Impulse response {} has {} channels but '{}' selects {}",
+                path.trim(),
+                wav.channels.len(),
+                channels,
+                targets.len()
+            ));
+        }
+    }
+
+    Ok(config)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    #[inline]
+    fn mul(self, other: Self) -> Self {
+        Self {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+/// In-place iterative radix-2 FFT with precomputed twiddles.
+#[derive(Debug, Clone)]
+struct Fft {
+    size: usize,
+    twiddles: Vec<Complex>,
+    bit_reverse: Vec<usize>,
+}
+
+impl Fft {
+    fn new(size: usize) -> Self {
+        debug_assert!(size.is_power_of_two());
+        let bits = size.trailing_zeros();
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f64 / size as f64;
+                Complex {
+                    re: angle.cos() as f32,
+                    im: angle.sin() as f32,
+                }
+            })
+            .collect();
+        let bit_reverse = (0..size)
+            .map(|i| {
+                if bits == 0 {
+                    0
+                } else {
+                    i.reverse_bits() >> (usize::BITS - bits)
+                }
+            })
+            .collect();
+        Self {
+            size,
+            twiddles,
+            bit_reverse,
+        }
+    }
+
+    fn forward(&self, buffer: &mut [Complex]) {
+        self.transform(buffer, false);
+    }
+
+    /// Inverse transform, including the 1/N scaling.
+    fn inverse(&self, buffer: &mut [Complex]) {
+        self.transform(buffer, true);
+        let scale = 1.0 / self.size as f32;
+        for value in buffer.iter_mut() {
+            value.re *= scale;
+            value.im *= scale;
+        }
+    }
+
+    fn transform(&self, buffer: &mut [Complex], inverse: bool) {
+        for i in 0..self.size {
+            let j = self.bit_reverse[i];
+            if i < j {
+                buffer.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= self.size {
+            let half = len / 2;
+            let step = self.size / len;
+            for start in (0..self.size).step_by(len) {
+                for k in 0..half {
+                    let mut w = self.twiddles[k * step];
+                    if inverse {
+                        w.im = -w.im;
+                    }
+                    let a = buffer[start + k];
+                    let b = buffer[start + k + half].mul(w);
+                    buffer[start + k] = Complex {
+                        re: a.re + b.re,
+                        im: a.im + b.im,
+                    };
+                    buffer[start + k + half] = Complex {
+                        re: a.re - b.re,
+                        im: a.im - b.im,
+                    };
+                }
+            }
+            len *= 2;
+        }
+    }
+}
+
+/// Uniformly partitioned overlap-save convolution of one channel.
+#[derive(Debug, Clone)]
+struct PartitionedConvolver {
+    block: usize,
+    /// Spectra of the impulse response partitions, each `2 * block` bins.
+    partitions: Vec<Vec<Complex>>,
+    /// Frequency-domain delay line of past input blocks (ring, newest at `fdl_pos`).
+    fdl: Vec<Vec<Complex>>,
+    fdl_pos: usize,
+    /// Last two input blocks in the time domain.
+    history: Vec<f32>,
+    scratch: Vec<Complex>,
+}
+
+impl PartitionedConvolver {
+    fn new(fft: &Fft, block: usize, impulse_response: &[f32]) -> Self {
+        let fft_size = 2 * block;
+        let partition_count = impulse_response.len().div_ceil(block).max(1);
+        let partitions = (0..partition_count)
+            .map(|p| {
+                let mut spectrum = vec![Complex::default(); fft_size];
+                let start = (p * block).min(impulse_response.len());
+                let end = ((p + 1) * block).min(impulse_response.len());
+                for (bin, tap) in spectrum.iter_mut().zip(&impulse_response[start..end]) {
+                    bin.re = *tap;
+                }
+                fft.forward(&mut spectrum);
+                spectrum
+            })
+            .collect();
+
+        Self {
+            block,
+            partitions,
+            fdl: vec![vec![Complex::default(); fft_size]; partition_count],
+            fdl_pos: 0,
+            history: vec![0.0; fft_size],
+            scratch: vec![Complex::default(); fft_size],
+        }
+    }
+
+    fn process_block(&mut self, fft: &Fft, input: &[f32], output: &mut [f32]) {
+        let block = self.block;
+        self.history.copy_within(block.., 0);
+        self.history[block..].copy_from_slice(input);
+
+        let spectrum = &mut self.fdl[self.fdl_pos];
+        for (bin, sample) in spectrum.iter_mut().zip(&self.history) {
+            *bin = Complex {
+                re: *sample,
+                im: 0.0,
+            };
+        }
+        fft.forward(spectrum);
+
+        self.scratch.fill(Complex::default());
+        let partition_count = self.partitions.len();
+        for (p, partition) in self.partitions.iter().enumerate() {
+            let delayed = &self.fdl[(self.fdl_pos + partition_count - p) % partition_count];
+            for ((acc, x), h) in self.scratch.iter_mut().zip(delayed).zip(partition) {
+                let product = x.mul(*h);
+                acc.re += product.re;
+                acc.im += product.im;
+            }
+        }
+        self.fdl_pos = (self.fdl_pos + 1) % partition_count;
+
+        fft.inverse(&mut self.scratch);
+        for (out, value) in output.iter_mut().zip(&self.scratch[block..]) {
+            *out = value.re;
+        }
+    }
+}
+
+/// Per-channel FIR convolution engine for interleaved 6-channel frames.
+///
+/// Audio is processed in partitions of `partition_frames`, so every channel
+/// (including ones without a filter) is delayed by exactly [`Self::latency_frames`].
+pub struct FirConvolver {
+    block: usize,
+    fft: Fft,
+    channels: [Option<PartitionedConvolver>; DSP_CHANNELS],
+    input_blocks: [Vec<f32>; DSP_CHANNELS],
+    output_blocks: [Vec<f32>; DSP_CHANNELS],
+    fill: usize,
+}
+
+impl FirConvolver {
+    pub fn new(config: &FirConfig) -> Self {
+        let block = config
+            .partition_frames
+            .max(MIN_FIR_PARTITION_FRAMES)
+            .next_power_of_two();
+        let fft = Fft::new(2 * block);
+        let channels = std::array::from_fn(|ch| {
+            config.filters[ch]
+                .as_deref()
+                .map(|ir| PartitionedConvolver::new(&fft, block, ir))
+        });
+
+        Self {
+            block,
+            fft,
+            channels,
+            input_blocks: std::array::from_fn(|_| vec![0.0; block]),
+            output_blocks: std::array::from_fn(|_| vec![0.0; block]),
+            fill: 0,
+        }
+    }
+
+    /// Delay added by the engine, in frames.
+    pub fn latency_frames(&self) -> usize {
+        self.block
+    }
+
+    /// Processes interleaved 6-channel frames in place. A trailing partial frame is
+    /// left untouched.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_exact_mut(DSP_CHANNELS) {
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                self.input_blocks[ch][self.fill] = *sample;
+                *sample = self.output_blocks[ch][self.fill];
+            }
+
+            self.fill += 1;
+            if self.fill == self.block {
+                self.fill = 0;
+                for ch in 0..DSP_CHANNELS {
+                    match self.channels[ch].as_mut() {
+                        Some(convolver) => convolver.process_block(
+                            &self.fft,
+                            &self.input_blocks[ch],
+                            &mut self.output_blocks[ch],
+                        ),
+                        None => self.output_blocks[ch].copy_from_slice(&self.input_blocks[ch]),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Metadata key written by `pactl set-default-sink` and desktop settings;
+/// WirePlumber derives `default.audio.sink` from it.
+pub const CONFIGURED_DEFAULT_SINK_KEY: &str = "default.configured.audio.sink";
+/// Metadata key holding the sink WirePlumber currently uses as default.
+pub const DEFAULT_SINK_KEY: &str = "default.audio.sink";
+/// Per-stream metadata key that moves a stream to another sink.
+pub const TARGET_OBJECT_KEY: &str = "target.object";
+/// Node name of the virtual sink streams are moved to.
+pub const INPUT_NODE_NAME: &str = "pw-ac3-live-input";
+
+const STATE_KEY: &str = "previous-default";
+
+/// Sink name from a default-sink metadata value, e.g. `{ "name": "alsa_output.hdmi" }`.
+pub fn parse_sink_name(value: &str) -> Option<String> {
+    let rest = &value[value.find("\"name\"")? + "\"name\"".len()..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let rest = rest.strip_prefix('"')?;
+
+    let mut name = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(name).filter(|name| !name.is_empty()),
+            '\\' => name.push(chars.next()?),
+            c => name.push(c),
+        }
+    }
+    None
+}
+
+/// Metadata value (`Spa:String:JSON`) selecting `name` as default sink.
+pub fn sink_name_json(name: &str) -> String {
+    let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("{{ \"name\": \"{escaped}\" }}")
+}
+
+/// What to put back when the takeover ends. Persisted while it is active so a
+/// crashed run can still be undone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TakeoverState {
+    /// Configured default sink before the takeover; `None` if none was set.
+    pub previous_default: Option<String>,
+}
+
+impl TakeoverState {
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("expected 'key = value', got '{line}'"))?;
+            if key.trim() == STATE_KEY {
+                let value = value.trim();
+                return Ok(Self {
+                    previous_default: (!value.is_empty()).then(|| value.to_string()),
+                });
+            }
+        }
+        Err(format!("missing '{STATE_KEY}' entry"))
+    }
+
+    pub fn to_contents(&self) -> String {
+        format!(
+            "# pw-ac3-live default sink takeover; undo with `pw-ac3-live restore-default-sink`\n{STATE_KEY} = {}\n",
+            self.previous_default.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+/// `$XDG_STATE_HOME/pw-ac3-live/default-sink.state` (or under `~/.local/state`).
+/// The state must survive a reboot: WirePlumber persists the configured default.
+pub fn default_state_path() -> PathBuf {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("pw-ac3-live").join("default-sink.state")
+}
+
+/// Reads a leftover state file; `Ok(None)` when there is none.
+pub fn load_state(path: &Path) -> Result<Option<TakeoverState>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => TakeoverState::parse(&contents)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Writes the state atomically (temporary file + rename).
+pub fn save_state(path: &Path, state: &TakeoverState) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, state.to_contents())
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn clear_state(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
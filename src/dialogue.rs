@@ -0,0 +1,211 @@
+use std::str::FromStr;
+
+use crate::dsp::{db_to_gain, time_coefficient, CH_FC, CH_LFE, DSP_CHANNELS};
+use crate::equalizer::{Biquad, BiquadCoefficients, BiquadKind, BiquadSpec};
+
+/// Speech band extracted from FC (roughly the telephone band).
+const SPEECH_LOW_HZ: f64 = 250.0;
+const SPEECH_HIGH_HZ: f64 = 4_000.0;
+/// Speech-band level in FC below which nothing is treated as dialogue (-50 dBFS).
+const SPEECH_THRESHOLD: f32 = 0.003_162_3;
+/// Minimum share of FC's level that must sit in the speech band.
+const SPEECH_DOMINANCE: f32 = 0.5;
+const ENVELOPE_ATTACK_MS: f32 = 5.0;
+const ENVELOPE_RELEASE_MS: f32 = 100.0;
+/// Ramp times of the enhancement itself, slow enough to avoid audible pumping.
+const ACTIVITY_ATTACK_MS: f32 = 30.0;
+const ACTIVITY_RELEASE_MS: f32 = 400.0;
+
+/// Dialogue enhancement presets selectable from the CLI or the runtime control file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DialogueEnhance {
+    #[default]
+    Off,
+    Low,
+    High,
+}
+
+impl DialogueEnhance {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Low => "low",
+            Self::High => "high",
+        }
+    }
+
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            Self::Off => 0,
+            Self::Low => 1,
+            Self::High => 2,
+        }
+    }
+
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Low,
+            2 => Self::High,
+            _ => Self::Off,
+        }
+    }
+
+    /// `(speech boost in FC, ducking of FL/FR/SL/SR)` in dB.
+    fn amounts_db(self) -> Option<(f32, f32)> {
+        match self {
+            Self::Off => None,
+            Self::Low => Some((4.0, 2.0)),
+            Self::High => Some((8.0, 4.0)),
+        }
+    }
+}
+
+impl FromStr for DialogueEnhance {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "low" => Ok(Self::Low),
+            "high" => Ok(Self::High),
+            other => Err(format!(
+                "unknown dialogue enhancement level '{other}' (expected off, low or high)"
+            )),
+        }
+    }
+}
+
+/// Speech-band boost for FC with ducking of the other full-range channels.
+///
+/// The speech band of FC is extracted with a high-pass/low-pass pair and added
+/// back on top of the dry signal. Dialogue is assumed when that band is above
+/// -50 dBFS and carries most of FC's level; only then are the boost and the
+/// FL/FR/SL/SR ducking ramped in, so music and effects in FC are left alone.
+/// LFE is never touched.
+#[derive(Debug, Clone)]
+pub struct DialogueEnhancer {
+    level: DialogueEnhance,
+    boost_gain: f32,
+    duck_db: f32,
+    high_pass: Biquad,
+    low_pass: Biquad,
+    envelope_attack_coef: f32,
+    envelope_release_coef: f32,
+    activity_attack_coef: f32,
+    activity_release_coef: f32,
+    speech_envelope: f32,
+    center_envelope: f32,
+    activity: f32,
+}
+
+impl DialogueEnhancer {
+    pub fn new(sample_rate: u32, level: DialogueEnhance) -> Self {
+        let sample_rate = sample_rate.max(1);
+        let rate = sample_rate as f32;
+        let band_filter = |kind, freq_hz| {
+            Biquad::new(BiquadCoefficients::design(
+                &BiquadSpec {
+                    kind,
+                    freq_hz,
+                    gain_db: 0.0,
+                    q: std::f64::consts::FRAC_1_SQRT_2,
+                },
+                f64::from(sample_rate),
+            ))
+        };
+        let mut enhancer = Self {
+            level: DialogueEnhance::Off,
+            boost_gain: 1.0,
+            duck_db: 0.0,
+            high_pass: band_filter(BiquadKind::HighPass, SPEECH_LOW_HZ),
+            low_pass: band_filter(BiquadKind::LowPass, SPEECH_HIGH_HZ),
+            envelope_attack_coef: time_coefficient(ENVELOPE_ATTACK_MS, rate),
+            envelope_release_coef: time_coefficient(ENVELOPE_RELEASE_MS, rate),
+            activity_attack_coef: time_coefficient(ACTIVITY_ATTACK_MS, rate),
+            activity_release_coef: time_coefficient(ACTIVITY_RELEASE_MS, rate),
+            speech_envelope: 0.0,
+            center_envelope: 0.0,
+            activity: 0.0,
+        };
+        enhancer.set_level(level);
+        enhancer
+    }
+
+    pub fn level(&self) -> DialogueEnhance {
+        self.level
+    }
+
+    pub fn set_level(&mut self, level: DialogueEnhance) {
+        self.level = level;
+        let (boost_db, duck_db) = level.amounts_db().unwrap_or((0.0, 0.0));
+        self.boost_gain = db_to_gain(boost_db);
+        self.duck_db = duck_db;
+        if level == DialogueEnhance::Off {
+            self.activity = 0.0;
+        }
+    }
+
+    /// Current amount of enhancement applied, from 0 (none) to 1 (full).
+    pub fn activity(&self) -> f32 {
+        self.activity
+    }
+
+    /// Processes interleaved 6-channel frames in place. A trailing partial frame is
+    /// left untouched.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if self.level == DialogueEnhance::Off {
+            return;
+        }
+
+        for frame in samples.chunks_exact_mut(DSP_CHANNELS) {
+            let center = frame[CH_FC];
+            let speech = self
+                .low_pass
+                .process(self.high_pass.process(f64::from(center))) as f32;
+
+            self.speech_envelope = follow(
+                self.speech_envelope,
+                speech.abs(),
+                self.envelope_attack_coef,
+                self.envelope_release_coef,
+            );
+            self.center_envelope = follow(
+                self.center_envelope,
+                center.abs(),
+                self.envelope_attack_coef,
+                self.envelope_release_coef,
+            );
+
+            let speaking = self.speech_envelope > SPEECH_THRESHOLD
+                && self.speech_envelope >= SPEECH_DOMINANCE * self.center_envelope;
+            self.activity = follow(
+                self.activity,
+                if speaking { 1.0 } else { 0.0 },
+                self.activity_attack_coef,
+                self.activity_release_coef,
+            );
+            if self.activity < 1.0e-4 {
+                continue;
+            }
+
+            let duck_gain = db_to_gain(-self.duck_db * self.activity);
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                match ch {
+                    CH_FC => *sample += speech * (self.boost_gain - 1.0) * self.activity,
+                    CH_LFE => {}
+                    _ => *sample *= duck_gain,
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn follow(current: f32, target: f32, attack_coef: f32, release_coef: f32) -> f32 {
+    let coef = if target > current {
+        attack_coef
+    } else {
+        release_coef
+    };
+    target + coef * (current - target)
+}
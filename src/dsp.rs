@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use crate::compressor::{NightModeCompressor, NightModePreset};
+use crate::convolution::{FirConfig, FirConvolver};
+use crate::dialogue::{DialogueEnhance, DialogueEnhancer};
+use crate::equalizer::{EqConfig, Equalizer};
+
+/// Channel count of the stream processed by the DSP chain (FL,FR,FC,LFE,SL,SR).
+pub const DSP_CHANNELS: usize = 6;
+
+pub(crate) const CH_FC: usize = 2;
+pub(crate) const CH_LFE: usize = 3;
+
+pub(crate) fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// Parses a whitespace or comma separated channel list into a DSP channel mask.
+///
+/// Accepts Equalizer APO names (`L`, `R`, `C`, `SUB`/`LFE`, `SL`, `SR`, `RL`, `RR`),
+/// 1-based channel numbers and `all`.
+pub fn parse_channel_selection(spec: &str) -> Result<[bool; DSP_CHANNELS]> {
+    let mut selected = [false; DSP_CHANNELS];
+    for token in spec.split(|c: char| c.is_whitespace() || c == ',') {
+        if token.is_empty() {
+            continue;
+        }
+        let upper = token.to_ascii_uppercase();
+        if upper == "ALL" {
+            return Ok([true; DSP_CHANNELS]);
+        }
+        let index = match upper.as_str() {
+            "L" | "FL" => 0,
+            "R" | "FR" => 1,
+            "C" | "FC" => CH_FC,
+            "SUB" | "LFE" => CH_LFE,
+            // 5.1 AC-3 has a single surround pair; rear and side names both map to it.
+            "SL" | "RL" => 4,
+            "SR" | "RR" => 5,
+            numeric => match numeric.parse::<usize>() {
+                Ok(n) if (1..=DSP_CHANNELS).contains(&n) => n - 1,
+                _ => return Err(anyhow!("unknown channel '{token}'")),
+            },
+        };
+        selected[index] = true;
+    }
+    if !selected.iter().any(|on| *on) {
+        return Err(anyhow!("no channel selected"));
+    }
+    Ok(selected)
+}
+
+/// One-pole smoothing coefficient reaching ~63% of a step after `time_ms`.
+pub(crate) fn time_coefficient(time_ms: f32, sample_rate: f32) -> f32 {
+    let samples = time_ms.max(0.0) * 0.001 * sample_rate;
+    if samples <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / samples).exp()
+    }
+}
+
+/// DSP settings fixed at startup (loaded from files or CLI flags).
+#[derive(Debug, Clone, Default)]
+pub struct DspConfig {
+    /// Per-channel biquad bank, applied first in the chain.
+    pub eq: Option<EqConfig>,
+    /// Per-channel impulse responses, applied after the EQ.
+    pub fir: Option<FirConfig>,
+}
+
+/// DSP settings that can be changed while the encoder is running.
+///
+/// Shared between the control file watcher and the encoder feeder thread; every
+/// field is a plain atomic so the feeder never blocks on it.
+#[derive(Debug, Default)]
+pub struct DspControls {
+    night_mode: AtomicU8,
+    dialogue_enhance: AtomicU8,
+}
+
+impl DspControls {
+    pub fn new(night_mode: NightModePreset, dialogue_enhance: DialogueEnhance) -> Self {
+        Self {
+            night_mode: AtomicU8::new(night_mode.to_u8()),
+            dialogue_enhance: AtomicU8::new(dialogue_enhance.to_u8()),
+        }
+    }
+
+    pub fn night_mode(&self) -> NightModePreset {
+        NightModePreset::from_u8(self.night_mode.load(Ordering::Relaxed))
+    }
+
+    pub fn set_night_mode(&self, preset: NightModePreset) {
+        self.night_mode.store(preset.to_u8(), Ordering::Relaxed);
+    }
+
+    pub fn dialogue_enhance(&self) -> DialogueEnhance {
+        DialogueEnhance::from_u8(self.dialogue_enhance.load(Ordering::Relaxed))
+    }
+
+    pub fn set_dialogue_enhance(&self, level: DialogueEnhance) {
+        self.dialogue_enhance
+            .store(level.to_u8(), Ordering::Relaxed);
+    }
+}
+
+/// Encoder-side processing applied to interleaved 6-channel frames before they
+/// are written to ffmpeg. Runs on the feeder thread, never in the RT capture callback.
+pub struct DspChain {
+    controls: Arc<DspControls>,
+    equalizer: Option<Equalizer>,
+    fir: Option<FirConvolver>,
+    dialogue: DialogueEnhancer,
+    compressor: NightModeCompressor,
+}
+
+impl DspChain {
+    pub fn new(sample_rate: u32, config: &DspConfig, controls: Arc<DspControls>) -> Self {
+        let night_mode = controls.night_mode();
+        Self {
+            equalizer: config
+                .eq
+                .as_ref()
+                .filter(|eq| !eq.is_empty())
+                .map(|eq| Equalizer::new(eq, sample_rate)),
+            fir: config
+                .fir
+                .as_ref()
+                .filter(|fir| !fir.is_empty())
+                .map(FirConvolver::new),
+            dialogue: DialogueEnhancer::new(sample_rate, controls.dialogue_enhance()),
+            compressor: NightModeCompressor::new(sample_rate, night_mode),
+            controls,
+        }
+    }
+
+    /// Delay added by the chain, in frames (only the FIR stage buffers audio).
+    pub fn latency_frames(&self) -> usize {
+        self.fir.as_ref().map_or(0, FirConvolver::latency_frames)
+    }
+
+    /// Applies the current runtime settings, then processes `samples` in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let night_mode = self.controls.night_mode();
+        if night_mode != self.compressor.preset() {
+            info!(
+                "Night mode preset changed: {} -> {}",
+                self.compressor.preset().as_str(),
+                night_mode.as_str()
+            );
+            self.compressor.set_preset(night_mode);
+        }
+        let dialogue_enhance = self.controls.dialogue_enhance();
+        if dialogue_enhance != self.dialogue.level() {
+            info!(
+                "Dialogue enhancement changed: {} -> {}",
+                self.dialogue.level().as_str(),
+                dialogue_enhance.as_str()
+            );
+            self.dialogue.set_level(dialogue_enhance);
+        }
+
+        if let Some(equalizer) = self.equalizer.as_mut() {
+            equalizer.process(samples);
+        }
+        if let Some(fir) = self.fir.as_mut() {
+            fir.process(samples);
+        }
+        self.dialogue.process(samples);
+        self.compressor.process(samples);
+    }
+}
@@ -4,20 +4,66 @@ use rtrb::{Consumer, Producer};
 use std::io::{Read, Write};
 use std::process::{Command, Stdio};
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU32, Ordering},
     Arc,
 };
 use std::thread;
 use std::time::{Duration, Instant};
 
+use crate::acmod::{
+    downmix_to_stereo, AutoLayout, AutoLayoutConfig, ChannelLayoutMode, EncodeLayout,
+    ALL_CHANNELS_MASK,
+};
+use crate::alsa_control::Iec958StatusSwitch;
+use crate::dsp::{DspChain, DspConfig, DspControls};
+use crate::iec61937::{self, BurstDetector};
+use crate::resampler::Resampler;
+use crate::voice::{VoiceDuckingConfig, VoiceMixer, VOICE_CHANNELS};
+
 #[cfg(target_os = "linux")]
 use std::os::unix::io::AsRawFd;
 
 const INPUT_CHANNELS: usize = 6;
+/// Default rate of the PCM fed to ffmpeg and of the encoded IEC61937 output.
+pub const SAMPLE_RATE_HZ: u32 = 48_000;
+/// Sample rates allowed by AC-3 and IEC 61937.
+pub const SUPPORTED_SAMPLE_RATES_HZ: [u32; 3] = [32_000, 44_100, 48_000];
+
+/// Parses an encoder rate given in Hz (`48000`) or kHz (`44.1k`).
+pub fn parse_sample_rate(value: &str) -> Result<u32, String> {
+    let value = value.trim();
+    let rate = match value.strip_suffix(['k', 'K']) {
+        Some(khz) => khz
+            .parse::<f64>()
+            .map(|khz| (khz * 1000.0).round() as u32)
+            .map_err(|_| format!("invalid sample rate '{value}'"))?,
+        None => value
+            .parse::<u32>()
+            .map_err(|_| format!("invalid sample rate '{value}'"))?,
+    };
+    if SUPPORTED_SAMPLE_RATES_HZ.contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(format!(
+            "unsupported AC-3 sample rate {rate} Hz (expected 32000, 44100 or 48000)"
+        ))
+    }
+}
 
-const OUTPUT_FRAME_BYTES_U8: usize = 4;
+const OUTPUT_FRAME_BYTES_U8: usize = iec61937::FRAME_BYTES;
+/// ffmpeg `-probesize` for IEC61937 input: two E-AC-3 bursts.
+const TRANSCODE_PROBE_SIZE: &str = "49152";
+/// Pause bursts written between two ffmpeg sessions when the output rate or the
+/// channel layout changes.
+const RESTART_PAUSE_MS: u32 = 32;
+/// How long an IEC958 status switch waits for the queued output to play out,
+/// and then for the status follower to apply the flag.
+const IEC958_SWITCH_TIMEOUT: Duration = Duration::from_secs(1);
 const MAX_STDOUT_READ_BUFFER_SIZE: usize = 1024;
 const MIN_STDOUT_READ_BUFFER_SIZE: usize = 512;
+/// Voice samples allowed to queue up beyond the current chunk before the oldest
+/// are dropped, so clock drift between the two sinks cannot build up latency.
+const MAX_VOICE_BACKLOG_SAMPLES: usize = 2048 * VOICE_CHANNELS;
 
 /// Minimum pipe buffer size (4KB = one page, the kernel minimum).
 const TARGET_PIPE_SIZE: i32 = 4096;
@@ -52,6 +98,56 @@ fn shrink_pipe_buffer(fd: std::os::unix::io::RawFd, label: &str) {
 pub struct EncoderConfig {
     pub ffmpeg_thread_queue_size: usize,
     pub feeder_chunk_frames: usize,
+    /// Static settings for the DSP chain run by the feeder thread.
+    pub dsp: DspConfig,
+    /// Runtime-adjustable settings for the DSP chain run by the feeder thread.
+    pub dsp_controls: Arc<DspControls>,
+    /// Ducking of the main input by the voice-chat sink, if one is connected.
+    pub voice_ducking: VoiceDuckingConfig,
+    /// Rate of the samples arriving on the input rings, published by the capture
+    /// stream once its format is negotiated. Other rates are resampled to
+    /// `sample_rate_hz` before the DSP chain.
+    pub capture_rate_hz: Arc<AtomicU32>,
+    /// Encode and IEC61937 output rate at startup; one of [`SUPPORTED_SAMPLE_RATES_HZ`].
+    pub sample_rate_hz: u32,
+    /// Rate the output stream actually negotiated (0 until known). When it moves
+    /// to another supported rate, ffmpeg is restarted to follow it.
+    pub output_rate_hz: Arc<AtomicU32>,
+    /// Channel layout of the AC-3 stream, fixed or following the source.
+    pub channel_layout: ChannelLayoutMode,
+    /// Thresholds of [`ChannelLayoutMode::Auto`].
+    pub auto_layout: AutoLayoutConfig,
+    /// 5.1 channels (bit per FL,FR,FC,LFE,SL,SR) the capture stream's negotiated
+    /// layout actually feeds, published by the capture stream.
+    pub capture_channel_mask: Arc<AtomicU32>,
+    /// Plain stereo PCM instead of AC-3 while only FL/FR carry signal.
+    pub pcm_bypass: PcmBypassConfig,
+    /// Set by the encoder while it writes PCM instead of IEC61937 bursts.
+    pub pcm_bypass_active: Arc<AtomicBool>,
+    /// Hand-off to the IEC958 status follower. When present, the encoder waits
+    /// for the follower at every switch, so the flag changes in step with the
+    /// output instead of after it.
+    pub iec958_status: Option<Arc<Iec958StatusSwitch>>,
+    /// Audio the output device buffers behind the output ring (the ALSA buffer
+    /// in direct mode). An IEC958 status switch waits for it to play out.
+    pub output_latency: Duration,
+}
+
+/// Settings of the stereo PCM bypass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmBypassConfig {
+    pub enabled: bool,
+    /// How long FC/LFE/SL/SR must stay silent before AC-3 is bypassed.
+    pub hold_ms: u32,
+}
+
+impl Default for PcmBypassConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hold_ms: 5_000,
+        }
+    }
 }
 
 impl Default for EncoderConfig {
@@ -59,6 +155,19 @@ impl Default for EncoderConfig {
         Self {
             ffmpeg_thread_queue_size: 128,
             feeder_chunk_frames: 128,
+            dsp: DspConfig::default(),
+            dsp_controls: Arc::new(DspControls::default()),
+            voice_ducking: VoiceDuckingConfig::default(),
+            capture_rate_hz: Arc::new(AtomicU32::new(SAMPLE_RATE_HZ)),
+            sample_rate_hz: SAMPLE_RATE_HZ,
+            output_rate_hz: Arc::new(AtomicU32::new(0)),
+            channel_layout: ChannelLayoutMode::default(),
+            auto_layout: AutoLayoutConfig::default(),
+            capture_channel_mask: Arc::new(AtomicU32::new(ALL_CHANNELS_MASK)),
+            pcm_bypass: PcmBypassConfig::default(),
+            pcm_bypass_active: Arc::new(AtomicBool::new(false)),
+            iec958_status: None,
+            output_latency: Duration::ZERO,
         }
     }
 }
@@ -78,20 +187,490 @@ pub fn run_encoder_loop(
     output: Producer<u8>,
     running: Arc<AtomicBool>,
 ) -> Result<()> {
-    run_encoder_loop_with_config(input, output, running, EncoderConfig::default())
+    run_encoder_loop_with_config(input, None, output, running, EncoderConfig::default())
 }
 
+/// Like [`run_encoder_loop`], with explicit settings and an optional voice-chat
+/// input (interleaved stereo F32) that is mixed in and ducks the main input.
+///
+/// When `config.output_rate_hz` changes mid-stream, or the automatic channel
+/// layout switches between 5.1 and 2.0, ffmpeg is restarted with the new format
+/// and the gap is filled with IEC61937 pause bursts. With `config.pcm_bypass`,
+/// ffmpeg is stopped while only FL/FR carry signal and stereo S16LE PCM is
+/// written to `output` instead. IEC61937 bursts arriving on FL/FR (a player in
+/// passthrough mode) are forwarded to `output` unchanged if they carry AC-3 at
+/// the output rate, and decoded and re-encoded to AC-3 by ffmpeg otherwise
+/// (E-AC-3, DTS, or AC-3 at another rate). Bitstreams ffmpeg cannot read from a
+/// 2ch carrier (TrueHD, DTS-HD) are replaced by pause bursts.
 pub fn run_encoder_loop_with_config(
-    mut input: Consumer<f32>,
+    input: Consumer<f32>,
+    voice_input: Option<Consumer<f32>>,
     mut output: Producer<u8>,
     running: Arc<AtomicBool>,
     config: EncoderConfig,
 ) -> Result<()> {
-    info!("Starting FFmpeg subprocess...");
+    if !SUPPORTED_SAMPLE_RATES_HZ.contains(&config.sample_rate_hz) {
+        return Err(anyhow!(
+            "Unsupported AC-3 sample rate: {} Hz",
+            config.sample_rate_hz
+        ));
+    }
+    let mut format = SessionFormat {
+        sample_rate_hz: config.sample_rate_hz,
+        layout: config.channel_layout.initial_layout(),
+        mode: SessionMode::Ac3,
+    };
+    let mut state = FeederState {
+        main: input,
+        voice: voice_input,
+        auto_layout: (config.channel_layout == ChannelLayoutMode::Auto)
+            .then(|| AutoLayout::new(config.auto_layout)),
+        pcm_bypass: config.pcm_bypass.enabled.then(|| {
+            AutoLayout::new(AutoLayoutConfig {
+                use_activity: true,
+                hold_ms: config.pcm_bypass.hold_ms,
+            })
+        }),
+        bursts: BurstDetector::new(),
+        transcode_failed: false,
+        pending: Vec::new(),
+    };
 
-    let ffmpeg_thread_queue_size = config.ffmpeg_thread_queue_size.max(1);
+    loop {
+        let (end, returned_state) = match format.mode {
+            SessionMode::Ac3 | SessionMode::Transcode => {
+                run_encoder_session(state, &mut output, &running, &config, format)?
+            }
+            SessionMode::PcmBypass => {
+                run_pcm_session(state, &mut output, &running, &config, format)?
+            }
+            SessionMode::Passthrough | SessionMode::Muted => {
+                run_passthrough_session(state, &mut output, &running, &config, format)?
+            }
+        };
+        state = returned_state;
+        match end {
+            SessionEnd::Shutdown => {
+                config.pcm_bypass_active.store(false, Ordering::Relaxed);
+                return Ok(());
+            }
+            SessionEnd::Restart(next) => {
+                if next.sample_rate_hz != format.sample_rate_hz {
+                    info!(
+                        "Output rate renegotiated: {} Hz -> {} Hz; restarting ffmpeg",
+                        format.sample_rate_hz, next.sample_rate_hz
+                    );
+                    if config.dsp.fir.is_some() && next.sample_rate_hz != config.sample_rate_hz {
+                        warn!(
+                            "FIR filters were loaded for {} Hz and now run at {} Hz",
+                            config.sample_rate_hz, next.sample_rate_hz
+                        );
+                    }
+                }
+                if next.mode != format.mode {
+                    let codec = state
+                        .bursts
+                        .data_type()
+                        .map_or("unknown", iec61937::data_type_name);
+                    match (format.mode, next.mode) {
+                        (_, SessionMode::Passthrough) => {
+                            info!(
+                                "IEC 61937 {} bitstream on the input; passing it through",
+                                codec
+                            )
+                        }
+                        (_, SessionMode::Transcode) => {
+                            info!(
+                                "IEC 61937 {} bitstream on the input; transcoding it to AC-3",
+                                codec
+                            )
+                        }
+                        // The failed transcoding session already logged why.
+                        (SessionMode::Transcode, SessionMode::Muted) => {}
+                        (_, SessionMode::Muted) => {
+                            warn!(
+                                "IEC 61937 {} bitstream on the input cannot reach an AC-3 output; \
+                                 sending pause bursts until the input turns back into PCM",
+                                codec
+                            )
+                        }
+                        (
+                            SessionMode::Passthrough | SessionMode::Transcode | SessionMode::Muted,
+                            _,
+                        ) => {
+                            info!("PCM input resumed; encoding again")
+                        }
+                        (_, SessionMode::PcmBypass) => {
+                            info!("Only FL/FR carry signal; bypassing AC-3 with stereo PCM")
+                        }
+                        _ => info!("Surround channels active; resuming AC-3"),
+                    }
+                } else if next.mode == SessionMode::Ac3 && next.layout != format.layout {
+                    info!(
+                        "Source channel layout changed: encoding {} instead of {}; restarting ffmpeg",
+                        next.layout.as_str(),
+                        format.layout.as_str()
+                    );
+                }
+                let pcm = next.mode == SessionMode::PcmBypass;
+                if pcm != (format.mode == SessionMode::PcmBypass) {
+                    switch_iec958_status(&mut output, &running, &config, format, pcm);
+                }
+                if matches!(next.mode, SessionMode::Ac3 | SessionMode::Transcode) {
+                    let gap = iec61937::pause_gap(next.sample_rate_hz, RESTART_PAUSE_MS);
+                    let writable = output.slots().min(gap.len());
+                    let writable = writable - writable % OUTPUT_FRAME_BYTES_U8;
+                    if let Ok(chunk) = output.write_chunk_uninit(writable) {
+                        chunk.fill_from_iter(gap.iter().copied());
+                    }
+                }
+                format = next;
+            }
+        }
+    }
+}
+
+/// Requests the IEC958 audio flag (`pcm`) through `config.pcm_bypass_active`
+/// when the output switches between PCM and IEC61937.
+///
+/// With a status follower, the flag changes in step with the output: a gap that
+/// is valid under the old flag is written first (pause bursts after IEC61937,
+/// digital silence after PCM), the output queued ahead of it is left to play,
+/// including what the device buffers behind the ring, and the next session
+/// only starts once the follower has applied the flag.
+fn switch_iec958_status(
+    output: &mut Producer<u8>,
+    running: &AtomicBool,
+    config: &EncoderConfig,
+    format: SessionFormat,
+    pcm: bool,
+) {
+    config.pcm_bypass_active.store(pcm, Ordering::Relaxed);
+    let Some(status) = config.iec958_status.as_ref() else {
+        return;
+    };
+
+    // The gap outlasts the device buffer, so the device is still playing it
+    // when the flag changes.
+    let latency_ms = u32::try_from(config.output_latency.as_millis()).unwrap_or(u32::MAX);
+    let mut gap = iec61937::pause_gap(
+        format.sample_rate_hz,
+        RESTART_PAUSE_MS.saturating_add(latency_ms),
+    );
+    if !pcm {
+        // Pause bursts would play as noise while the flag still says audio.
+        gap.fill(0);
+    }
+    let writable = output.slots().min(gap.len());
+    let writable = writable - writable % OUTPUT_FRAME_BYTES_U8;
+    if let Ok(chunk) = output.write_chunk_uninit(writable) {
+        chunk.fill_from_iter(gap.iter().copied());
+    }
+
+    let capacity = output.buffer().capacity();
+    let deadline = Instant::now() + IEC958_SWITCH_TIMEOUT;
+    while running.load(Ordering::Relaxed)
+        && capacity - output.slots() > writable
+        && Instant::now() < deadline
+    {
+        thread::sleep(Duration::from_millis(1));
+    }
+    // Everything ahead of the gap has left the ring; the device may still
+    // hold up to `output_latency` of it.
+    let deadline = Instant::now() + config.output_latency;
+    while running.load(Ordering::Relaxed) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    if !status.request(pcm, IEC958_SWITCH_TIMEOUT) && running.load(Ordering::Relaxed) {
+        warn!(
+            "IEC958 status was not switched to {} within {:?}; continuing anyway",
+            if pcm { "audio" } else { "non-audio" },
+            IEC958_SWITCH_TIMEOUT
+        );
+    }
+}
+
+/// Rate, channel layout and kind of one session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SessionFormat {
+    sample_rate_hz: u32,
+    /// Layout of AC-3 sessions; kept across the other modes for the next one.
+    layout: EncodeLayout,
+    mode: SessionMode,
+}
+
+/// What a session writes to the output ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionMode {
+    /// IEC61937 bursts from ffmpeg.
+    Ac3,
+    /// Stereo S16LE PCM, without ffmpeg.
+    PcmBypass,
+    /// The IEC61937 bursts found on the input, forwarded word for word.
+    Passthrough,
+    /// Bursts found on the input that cannot be forwarded (another codec, or
+    /// AC-3 at another rate), decoded and re-encoded by ffmpeg.
+    Transcode,
+    /// Pause bursts in place of a bitstream that cannot reach the output.
+    Muted,
+}
+
+/// Feeder state handed from one session to the next.
+struct FeederState {
+    main: Consumer<f32>,
+    voice: Option<Consumer<f32>>,
+    /// Present in automatic layout mode; kept across sessions so its hold time
+    /// is not reset by a restart.
+    auto_layout: Option<AutoLayout>,
+    /// Present when the PCM bypass is enabled; "stereo" means bypass.
+    pcm_bypass: Option<AutoLayout>,
+    bursts: BurstDetector,
+    /// ffmpeg could not transcode the current bitstream; mute it until the
+    /// input turns back into PCM.
+    transcode_failed: bool,
+    /// Input chunk read by the previous session but meant for the next one.
+    pending: Vec<f32>,
+}
+
+impl FeederState {
+    /// Session mode for the bitstream currently on the input. Only AC-3 at the
+    /// output rate can be forwarded as it is; ffmpeg's `spdif` demuxer reads the
+    /// other codecs, and AC-3 at other rates, from the words at any input rate.
+    fn bitstream_mode(&self, input_rate: u32, output_rate: u32) -> SessionMode {
+        match self.bursts.data_type() {
+            Some(data_type)
+                if (data_type != iec61937::DATA_TYPE_AC3 || input_rate != output_rate)
+                    && iec61937::can_transcode(data_type) =>
+            {
+                if self.transcode_failed {
+                    SessionMode::Muted
+                } else {
+                    SessionMode::Transcode
+                }
+            }
+            // Only pause bursts so far.
+            None if input_rate == output_rate => SessionMode::Passthrough,
+            Some(iec61937::DATA_TYPE_AC3) => SessionMode::Passthrough,
+            _ => SessionMode::Muted,
+        }
+    }
+}
+
+/// Why a session stopped.
+enum SessionEnd {
+    Shutdown,
+    /// The output rate, the source layout, the bypass state or the input type
+    /// changed; continue with this format.
+    Restart(SessionFormat),
+}
+
+/// Runs the feeder until shutdown, a write error or a format change: reads the
+/// input rings, mixes voice, resamples to the session rate, applies the DSP
+/// chain and hands every chunk, in the session's channel layout, to `write`.
+///
+/// Returns the format to restart with, if any.
+fn run_feeder(
+    state: &mut FeederState,
+    config: &EncoderConfig,
+    running: &AtomicBool,
+    format: SessionFormat,
+    mut write: impl FnMut(&[f32]) -> Result<()>,
+) -> Result<Option<SessionFormat>> {
+    let sample_rate_hz = format.sample_rate_hz;
     let feeder_chunk_frames = config.feeder_chunk_frames.max(1);
+    let mut sample_buffer = Vec::with_capacity(feeder_chunk_frames * INPUT_CHANNELS);
+    let mut resampled_buffer = Vec::with_capacity(feeder_chunk_frames * INPUT_CHANNELS * 2);
+    let mut stereo_buffer = Vec::with_capacity(feeder_chunk_frames * 2 * 2);
+    let mut voice_buffer = Vec::with_capacity(feeder_chunk_frames * VOICE_CHANNELS);
+    let mut input_rate = config.capture_rate_hz.load(Ordering::Relaxed);
+    let mut voice_mixer = state
+        .voice
+        .as_ref()
+        .map(|_| VoiceMixer::new(input_rate, &config.voice_ducking));
+    let mut resampler = Resampler::new(input_rate, sample_rate_hz, INPUT_CHANNELS);
+    let mut dsp = DspChain::new(sample_rate_hz, &config.dsp, config.dsp_controls.clone());
+    let dsp_latency_frames = dsp.latency_frames();
+    if dsp_latency_frames > 0 {
+        info!(
+            "DSP chain latency: {} frames ({:.2} ms)",
+            dsp_latency_frames,
+            dsp_latency_frames as f64 * 1000.0 / f64::from(sample_rate_hz)
+        );
+    }
+
+    let mut ignored_output_rate = 0;
+    while running.load(Ordering::Relaxed) {
+        let requested_rate = config.output_rate_hz.load(Ordering::Relaxed);
+        if requested_rate != 0 && requested_rate != sample_rate_hz {
+            if SUPPORTED_SAMPLE_RATES_HZ.contains(&requested_rate) {
+                return Ok(Some(SessionFormat {
+                    sample_rate_hz: requested_rate,
+                    layout: state
+                        .auto_layout
+                        .as_ref()
+                        .map_or(format.layout, AutoLayout::layout),
+                    ..format
+                }));
+            }
+            if requested_rate != ignored_output_rate {
+                warn!(
+                    "Output stream renegotiated to {} Hz, which AC-3 cannot carry; \
+                     still encoding at {} Hz",
+                    requested_rate, sample_rate_hz
+                );
+                ignored_output_rate = requested_rate;
+            }
+        }
+
+        // A pending chunk was already scanned for bursts by the previous session.
+        let scanned = !state.pending.is_empty();
+        if scanned {
+            sample_buffer.clear();
+            sample_buffer.append(&mut state.pending);
+        } else {
+            // Copy to local buffer so the DSP chain can work in place
+            if !read_input_chunk(&mut state.main, feeder_chunk_frames, &mut sample_buffer) {
+                thread::sleep(Duration::from_micros(250));
+                continue;
+            }
+        }
+
+        let current_rate = config.capture_rate_hz.load(Ordering::Relaxed);
+        if current_rate != 0 && current_rate != input_rate {
+            input_rate = current_rate;
+            resampler = Resampler::new(input_rate, sample_rate_hz, INPUT_CHANNELS);
+            if voice_mixer.is_some() {
+                voice_mixer = Some(VoiceMixer::new(input_rate, &config.voice_ducking));
+            }
+            if resampler.is_passthrough() {
+                info!("Capture rate is {} Hz; resampling disabled", input_rate);
+            } else {
+                info!(
+                    "Capture rate is {} Hz; resampling to {} Hz (latency {} frames)",
+                    input_rate,
+                    sample_rate_hz,
+                    resampler.latency_frames()
+                );
+            }
+        }
+
+        // Bursts must reach the output bit-exact, so look for them before any
+        // processing and hand the chunk to a bitstream session.
+        let bitstream = if scanned {
+            state.bursts.is_active()
+        } else {
+            state.bursts.scan(&sample_buffer, INPUT_CHANNELS)
+        };
+        if bitstream {
+            state.pending.append(&mut sample_buffer);
+            return Ok(Some(SessionFormat {
+                mode: state.bitstream_mode(input_rate, sample_rate_hz),
+                ..format
+            }));
+        }
+
+        // A burst is only confirmed at the next preamble; meanwhile its
+        // periods would be encoded as noise.
+        if state.bursts.is_pending() {
+            sample_buffer.fill(0.0);
+        }
+
+        if let (Some(voice), Some(mixer)) = (state.voice.as_mut(), voice_mixer.as_mut()) {
+            let wanted = sample_buffer.len() / INPUT_CHANNELS * VOICE_CHANNELS;
+            let backlog = voice.slots().saturating_sub(wanted);
+            if backlog > MAX_VOICE_BACKLOG_SAMPLES {
+                let excess = backlog - backlog % VOICE_CHANNELS;
+                if let Ok(stale) = voice.read_chunk(excess) {
+                    stale.commit_all();
+                }
+            }
+
+            voice_buffer.clear();
+            let available = voice.slots().min(wanted);
+            if let Ok(chunk) = voice.read_chunk(available - available % VOICE_CHANNELS) {
+                voice_buffer.extend(chunk);
+            }
+            mixer.process(&mut sample_buffer, &voice_buffer);
+        }
+
+        let encoder_samples = if resampler.is_passthrough() {
+            &mut sample_buffer
+        } else {
+            resampled_buffer.clear();
+            resampler.process(&sample_buffer, &mut resampled_buffer);
+            &mut resampled_buffer
+        };
+        dsp.process(encoder_samples);
+
+        let channel_mask = config.capture_channel_mask.load(Ordering::Relaxed);
+        let next = SessionFormat {
+            sample_rate_hz,
+            layout: state.auto_layout.as_mut().map_or(format.layout, |auto| {
+                auto.update(encoder_samples, sample_rate_hz, channel_mask)
+            }),
+            mode: if state.pcm_bypass.as_mut().is_some_and(|bypass| {
+                bypass.update(encoder_samples, sample_rate_hz, channel_mask) == EncodeLayout::Stereo
+            }) {
+                SessionMode::PcmBypass
+            } else {
+                SessionMode::Ac3
+            },
+        };
+
+        let output_samples =
+            if format.mode == SessionMode::PcmBypass || format.layout == EncodeLayout::Stereo {
+                downmix_to_stereo(encoder_samples, &mut stereo_buffer);
+                &stereo_buffer
+            } else {
+                encoder_samples
+            };
+        if let Err(e) = write(output_samples) {
+            if running.load(Ordering::Relaxed) {
+                return Err(e);
+            }
+            break;
+        }
+
+        // While bypassing, layout changes wait for the next AC-3 session.
+        let restart = next.mode != format.mode
+            || (next.mode == SessionMode::Ac3 && next.layout != format.layout);
+        if restart {
+            return Ok(Some(next));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Runs one ffmpeg process with `format` until shutdown, an error, or a format
+/// change. The feeder state is returned for the next session.
+fn run_encoder_session(
+    state: FeederState,
+    output: &mut Producer<u8>,
+    running: &Arc<AtomicBool>,
+    config: &EncoderConfig,
+    format: SessionFormat,
+) -> Result<(SessionEnd, FeederState)> {
+    let sample_rate_hz = format.sample_rate_hz;
+    let transcode = format.mode == SessionMode::Transcode;
+    if transcode {
+        info!(
+            "Starting FFmpeg subprocess at {} Hz, transcoding IEC61937 input...",
+            sample_rate_hz
+        );
+    } else {
+        info!(
+            "Starting FFmpeg subprocess at {} Hz, {}...",
+            sample_rate_hz,
+            format.layout.as_str()
+        );
+    }
+
+    let ffmpeg_thread_queue_size = config.ffmpeg_thread_queue_size.max(1);
     let ffmpeg_thread_queue_size_arg = ffmpeg_thread_queue_size.to_string();
+    let sample_rate_arg = sample_rate_hz.to_string();
+    let channels_arg = format.layout.channels().to_string();
 
     // Command:
     // ffmpeg -y -f f32le -ar 48000 -ac 6 -i pipe:0 -c:ac3 -b:a 640k -f spdif pipe:1
@@ -105,29 +684,48 @@ pub fn run_encoder_loop_with_config(
     command.args([
         "-y",
         "-probesize",
-        "32",
+        // The spdif demuxer has to see a whole burst (up to 24 KiB for E-AC-3)
+        // to find the codec.
+        if transcode {
+            TRANSCODE_PROBE_SIZE
+        } else {
+            "32"
+        },
         "-analyzeduration",
         "0",
         "-fflags",
         "+nobuffer",
         "-flags",
         "+low_delay",
-        // Input Format
-        "-f",
-        "f32le",
-        "-ar",
-        "48000",
-        "-ac",
-        "6",
+    ]);
+
+    // Input Format
+    if transcode {
+        // Raw IEC61937 words as the player sent them; ffmpeg picks the decoder.
+        command.args(["-f", "spdif"]);
+    } else {
+        command.args([
+            "-f",
+            "f32le",
+            "-ar",
+            sample_rate_arg.as_str(),
+            "-ac",
+            channels_arg.as_str(),
+        ]);
+    }
+    command.args([
         "-thread_queue_size",
         ffmpeg_thread_queue_size_arg.as_str(),
         "-i",
         "pipe:0", // Input
     ]);
 
-    command.args([
-        "-c:a", "ac3", "-b:a", "640k", "-bufsize", "0", "-f", "spdif",
-    ]);
+    command.args(["-c:a", "ac3", "-b:a", "640k", "-bufsize", "0"]);
+    if transcode {
+        // Decoded layouts beyond 5.1 are downmixed by ffmpeg; the rate must match the output.
+        command.args(["-ar", sample_rate_arg.as_str()]);
+    }
+    command.args(["-f", "spdif"]);
 
     // Muxer / Output flags
     command.args([
@@ -167,7 +765,7 @@ pub fn run_encoder_loop_with_config(
     }
 
     let running_feeder = running.clone();
-    let output_capacity = output.slots();
+    let output_capacity = output.buffer().capacity();
     // Keep read chunks small enough to avoid bursty output->playback pressure.
     let mut stdout_read_buffer_size =
         (output_capacity / 8).clamp(MIN_STDOUT_READ_BUFFER_SIZE, MAX_STDOUT_READ_BUFFER_SIZE);
@@ -180,52 +778,54 @@ pub fn run_encoder_loop_with_config(
         stdout_read_buffer_size, output_capacity
     );
 
-    // Spawn Feeder Thread (RingBuffer -> Stdin)
-    let feeder_handle = thread::spawn(move || -> Result<()> {
-        let mut byte_buffer = Vec::with_capacity(feeder_chunk_frames * INPUT_CHANNELS * 4);
-
-        while running_feeder.load(Ordering::Relaxed) {
-            // Read from RingBuffer
-            // We want to move data as fast as possible.
-            let readable_samples = input.slots();
-            if readable_samples > 0 {
-                if let Ok(chunk) =
-                    input.read_chunk(readable_samples.min(feeder_chunk_frames * INPUT_CHANNELS))
-                {
-                    // Copy to local buffer
+    // Spawn Feeder Thread (RingBuffer -> DSP -> Stdin, or RingBuffer -> Stdin
+    // for a bitstream being transcoded)
+    let feeder_config = config.clone();
+    // Set by the feeder before it closes ffmpeg's stdin to switch formats.
+    let restarting = Arc::new(AtomicBool::new(false));
+    let feeder_restarting = restarting.clone();
+    let feeder_handle = thread::spawn(move || {
+        let mut state = state;
+        let mut write_stdin = |bytes: &[u8]| -> Result<()> {
+            // Write to stdin
+            stdin
+                .write_all(bytes)
+                .context("Failed to write to ffmpeg stdin")?;
+            // Force flush to prevent buffering in the pipe
+            stdin.flush().context("Failed to flush ffmpeg stdin")
+        };
+        let result = if format.mode == SessionMode::Transcode {
+            forward_bitstream(
+                &mut state,
+                &feeder_config,
+                &running_feeder,
+                format,
+                &mut write_stdin,
+            )
+        } else {
+            let mut byte_buffer =
+                Vec::with_capacity(feeder_config.feeder_chunk_frames.max(1) * INPUT_CHANNELS * 4);
+            run_feeder(
+                &mut state,
+                &feeder_config,
+                &running_feeder,
+                format,
+                |samples| {
                     byte_buffer.clear();
-                    for sample in chunk {
+                    for sample in samples {
                         // Convert f32 to bytes (le)
                         byte_buffer.extend_from_slice(&sample.to_le_bytes());
                     }
-
-                    // Write to stdin
-                    if let Err(e) = stdin.write_all(&byte_buffer) {
-                        if running_feeder.load(Ordering::Relaxed) {
-                            return Err(
-                                anyhow::Error::new(e).context("Failed to write to ffmpeg stdin")
-                            );
-                        }
-                        break;
-                    }
-                    // Force flush to prevent buffering in the pipe
-                    if let Err(e) = stdin.flush() {
-                        if running_feeder.load(Ordering::Relaxed) {
-                            return Err(
-                                anyhow::Error::new(e).context("Failed to flush ffmpeg stdin")
-                            );
-                        }
-                        break;
-                    }
-                } else {
-                    thread::sleep(Duration::from_micros(250));
-                }
-            } else {
-                thread::sleep(Duration::from_micros(250));
-            }
+                    write_stdin(&byte_buffer)
+                },
+            )
+        };
+        if matches!(result, Ok(Some(_))) {
+            feeder_restarting.store(true, Ordering::Relaxed);
         }
-
-        Ok(())
+        // Closing stdin lets ffmpeg flush and exit.
+        drop(stdin);
+        (result, state)
     });
 
     // Run Reader Loop (Stdout -> RingBuffer) in this thread
@@ -237,50 +837,14 @@ pub fn run_encoder_loop_with_config(
 
         match stdout.read(&mut read_buffer) {
             Ok(0) => {
-                if running.load(Ordering::Relaxed) {
+                if running.load(Ordering::Relaxed) && !restarting.load(Ordering::Relaxed) {
                     warn!("FFmpeg stdout closed unexpectedly.");
                     reader_error = Some(anyhow!("FFmpeg stdout closed unexpectedly"));
                 }
                 break;
             }
             Ok(n) => {
-                // Write to RingBuffer
-                // We need to write all `n` bytes.
-                let mut bytes_written = 0;
-                let mut abort_due_to_shutdown_backpressure = false;
-
-                while bytes_written < n {
-                    if output.slots() > 0 {
-                        let request = (n - bytes_written).min(output.slots());
-                        match output.write_chunk_uninit(request) {
-                            Ok(chunk) => {
-                                let to_write = chunk.len();
-                                chunk.fill_from_iter(
-                                    read_buffer[bytes_written..bytes_written + to_write]
-                                        .iter()
-                                        .copied(),
-                                );
-                                bytes_written += to_write;
-                            }
-                            Err(_) => {
-                                // Full
-                                if !running.load(Ordering::Relaxed) {
-                                    abort_due_to_shutdown_backpressure = true;
-                                    break;
-                                }
-                                thread::sleep(Duration::from_micros(100));
-                            }
-                        }
-                    } else {
-                        if !running.load(Ordering::Relaxed) {
-                            abort_due_to_shutdown_backpressure = true;
-                            break;
-                        }
-                        thread::sleep(Duration::from_micros(250));
-                    }
-                }
-
-                if abort_due_to_shutdown_backpressure {
+                if !write_output(output, &read_buffer[..n], running) {
                     break;
                 }
             }
@@ -296,11 +860,18 @@ pub fn run_encoder_loop_with_config(
     }
 
     info!("Stopping ffmpeg...");
+    let mut returned_state = None;
+    let mut restart = None;
     match feeder_handle.join() {
-        Ok(Ok(())) => {}
-        Ok(Err(e)) => {
-            if reader_error.is_none() {
-                reader_error = Some(e);
+        Ok((result, state)) => {
+            returned_state = Some(state);
+            match result {
+                Ok(next) => restart = next,
+                Err(e) => {
+                    if reader_error.is_none() {
+                        reader_error = Some(e);
+                    }
+                }
             }
         }
         Err(e) => {
@@ -336,21 +907,275 @@ pub fn run_encoder_loop_with_config(
         }
     };
 
+    let mut failure = None;
     if running.load(Ordering::Relaxed) {
         if let Some(err) = reader_error {
-            return Err(err);
-        }
-        if forced_kill {
-            return Err(anyhow!(
+            failure = Some(err);
+        } else if forced_kill {
+            failure = Some(anyhow!(
                 "FFmpeg process did not terminate in time and was killed"
             ));
-        }
-        if let Some(status) = child_status {
+        } else if let Some(status) = child_status {
             if !status.success() {
-                return Err(anyhow!("FFmpeg exited with status: {status}"));
+                failure = Some(anyhow!("FFmpeg exited with status: {status}"));
             }
         }
     }
 
-    Ok(())
+    let state = match (failure, returned_state) {
+        (None, Some(state)) => state,
+        // ffmpeg may lack a decoder for the bitstream. Forwarding it would
+        // send a codec the sink cannot play, so mute it instead.
+        (Some(err), Some(mut state)) if transcode => {
+            error!(
+                "Could not transcode the IEC61937 {} input ({:#}); sending pause bursts \
+                 until the input turns back into PCM",
+                state
+                    .bursts
+                    .data_type()
+                    .map_or("unknown", iec61937::data_type_name),
+                err
+            );
+            state.transcode_failed = true;
+            return Ok((
+                SessionEnd::Restart(SessionFormat {
+                    mode: SessionMode::Muted,
+                    ..format
+                }),
+                state,
+            ));
+        }
+        (Some(err), _) => return Err(err),
+        (None, None) => return Err(anyhow!("Encoder feeder thread did not return its input")),
+    };
+    match restart {
+        Some(next) if running.load(Ordering::Relaxed) => Ok((SessionEnd::Restart(next), state)),
+        _ => Ok((SessionEnd::Shutdown, state)),
+    }
+}
+
+/// Runs the feeder without ffmpeg, writing its stereo output to `output` as
+/// S16LE PCM, until shutdown or a format change.
+fn run_pcm_session(
+    mut state: FeederState,
+    output: &mut Producer<u8>,
+    running: &Arc<AtomicBool>,
+    config: &EncoderConfig,
+    format: SessionFormat,
+) -> Result<(SessionEnd, FeederState)> {
+    info!(
+        "Writing stereo PCM at {} Hz (AC-3 bypassed)",
+        format.sample_rate_hz
+    );
+    let mut byte_buffer = Vec::with_capacity(config.feeder_chunk_frames.max(1) * 2 * 2);
+    let restart = run_feeder(&mut state, config, running, format, |samples| {
+        byte_buffer.clear();
+        for sample in samples {
+            byte_buffer.extend_from_slice(&pcm_sample(*sample).to_le_bytes());
+        }
+        write_output(output, &byte_buffer, running);
+        Ok(())
+    })?;
+
+    match restart {
+        Some(next) if running.load(Ordering::Relaxed) => Ok((SessionEnd::Restart(next), state)),
+        _ => Ok((SessionEnd::Shutdown, state)),
+    }
+}
+
+/// Forwards the IEC61937 bursts found on the input's FL/FR channels to `output`
+/// word for word, or writes pause bursts in their place when muted, until the
+/// input turns back into PCM, the bitstream needs another mode, shutdown, or an
+/// output rate change.
+fn run_passthrough_session(
+    mut state: FeederState,
+    output: &mut Producer<u8>,
+    running: &Arc<AtomicBool>,
+    config: &EncoderConfig,
+    format: SessionFormat,
+) -> Result<(SessionEnd, FeederState)> {
+    if format.mode == SessionMode::Muted {
+        info!(
+            "Writing IEC61937 pause bursts at {} Hz",
+            format.sample_rate_hz
+        );
+    } else {
+        info!(
+            "Passing IEC61937 input through at {} Hz",
+            format.sample_rate_hz
+        );
+    }
+    let restart = forward_bitstream(&mut state, config, running, format, |bytes| {
+        write_output(output, bytes, running);
+        Ok(())
+    })?;
+
+    match restart {
+        Some(next) if running.load(Ordering::Relaxed) => Ok((SessionEnd::Restart(next), state)),
+        _ => Ok((SessionEnd::Shutdown, state)),
+    }
+}
+
+/// Hands the FL/FR words of the input (the bursts a player sent, with their
+/// zero stuffing) to `write` as S16LE bytes while the input carries a bitstream
+/// of the kind `format.mode` handles. The words go out at the input rate, which
+/// may differ from the output rate when transcoding. In muted mode, pause
+/// bursts covering the same duration at the output rate are written instead.
+/// Voice input is discarded meanwhile.
+///
+/// Returns the format to continue with: AC-3 encoding once the input is PCM
+/// again, or another bitstream mode or output rate. The chunk that ended the
+/// session is kept in `state.pending`.
+fn forward_bitstream(
+    state: &mut FeederState,
+    config: &EncoderConfig,
+    running: &AtomicBool,
+    format: SessionFormat,
+    mut write: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<Option<SessionFormat>> {
+    let feeder_chunk_frames = config.feeder_chunk_frames.max(1);
+    let mut sample_buffer = std::mem::take(&mut state.pending);
+    // The handed-over chunk was already scanned by the previous session.
+    let mut scanned = !sample_buffer.is_empty();
+    let mut byte_buffer = Vec::with_capacity(feeder_chunk_frames * OUTPUT_FRAME_BYTES_U8);
+    let pause = iec61937::pause_burst(iec61937::PAUSE_BURST_FRAMES as u32);
+    // Output frames owed as pause bursts, scaled by the input rate.
+    let mut pause_debt = 0u64;
+
+    while running.load(Ordering::Relaxed) {
+        if sample_buffer.is_empty()
+            && !read_input_chunk(&mut state.main, feeder_chunk_frames, &mut sample_buffer)
+        {
+            thread::sleep(Duration::from_micros(250));
+            continue;
+        }
+
+        let requested_rate = config.output_rate_hz.load(Ordering::Relaxed);
+        let output_rate = if SUPPORTED_SAMPLE_RATES_HZ.contains(&requested_rate) {
+            requested_rate
+        } else {
+            format.sample_rate_hz
+        };
+        let active = if scanned {
+            scanned = false;
+            state.bursts.is_active()
+        } else {
+            state.bursts.scan(&sample_buffer, INPUT_CHANNELS)
+        };
+        if !active {
+            state.pending.append(&mut sample_buffer);
+            state.transcode_failed = false;
+            return Ok(Some(SessionFormat {
+                sample_rate_hz: output_rate,
+                layout: state
+                    .auto_layout
+                    .as_ref()
+                    .map_or(format.layout, AutoLayout::layout),
+                mode: SessionMode::Ac3,
+            }));
+        }
+        let input_rate = match config.capture_rate_hz.load(Ordering::Relaxed) {
+            0 => output_rate,
+            rate => rate,
+        };
+        let mode = state.bitstream_mode(input_rate, output_rate);
+        if mode != format.mode || output_rate != format.sample_rate_hz {
+            state.pending.append(&mut sample_buffer);
+            return Ok(Some(SessionFormat {
+                sample_rate_hz: output_rate,
+                mode,
+                ..format
+            }));
+        }
+
+        if let Some(voice) = state.voice.as_mut() {
+            if let Ok(stale) = voice.read_chunk(voice.slots()) {
+                stale.commit_all();
+            }
+        }
+
+        byte_buffer.clear();
+        if mode == SessionMode::Muted {
+            let frames = (sample_buffer.len() / INPUT_CHANNELS) as u64;
+            pause_debt += frames * u64::from(output_rate);
+            let burst_debt = u64::from(input_rate) * iec61937::PAUSE_BURST_FRAMES as u64;
+            let bursts = pause_debt / burst_debt;
+            pause_debt -= bursts * burst_debt;
+            for _ in 0..bursts {
+                byte_buffer.extend_from_slice(&pause);
+            }
+        } else {
+            for frame in sample_buffer.chunks_exact(INPUT_CHANNELS) {
+                for sample in &frame[..2] {
+                    byte_buffer.extend_from_slice(&iec61937::sample_to_word(*sample).to_le_bytes());
+                }
+            }
+        }
+        sample_buffer.clear();
+        if let Err(e) = write(&byte_buffer) {
+            if running.load(Ordering::Relaxed) {
+                return Err(e);
+            }
+            break;
+        }
+    }
+
+    Ok(None)
+}
+
+/// Replaces `buffer` with up to `chunk_frames` frames from the main input ring.
+/// Returns `false` if nothing could be read.
+fn read_input_chunk(input: &mut Consumer<f32>, chunk_frames: usize, buffer: &mut Vec<f32>) -> bool {
+    // Read from RingBuffer
+    // We want to move data as fast as possible.
+    let readable_samples = input.slots();
+    if readable_samples == 0 {
+        return false;
+    }
+    let Ok(chunk) = input.read_chunk(readable_samples.min(chunk_frames * INPUT_CHANNELS)) else {
+        return false;
+    };
+    buffer.clear();
+    buffer.extend(chunk);
+    true
+}
+
+/// Converts a float sample to S16, clipping at full scale.
+fn pcm_sample(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16
+}
+
+/// Writes all of `bytes` to the output ring, waiting while it is full.
+/// Returns `false` if shutdown was requested before everything was written.
+fn write_output(output: &mut Producer<u8>, bytes: &[u8], running: &AtomicBool) -> bool {
+    let mut bytes_written = 0;
+    while bytes_written < bytes.len() {
+        if output.slots() > 0 {
+            let request = (bytes.len() - bytes_written).min(output.slots());
+            match output.write_chunk_uninit(request) {
+                Ok(chunk) => {
+                    let to_write = chunk.len();
+                    chunk.fill_from_iter(
+                        bytes[bytes_written..bytes_written + to_write]
+                            .iter()
+                            .copied(),
+                    );
+                    bytes_written += to_write;
+                }
+                Err(_) => {
+                    // Full
+                    if !running.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    thread::sleep(Duration::from_micros(100));
+                }
+            }
+        } else {
+            if !running.load(Ordering::Relaxed) {
+                return false;
+            }
+            thread::sleep(Duration::from_micros(250));
+        }
+    }
+    true
 }
@@ -0,0 +1,353 @@
+use anyhow::{anyhow, Context, Result};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use crate::dsp::{db_to_gain, parse_channel_selection, DSP_CHANNELS};
+
+const DEFAULT_Q: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+/// Filter types understood in Equalizer APO / REW filter files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BiquadKind {
+    Peaking,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+    Notch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiquadSpec {
+    pub kind: BiquadKind,
+    pub freq_hz: f64,
+    pub gain_db: f64,
+    pub q: f64,
+}
+
+/// Filters and preamp applied to one output channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelEq {
+    pub preamp_db: f64,
+    pub filters: Vec<BiquadSpec>,
+}
+
+/// Per-channel EQ bank, indexed like the DSP stream (FL,FR,FC,LFE,SL,SR).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EqConfig {
+    pub channels: [ChannelEq; DSP_CHANNELS],
+}
+
+impl EqConfig {
+    pub fn is_empty(&self) -> bool {
+        self.channels
+            .iter()
+            .all(|channel| channel.filters.is_empty() && channel.preamp_db == 0.0)
+    }
+}
+
+/// Reads and parses an Equalizer APO / REW text filter file.
+pub fn load_eq_config(path: &Path) -> Result<EqConfig> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read EQ config {}", path.display()))?;
+    parse_eq_config(&contents).with_context(|| format!("Invalid EQ config {}", path.display()))
+}
+
+/// Parses the Equalizer APO text format (as also exported by REW).
+///
+/// Supported commands are `Preamp:`, `Filter:`/`Filter N:` and `Channel:`.
+/// `Channel:` selects the channels that following commands apply to; until the
+/// first one, everything applies to all channels. Other lines (REW headers,
+/// `Include:`, device selection...) are ignored.
+pub fn parse_eq_config(contents: &str) -> Result<EqConfig> {
+    let mut config = EqConfig::default();
+    let mut selected = [true; DSP_CHANNELS];
+
+    for (index, raw_line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.split('#').next().unwrap_or_default().trim();
+        let Some((command, rest)) = line.split_once(':') else {
+            continue;
+        };
+        let command = command.trim().to_ascii_lowercase();
+
+        if command == "channel" {
+            selected = parse_channel_selection(rest)
+                .with_context(|| format!("line {line_number}: invalid Channel command"))?;
+        } else if command == "preamp" {
+            let preamp_db = parse_db_value(rest)
+                .with_context(|| format!("line {line_number}: invalid Preamp command"))?;
+            for (channel, _) in config
+                .channels
+                .iter_mut()
+                .zip(selected)
+                .filter(|(_, on)| *on)
+            {
+                channel.preamp_db += preamp_db;
+            }
+        } else if command == "filter" || command.starts_with("filter ") {
+            let Some(filter) = parse_filter(rest)
+                .with_context(|| format!("line {line_number}: invalid Filter command"))?
+            else {
+                continue;
+            };
+            for (channel, _) in config
+                .channels
+                .iter_mut()
+                .zip(selected)
+                .filter(|(_, on)| *on)
+            {
+                channel.filters.push(filter);
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+fn parse_db_value(rest: &str) -> Result<f64> {
+    let value = rest
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("missing value"))?;
+    value
+        .parse::<f64>()
+        .map_err(|_| anyhow!("invalid number '{value}'"))
+}
+
+/// Parses `ON PK Fc 50 Hz Gain -3.0 dB Q 4.00`. Returns `None` for disabled
+/// or `None`-type filters.
+fn parse_filter(rest: &str) -> Result<Option<BiquadSpec>> {
+    let tokens: Vec<&str> = rest.split_whitespace().collect();
+    let mut tokens = tokens.as_slice();
+
+    match tokens.first().map(|t| t.to_ascii_uppercase()).as_deref() {
+        Some("ON") => tokens = &tokens[1..],
+        Some("OFF") => return Ok(None),
+        _ => {}
+    }
+
+    let kind_token = tokens
+        .first()
+        .ok_or_else(|| anyhow!("missing filter type"))?
+        .to_ascii_uppercase();
+    let kind = match kind_token.as_str() {
+        "NONE" => return Ok(None),
+        "PK" | "PEQ" | "MODAL" => BiquadKind::Peaking,
+        "LS" | "LSC" | "LSQ" => BiquadKind::LowShelf,
+        "HS" | "HSC" | "HSQ" => BiquadKind::HighShelf,
+        "LP" | "LPQ" => BiquadKind::LowPass,
+        "HP" | "HPQ" => BiquadKind::HighPass,
+        "NO" => BiquadKind::Notch,
+        other => return Err(anyhow!("unsupported filter type '{other}'")),
+    };
+
+    let mut freq_hz = None;
+    let mut gain_db = 0.0;
+    let mut q = DEFAULT_Q;
+    let mut i = 1;
+    while i < tokens.len() {
+        let key = tokens[i].to_ascii_uppercase();
+        let value = tokens.get(i + 1);
+        let number = || -> Result<f64> {
+            let value = value.ok_or_else(|| anyhow!("missing value after '{key}'"))?;
+            value
+                .parse::<f64>()
+                .map_err(|_| anyhow!("invalid number '{value}' after '{key}'"))
+        };
+        match key.as_str() {
+            "FC" => {
+                freq_hz = Some(number()?);
+                i += 2;
+            }
+            "GAIN" => {
+                gain_db = number()?;
+                i += 2;
+            }
+            "Q" => {
+                q = number()?;
+                i += 2;
+            }
+            // Units and shelf slope variants (e.g. "LSC 12 dB") carry no extra information.
+            _ => i += 1,
+        }
+    }
+
+    let freq_hz = freq_hz.ok_or_else(|| anyhow!("missing 'Fc <freq> Hz'"))?;
+    if !freq_hz.is_finite() || freq_hz <= 0.0 {
+        return Err(anyhow!("filter frequency must be positive, got {freq_hz}"));
+    }
+    if !q.is_finite() || q <= 0.0 {
+        return Err(anyhow!("filter Q must be positive, got {q}"));
+    }
+
+    Ok(Some(BiquadSpec {
+        kind,
+        freq_hz,
+        gain_db,
+        q,
+    }))
+}
+
+/// Normalized biquad coefficients (a0 == 1), RBJ audio EQ cookbook formulas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BiquadCoefficients {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl BiquadCoefficients {
+    pub(crate) fn design(spec: &BiquadSpec, sample_rate: f64) -> Self {
+        // Keep the centre frequency below Nyquist so the design stays stable.
+        let freq = spec.freq_hz.min(sample_rate * 0.49);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * spec.q);
+        let a = 10.0f64.powf(spec.gain_db / 40.0);
+
+        let (b0, b1, b2, a0, a1, a2) = match spec.kind {
+            BiquadKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos_w0,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos_w0,
+                1.0 - alpha / a,
+            ),
+            BiquadKind::LowShelf => {
+                let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a_alpha),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a_alpha),
+                    (a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a_alpha,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    (a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a_alpha,
+                )
+            }
+            BiquadKind::HighShelf => {
+                let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a_alpha),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a_alpha),
+                    (a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a_alpha,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    (a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a_alpha,
+                )
+            }
+            BiquadKind::LowPass => (
+                (1.0 - cos_w0) / 2.0,
+                1.0 - cos_w0,
+                (1.0 - cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            BiquadKind::HighPass => (
+                (1.0 + cos_w0) / 2.0,
+                -(1.0 + cos_w0),
+                (1.0 + cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            BiquadKind::Notch => (
+                1.0,
+                -2.0 * cos_w0,
+                1.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+/// Transposed direct form II biquad. State is kept in f64 so low-frequency
+/// room-correction filters do not accumulate f32 rounding noise.
+#[derive(Debug, Clone)]
+pub(crate) struct Biquad {
+    coefficients: BiquadCoefficients,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    pub(crate) fn new(coefficients: BiquadCoefficients) -> Self {
+        Self {
+            coefficients,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn process(&mut self, input: f64) -> f64 {
+        let c = &self.coefficients;
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChannelFilters {
+    preamp: f32,
+    biquads: Vec<Biquad>,
+}
+
+/// Runs the per-channel biquad bank over interleaved 6-channel frames.
+#[derive(Debug, Clone)]
+pub struct Equalizer {
+    channels: [ChannelFilters; DSP_CHANNELS],
+}
+
+impl Equalizer {
+    pub fn new(config: &EqConfig, sample_rate: u32) -> Self {
+        let sample_rate = f64::from(sample_rate.max(1));
+        Self {
+            channels: std::array::from_fn(|ch| {
+                let channel = &config.channels[ch];
+                ChannelFilters {
+                    preamp: db_to_gain(channel.preamp_db as f32),
+                    biquads: channel
+                        .filters
+                        .iter()
+                        .map(|spec| Biquad::new(BiquadCoefficients::design(spec, sample_rate)))
+                        .collect(),
+                }
+            }),
+        }
+    }
+
+    /// Processes interleaved 6-channel frames in place. A trailing partial frame is
+    /// left untouched.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_exact_mut(DSP_CHANNELS) {
+            for (sample, channel) in frame.iter_mut().zip(self.channels.iter_mut()) {
+                if channel.biquads.is_empty() && channel.preamp == 1.0 {
+                    continue;
+                }
+                let mut value = f64::from(*sample * channel.preamp);
+                for biquad in &mut channel.biquads {
+                    value = biquad.process(value);
+                }
+                *sample = value as f32;
+            }
+        }
+    }
+}
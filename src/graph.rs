@@ -0,0 +1,625 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::default_sink::{self, DEFAULT_SINK_KEY};
+use crate::volume_guard::NodeVolume;
+
+/// Target value that asks for automatic sink discovery.
+pub const AUTO_TARGET: &str = "auto";
+/// Target value that makes the playback stream follow the default sink.
+pub const DEFAULT_TARGET: &str = "default";
+
+pub fn is_auto_target(target: &str) -> bool {
+    target.trim().eq_ignore_ascii_case(AUTO_TARGET)
+}
+
+pub fn is_default_target(target: &str) -> bool {
+    target.trim().eq_ignore_ascii_case(DEFAULT_TARGET)
+}
+
+/// Kind of a PipeWire registry global, as far as pw-ac3-live cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Node,
+    Port,
+    Link,
+    Device,
+    Metadata,
+    Other,
+}
+
+impl ObjectKind {
+    /// Maps a registry type string (e.g. `PipeWire:Interface:Node`).
+    pub fn from_type_name(type_name: &str) -> Self {
+        match type_name.rsplit(':').next().unwrap_or_default() {
+            "Node" => Self::Node,
+            "Port" => Self::Port,
+            "Link" => Self::Link,
+            "Device" => Self::Device,
+            "Metadata" => Self::Metadata,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// One registry global with a copy of its properties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphObject {
+    pub id: u32,
+    pub kind: ObjectKind,
+    pub props: BTreeMap<String, String>,
+}
+
+impl GraphObject {
+    pub fn new<K, V>(id: u32, kind: ObjectKind, props: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            id,
+            kind,
+            props: props
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        }
+    }
+
+    pub fn prop(&self, key: &str) -> Option<&str> {
+        self.props.get(key).map(String::as_str)
+    }
+}
+
+/// Mirror of the PipeWire registry, fed by the `global`/`global_remove` events.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    objects: BTreeMap<u32, GraphObject>,
+    /// IEC958 codecs found in each node's EnumFormat params.
+    enum_codecs: BTreeMap<u32, Vec<String>>,
+    /// Entries of the `default` metadata object, by (subject, key).
+    metadata: BTreeMap<(u32, String), String>,
+    /// EnumProfile params of each device.
+    device_profiles: BTreeMap<u32, Vec<DeviceProfile>>,
+    /// Profile param (active profile) of each device.
+    active_profiles: BTreeMap<u32, DeviceProfile>,
+    /// Volume entries of the Props param of each bound node.
+    node_volumes: BTreeMap<u32, NodeVolume>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, object: GraphObject) {
+        self.objects.insert(object.id, object);
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<GraphObject> {
+        self.enum_codecs.remove(&id);
+        self.metadata.retain(|(subject, _), _| *subject != id);
+        self.device_profiles.remove(&id);
+        self.active_profiles.remove(&id);
+        self.node_volumes.remove(&id);
+        self.objects.remove(&id)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&GraphObject> {
+        self.objects.get(&id)
+    }
+
+    pub fn objects(&self, kind: ObjectKind) -> impl Iterator<Item = &GraphObject> {
+        self.objects
+            .values()
+            .filter(move |object| object.kind == kind)
+    }
+
+    /// Node whose `node.name` is `name`.
+    pub fn node_by_name(&self, name: &str) -> Option<&GraphObject> {
+        self.objects(ObjectKind::Node)
+            .find(|node| node.prop("node.name") == Some(name))
+    }
+
+    /// Records IEC958 codecs found in one of a node's EnumFormat params.
+    pub fn add_enum_codecs(&mut self, node_id: u32, codecs: &[String]) {
+        let known = self.enum_codecs.entry(node_id).or_default();
+        for codec in codecs {
+            if !known.contains(codec) {
+                known.push(codec.clone());
+            }
+        }
+    }
+
+    /// Applies a `default` metadata property event. A `None` key clears every
+    /// entry of `subject`, a `None` value clears one entry.
+    pub fn set_metadata(&mut self, subject: u32, key: Option<&str>, value: Option<&str>) {
+        match (key, value) {
+            (None, _) => self.metadata.retain(|(entry, _), _| *entry != subject),
+            (Some(key), None) => {
+                self.metadata.remove(&(subject, key.to_string()));
+            }
+            (Some(key), Some(value)) => {
+                self.metadata
+                    .insert((subject, key.to_string()), value.to_string());
+            }
+        }
+    }
+
+    pub fn metadata(&self, subject: u32, key: &str) -> Option<&str> {
+        self.metadata
+            .get(&(subject, key.to_string()))
+            .map(String::as_str)
+    }
+
+    /// Playback streams of other applications that should be moved to our
+    /// sink: not already moved, and not pinned to a target by their app.
+    pub fn streams_to_move(&self, moved: &[u32]) -> Vec<u32> {
+        self.objects(ObjectKind::Node)
+            .filter(|node| node.prop("media.class") == Some("Stream/Output/Audio"))
+            .filter(|node| !moved.contains(&node.id))
+            .filter(|node| {
+                !node
+                    .prop("node.name")
+                    .is_some_and(|name| name.starts_with("pw-ac3-live"))
+                    && node.prop("application.name") != Some("pw-ac3-live")
+            })
+            .filter(|node| {
+                ["target.object", "node.target"]
+                    .iter()
+                    .all(|key| node.prop(key).is_none())
+            })
+            .map(|node| node.id)
+            .collect()
+    }
+
+    /// Every `Audio/Sink` node except our own, with its passthrough capabilities.
+    pub fn sink_candidates(&self) -> Vec<SinkCandidate> {
+        self.objects(ObjectKind::Node)
+            .filter(|node| node.prop("media.class") == Some("Audio/Sink"))
+            .filter(|node| {
+                !node
+                    .prop("node.name")
+                    .is_some_and(|name| name.starts_with("pw-ac3-live"))
+            })
+            .map(|node| {
+                let mut codecs = node
+                    .prop("iec958.codecs")
+                    .map(parse_iec958_codecs)
+                    .unwrap_or_default();
+                for codec in self.enum_codecs.get(&node.id).into_iter().flatten() {
+                    if !codecs.contains(codec) {
+                        codecs.push(codec.clone());
+                    }
+                }
+                SinkCandidate {
+                    id: node.id,
+                    name: node.prop("node.name").unwrap_or_default().to_string(),
+                    description: node
+                        .prop("node.description")
+                        .or_else(|| node.prop("node.nick"))
+                        .unwrap_or_default()
+                        .to_string(),
+                    digital: is_digital_sink(node),
+                    codecs,
+                }
+            })
+            .collect()
+    }
+
+    /// Node name of the sink in `default.audio.sink`, if that is a sink in the
+    /// graph and not one of ours (the takeover makes our input the default).
+    pub fn default_sink_target(&self) -> Option<String> {
+        let name = default_sink::parse_sink_name(self.metadata(0, DEFAULT_SINK_KEY)?)?;
+        if name.starts_with("pw-ac3-live") {
+            return None;
+        }
+        self.node_by_name(&name)
+            .filter(|node| node.prop("media.class") == Some("Audio/Sink"))
+            .map(|_| name)
+    }
+
+    /// Resolves a `--target` value (node name or numeric ID) to a node ID.
+    pub fn resolve_node(&self, target: &str) -> Option<u32> {
+        let target = target.trim();
+        if let Ok(id) = target.parse::<u32>() {
+            return self
+                .get(id)
+                .filter(|object| object.kind == ObjectKind::Node)
+                .map(|node| node.id);
+        }
+        self.node_by_name(target).map(|node| node.id)
+    }
+
+    /// Non-monitor port of `node_id` for `channel` in `direction` (`in`/`out`).
+    pub fn channel_port(&self, node_id: u32, direction: &str, channel: &str) -> Option<u32> {
+        let node_id = node_id.to_string();
+        self.objects(ObjectKind::Port)
+            .filter(|port| port.prop("node.id") == Some(node_id.as_str()))
+            .filter(|port| port.prop("port.direction") == Some(direction))
+            .filter(|port| port.prop("port.monitor") != Some("true"))
+            .find(|port| port.prop("audio.channel") == Some(channel))
+            .map(|port| port.id)
+    }
+
+    /// Links from `output_node` to `input_node`, as (link ID, ports).
+    pub fn links_between(&self, output_node: u32, input_node: u32) -> Vec<(u32, PortPair)> {
+        let output_node = output_node.to_string();
+        let input_node = input_node.to_string();
+        self.objects(ObjectKind::Link)
+            .filter(|link| link.prop("link.output.node") == Some(output_node.as_str()))
+            .filter(|link| link.prop("link.input.node") == Some(input_node.as_str()))
+            .filter_map(|link| {
+                Some((
+                    link.id,
+                    PortPair {
+                        output_port: link.prop("link.output.port")?.parse().ok()?,
+                        input_port: link.prop("link.input.port")?.parse().ok()?,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Audio sinks `output_node` is linked to, whoever created the links.
+    pub fn linked_sinks(&self, output_node: u32) -> Vec<u32> {
+        let output_node = output_node.to_string();
+        let mut sinks: Vec<u32> = self
+            .objects(ObjectKind::Link)
+            .filter(|link| link.prop("link.output.node") == Some(output_node.as_str()))
+            .filter_map(|link| link.prop("link.input.node")?.parse().ok())
+            .filter(|id| {
+                self.get(*id)
+                    .is_some_and(|node| node.prop("media.class") == Some("Audio/Sink"))
+            })
+            .collect();
+        sinks.sort_unstable();
+        sinks.dedup();
+        sinks
+    }
+
+    /// `(link ID, input node)` of every link leaving `node_id`.
+    fn links_from(&self, node_id: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let node_id = node_id.to_string();
+        self.objects(ObjectKind::Link)
+            .filter(move |link| link.prop("link.output.node") == Some(node_id.as_str()))
+            .filter_map(|link| Some((link.id, link.prop("link.input.node")?.parse().ok()?)))
+    }
+
+    /// Shortest chain of nodes linked from `from` to `to`, both included.
+    /// Whatever enters a node is assumed to leave it again (filters, loopbacks,
+    /// sink monitors).
+    pub fn route_path(&self, from: u32, to: u32) -> Option<Vec<u32>> {
+        let mut previous = BTreeMap::new();
+        let mut visited = BTreeSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            for (_, next) in self.links_from(node) {
+                if !visited.insert(next) {
+                    continue;
+                }
+                previous.insert(next, node);
+                if next == to {
+                    let mut path = vec![to];
+                    while let Some(&node) = previous.get(path.last()?) {
+                        path.push(node);
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                queue.push_back(next);
+            }
+        }
+        None
+    }
+
+    /// Links leaving `output_node` that start a route back into `input_node`.
+    pub fn feedback_links(&self, output_node: u32, input_node: u32) -> Vec<u32> {
+        self.links_from(output_node)
+            .filter(|(_, next)| *next == input_node || self.route_path(*next, input_node).is_some())
+            .map(|(link, _)| link)
+            .collect()
+    }
+
+    /// Same-channel links from `output_node` to `input_node` for each of
+    /// `channels`, or `None` while a port is still missing.
+    pub fn plan_links(
+        &self,
+        output_node: u32,
+        input_node: u32,
+        channels: &[&str],
+    ) -> Option<Vec<PortPair>> {
+        channels
+            .iter()
+            .map(|channel| {
+                Some(PortPair {
+                    output_port: self.channel_port(output_node, "out", channel)?,
+                    input_port: self.channel_port(input_node, "in", channel)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Compares the links from `output_node` to `input_node` with `plan`. `owned`
+    /// are the links created by us, which may not have reached the registry yet.
+    /// Links to other nodes (recorders, monitors) are left alone.
+    pub fn reconcile_links(
+        &self,
+        output_node: u32,
+        input_node: u32,
+        plan: &[PortPair],
+        owned: &[PortPair],
+    ) -> LinkActions {
+        let existing = self.links_between(output_node, input_node);
+        let mut actions = LinkActions::default();
+        for pair in plan {
+            if !existing.iter().any(|(_, link)| link == pair) && !owned.contains(pair) {
+                actions.create.push(*pair);
+            }
+        }
+        actions.drop_owned = owned
+            .iter()
+            .filter(|pair| !plan.contains(pair))
+            .copied()
+            .collect();
+        actions.destroy = existing
+            .iter()
+            .filter(|(_, link)| !plan.contains(link) && !owned.contains(link))
+            .map(|(id, _)| *id)
+            .collect();
+        actions.verified = actions.destroy.is_empty()
+            && plan
+                .iter()
+                .all(|pair| existing.iter().any(|(_, link)| link == pair))
+            && existing.iter().all(|(_, link)| plan.contains(link));
+        actions
+    }
+
+    /// Records one EnumProfile entry of `device_id`, replacing one with the same index.
+    pub fn add_device_profile(&mut self, device_id: u32, profile: DeviceProfile) {
+        let profiles = self.device_profiles.entry(device_id).or_default();
+        profiles.retain(|known| known.index != profile.index);
+        profiles.push(profile);
+    }
+
+    pub fn set_active_profile(&mut self, device_id: u32, profile: DeviceProfile) {
+        self.active_profiles.insert(device_id, profile);
+    }
+
+    pub fn device_profiles(&self, device_id: u32) -> &[DeviceProfile] {
+        self.device_profiles
+            .get(&device_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn active_profile(&self, device_id: u32) -> Option<&DeviceProfile> {
+        self.active_profiles.get(&device_id)
+    }
+
+    /// Device of a sink given as `--target`: its `device.id`, or the card named
+    /// after it while the sink is missing (its profile may be inactive).
+    pub fn device_for_sink(&self, target: &str) -> Option<u32> {
+        let from_node = self
+            .resolve_node(target)
+            .and_then(|id| self.get(id))
+            .and_then(|node| node.prop("device.id"))
+            .and_then(|id| id.parse().ok())
+            .filter(|id| self.get(*id).is_some_and(|o| o.kind == ObjectKind::Device));
+        from_node.or_else(|| {
+            let card = card_name_for_sink(target)?;
+            self.objects(ObjectKind::Device)
+                .find(|device| device.prop("device.name") == Some(card.as_str()))
+                .map(|device| device.id)
+        })
+    }
+
+    /// Device whose ALSA card is `card` (index or card ID).
+    pub fn device_for_alsa_card(&self, card: &str) -> Option<u32> {
+        self.objects(ObjectKind::Device)
+            .find(|device| {
+                ["api.alsa.card", "api.alsa.card.id"]
+                    .iter()
+                    .any(|key| device.prop(key) == Some(card))
+            })
+            .map(|device| device.id)
+    }
+
+    /// Merges the volume entries of a Props param reported by `node_id`.
+    pub fn update_node_volume(&mut self, node_id: u32, update: NodeVolume) {
+        self.node_volumes.entry(node_id).or_default().merge(update);
+    }
+
+    pub fn node_volume(&self, node_id: u32) -> Option<&NodeVolume> {
+        self.node_volumes.get(&node_id)
+    }
+
+    pub fn target_identity(&self, node_id: u32) -> Option<TargetIdentity> {
+        let node = self.get(node_id)?;
+        let identity = TargetIdentity {
+            node_name: node.prop("node.name").map(str::to_string),
+            object_path: node.prop("object.path").map(str::to_string),
+        };
+        (identity.node_name.is_some() || identity.object_path.is_some()).then_some(identity)
+    }
+
+    /// Node matching `identity`: by `node.name`, else by `object.path`.
+    pub fn find_target(&self, identity: &TargetIdentity) -> Option<u32> {
+        let by_prop = |key: &str, value: &Option<String>| {
+            let value = value.as_deref()?;
+            self.objects(ObjectKind::Node)
+                .find(|node| node.prop(key) == Some(value))
+                .map(|node| node.id)
+        };
+        by_prop("node.name", &identity.node_name)
+            .or_else(|| by_prop("object.path", &identity.object_path))
+    }
+}
+
+/// An `Audio/Sink` that could carry the IEC 61937 stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkCandidate {
+    pub id: u32,
+    pub name: String,
+    pub description: String,
+    /// HDMI, DisplayPort or S/PDIF output.
+    pub digital: bool,
+    /// IEC958 codecs advertised by `iec958.codecs` or EnumFormat (e.g. `PCM`, `AC3`).
+    pub codecs: Vec<String>,
+}
+
+impl SinkCandidate {
+    pub fn supports_ac3(&self) -> bool {
+        self.codecs.iter().any(|codec| codec == "AC3")
+    }
+
+    /// Short capability summary for `list-targets`.
+    pub fn capabilities(&self) -> String {
+        let kind = if self.digital { "digital" } else { "analog" };
+        if self.codecs.is_empty() {
+            format!("{kind}, codecs unknown")
+        } else {
+            format!("{kind}, codecs {}", self.codecs.join(" "))
+        }
+    }
+}
+
+/// Parses an `iec958.codecs` property, e.g. `[ PCM AC3 DTS ]` or `["PCM","AC3"]`.
+pub fn parse_iec958_codecs(value: &str) -> Vec<String> {
+    let mut codecs = Vec::new();
+    for codec in value
+        .split(|c: char| c.is_whitespace() || matches!(c, '[' | ']' | ',' | '"'))
+        .filter(|codec| !codec.is_empty())
+    {
+        let codec = codec.to_ascii_uppercase();
+        if !codecs.contains(&codec) {
+            codecs.push(codec);
+        }
+    }
+    codecs
+}
+
+fn is_digital_sink(node: &GraphObject) -> bool {
+    [
+        "node.name",
+        "api.alsa.path",
+        "device.profile.name",
+        "api.alsa.pcm.name",
+    ]
+    .iter()
+    .filter_map(|key| node.prop(key))
+    .any(|value| {
+        let value = value.to_ascii_lowercase();
+        ["hdmi", "iec958", "spdif", "displayport"]
+            .iter()
+            .any(|hint| value.contains(hint))
+    })
+}
+
+/// Picks the sink to send AC-3 to: an AC-3-capable digital sink first, then any
+/// digital sink (its codecs may just not be advertised). Ties go to the lowest ID.
+pub fn pick_ac3_target(candidates: &[SinkCandidate]) -> Option<&SinkCandidate> {
+    candidates
+        .iter()
+        .filter(|candidate| candidate.digital)
+        .min_by_key(|candidate| (!candidate.supports_ac3(), candidate.id))
+}
+
+/// One port-to-port link, by port ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PortPair {
+    pub output_port: u32,
+    pub input_port: u32,
+}
+
+/// What it takes to leave exactly the planned links between two nodes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkActions {
+    /// Planned links missing from the graph and not created yet.
+    pub create: Vec<PortPair>,
+    /// Links we created that are no longer in the plan.
+    pub drop_owned: Vec<PortPair>,
+    /// IDs of other links between the two nodes (e.g. FL->FR).
+    pub destroy: Vec<u32>,
+    /// Exactly the planned links connect the two nodes.
+    pub verified: bool,
+}
+
+/// One entry of a device's EnumProfile/Profile params.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceProfile {
+    pub index: i32,
+    pub name: String,
+    pub description: String,
+    pub priority: i32,
+    /// False only when the device reports the profile as unavailable
+    /// (e.g. no HDMI cable); unknown counts as available.
+    pub available: bool,
+}
+
+/// Card profile an output path needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileRequest {
+    /// Release the card, so direct ALSA can open the PCM.
+    Off,
+    /// A profile providing this output, e.g. `hdmi-stereo` (as in `output:hdmi-stereo+input:...`).
+    Output(String),
+}
+
+impl ProfileRequest {
+    pub fn matches(&self, profile: &DeviceProfile) -> bool {
+        match self {
+            Self::Off => profile.name == "off",
+            Self::Output(output) => profile
+                .name
+                .split('+')
+                .any(|part| part.strip_prefix("output:") == Some(output.as_str())),
+        }
+    }
+}
+
+/// Profile that satisfies `request`: the active one if it already does,
+/// otherwise the available match with the highest priority.
+pub fn pick_profile<'a>(
+    profiles: &'a [DeviceProfile],
+    active: Option<&'a DeviceProfile>,
+    request: &ProfileRequest,
+) -> Option<&'a DeviceProfile> {
+    if let Some(active) = active.filter(|active| request.matches(active)) {
+        return Some(active);
+    }
+    profiles
+        .iter()
+        .filter(|profile| profile.available && request.matches(profile))
+        .max_by_key(|profile| (profile.priority, std::cmp::Reverse(profile.index)))
+}
+
+/// Card a PipeWire ALSA sink belongs to, from the naming scheme
+/// `alsa_output.<card>.<profile>` -> `alsa_card.<card>`.
+pub fn card_name_for_sink(sink_name: &str) -> Option<String> {
+    let (card, _) = sink_name.strip_prefix("alsa_output.")?.rsplit_once('.')?;
+    Some(format!("alsa_card.{card}"))
+}
+
+/// Output profile a PipeWire ALSA sink needs, e.g. `hdmi-stereo-extra1`.
+pub fn output_profile_for_sink(sink_name: &str) -> Option<String> {
+    sink_name
+        .strip_prefix("alsa_output.")?
+        .rsplit_once('.')
+        .map(|(_, output)| output.to_string())
+}
+
+/// ALSA card of a PCM device string (`hw:0,8`, `hw:CARD=1,DEV=3`, `hdmi:2`).
+pub fn alsa_card_of(device: &str) -> Option<String> {
+    let (_, args) = device.split_once(':')?;
+    let first = args.split(',').next()?.trim();
+    let card = first.strip_prefix("CARD=").unwrap_or(first);
+    (!card.is_empty()).then(|| card.to_string())
+}
+
+/// What finds an explicit target again after it was replugged (HDMI unplug,
+/// TV power cycle) and came back with a new node ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetIdentity {
+    pub node_name: Option<String>,
+    pub object_path: Option<String>,
+}
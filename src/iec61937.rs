@@ -0,0 +1,262 @@
+/// First burst preamble word (Pa), as it appears in the S16LE stream: `72 f8`.
+pub const PREAMBLE_PA: u16 = 0xF872;
+/// Second burst preamble word (Pb): `1f 4e`.
+pub const PREAMBLE_PB: u16 = 0x4E1F;
+/// Burst-info (Pc) data type of a pause burst.
+pub const DATA_TYPE_PAUSE: u16 = 0x0003;
+/// Bytes per IEC 60958 frame carrying the bursts (2 × 16-bit).
+pub const FRAME_BYTES: usize = 4;
+/// Repetition period of a pause burst in IEC 60958 frames; the burst is
+/// exactly Pa/Pb/Pc/Pd plus one 32-bit payload word.
+pub const PAUSE_BURST_FRAMES: usize = 3;
+
+/// Builds one pause burst announcing a gap of `gap_frames` frames.
+///
+/// Receivers keep their decoder locked across pause bursts instead of
+/// dropping out (or briefly playing noise) when the AC-3 stream stops.
+pub fn pause_burst(gap_frames: u32) -> [u8; PAUSE_BURST_FRAMES * FRAME_BYTES] {
+    let gap = gap_frames.min(u32::from(u16::MAX)) as u16;
+    // Pd is the payload length in bits.
+    let words = [PREAMBLE_PA, PREAMBLE_PB, DATA_TYPE_PAUSE, 32, gap, 0];
+    let mut burst = [0u8; PAUSE_BURST_FRAMES * FRAME_BYTES];
+    for (bytes, word) in burst.chunks_exact_mut(2).zip(words) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    burst
+}
+
+/// Fills a gap of `duration_ms` at `sample_rate_hz` with back-to-back pause
+/// bursts, returned as raw S16LE stream bytes.
+pub fn pause_gap(sample_rate_hz: u32, duration_ms: u32) -> Vec<u8> {
+    let gap_frames = (u64::from(sample_rate_hz) * u64::from(duration_ms) / 1000) as usize;
+    let bursts = gap_frames.div_ceil(PAUSE_BURST_FRAMES).max(1);
+    let burst = pause_burst((bursts * PAUSE_BURST_FRAMES) as u32);
+    burst.repeat(bursts)
+}
+
+/// Returns the byte offset of the first burst preamble (Pa Pb) in `bytes`,
+/// checking only 16-bit aligned positions.
+pub fn find_preamble(bytes: &[u8]) -> Option<usize> {
+    let pa = PREAMBLE_PA.to_le_bytes();
+    let pb = PREAMBLE_PB.to_le_bytes();
+    bytes
+        .windows(4)
+        .step_by(2)
+        .position(|window| window[..2] == pa && window[2..] == pb)
+        .map(|index| index * 2)
+}
+
+/// Burst-info (Pc) data type of a null burst.
+pub const DATA_TYPE_NULL: u16 = 0x0000;
+/// Burst-info (Pc) data type of an AC-3 burst.
+pub const DATA_TYPE_AC3: u16 = 0x0001;
+/// DTS type I/II/III (512/1024/2048 samples per frame) and DTS-HD bursts.
+pub const DATA_TYPE_DTS_I: u16 = 0x000B;
+pub const DATA_TYPE_DTS_II: u16 = 0x000C;
+pub const DATA_TYPE_DTS_III: u16 = 0x000D;
+pub const DATA_TYPE_DTS_HD: u16 = 0x0011;
+/// E-AC-3 (Dolby Digital Plus) bursts.
+pub const DATA_TYPE_EAC3: u16 = 0x0015;
+/// MAT bursts, which carry Dolby TrueHD.
+pub const DATA_TYPE_MAT: u16 = 0x0016;
+/// Frames without a valid burst after which the input is treated as PCM
+/// again; twice the longest 2ch repetition period (E-AC-3, 6144 frames).
+pub const PASSTHROUGH_TIMEOUT_FRAMES: usize = 2 * 6144;
+
+/// Repetition period of a data type in IEC 60958 frames, which bounds the
+/// burst length; `None` for pause/null bursts and unknown types.
+pub fn repetition_period_frames(data_type: u16) -> Option<usize> {
+    match data_type {
+        DATA_TYPE_AC3 => Some(1536),
+        DATA_TYPE_DTS_I => Some(512),
+        DATA_TYPE_DTS_II => Some(1024),
+        DATA_TYPE_DTS_III => Some(2048),
+        DATA_TYPE_DTS_HD => Some(8192),
+        DATA_TYPE_EAC3 => Some(6144),
+        DATA_TYPE_MAT => Some(15360),
+        _ => None,
+    }
+}
+
+/// Payload length in 16-bit words announced by Pd, or `None` if it cannot
+/// fit the type's repetition period. Pd counts bytes for E-AC-3, MAT and
+/// DTS-HD and bits for the others.
+pub fn payload_words(data_type: u16, pd: u16) -> Option<usize> {
+    let pd = usize::from(pd);
+    let words = match data_type {
+        DATA_TYPE_EAC3 | DATA_TYPE_MAT | DATA_TYPE_DTS_HD => pd.div_ceil(2),
+        _ => pd.div_ceil(16),
+    };
+    let max_words = match data_type {
+        DATA_TYPE_NULL | DATA_TYPE_PAUSE => PAUSE_BURST_FRAMES * 2 - 4,
+        other => repetition_period_frames(other).unwrap_or(PASSTHROUGH_TIMEOUT_FRAMES) * 2 - 4,
+    };
+    (words <= max_words).then_some(words)
+}
+
+/// Codec name of a burst data type, for the log.
+pub fn data_type_name(data_type: u16) -> &'static str {
+    match data_type {
+        DATA_TYPE_AC3 => "AC-3",
+        DATA_TYPE_DTS_I | DATA_TYPE_DTS_II | DATA_TYPE_DTS_III => "DTS",
+        DATA_TYPE_DTS_HD => "DTS-HD",
+        DATA_TYPE_EAC3 => "E-AC-3",
+        DATA_TYPE_MAT => "TrueHD",
+        _ => "unknown",
+    }
+}
+
+/// Whether a burst must be decoded and re-encoded to reach an AC-3-only sink.
+pub fn needs_transcoding(data_type: u16) -> bool {
+    !matches!(data_type, DATA_TYPE_AC3 | DATA_TYPE_NULL | DATA_TYPE_PAUSE)
+}
+
+/// Whether ffmpeg's `spdif` demuxer can read this data type from a 2ch
+/// carrier. MAT (TrueHD) and DTS-HD need an 8ch high-bit-rate carrier.
+pub fn can_transcode(data_type: u16) -> bool {
+    matches!(
+        data_type,
+        DATA_TYPE_AC3 | DATA_TYPE_EAC3 | DATA_TYPE_DTS_I | DATA_TYPE_DTS_II | DATA_TYPE_DTS_III
+    )
+}
+
+/// Recovers the 16-bit word a player sent from a sample the graph converted to
+/// float (`word / 32768`).
+pub fn sample_to_word(sample: f32) -> u16 {
+    (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16 as u16
+}
+
+/// Where the detector is within the current candidate burst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BurstPhase {
+    /// Looking for a Pa/Pb preamble.
+    Search,
+    /// Pa/Pb seen; Pc (FL) and Pd (FR) come next.
+    Header,
+    /// Skipping `remaining` payload words of a burst of `data_type`.
+    Payload { data_type: u16, remaining: usize },
+    /// Payload done; only zero words may follow until the next preamble.
+    Stuffing { data_type: u16 },
+}
+
+/// Recognises IEC 61937 bursts carried in the FL/FR channels of the capture
+/// stream, e.g. from a player in passthrough mode, so they can be forwarded
+/// instead of being encoded as (very loud) PCM.
+///
+/// A Pa/Pb match alone can happen in loud PCM. A burst only counts once its
+/// Pd fits the data type, its payload is followed by zero stuffing only, and
+/// the next preamble starts right after that.
+#[derive(Debug, Clone)]
+pub struct BurstDetector {
+    active: bool,
+    frames_since_burst: usize,
+    data_type: Option<u16>,
+    phase: BurstPhase,
+    /// Pc of the burst whose header is being read.
+    header_data_type: Option<u16>,
+}
+
+impl Default for BurstDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BurstDetector {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            frames_since_burst: 0,
+            data_type: None,
+            phase: BurstPhase::Search,
+            header_data_type: None,
+        }
+    }
+
+    /// Whether the input currently carries a bitstream.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Whether a candidate burst is being checked: a preamble was seen but
+    /// the burst is neither confirmed nor refuted yet.
+    pub fn is_pending(&self) -> bool {
+        self.phase != BurstPhase::Search
+    }
+
+    /// Pc data type (low 5 bits) of the last valid burst, ignoring null and
+    /// pause bursts.
+    pub fn data_type(&self) -> Option<u16> {
+        self.data_type
+    }
+
+    /// Scans interleaved `samples` with `channels` channels (FL/FR first) and
+    /// returns whether the input carries a bitstream. It becomes active with
+    /// the first valid burst and stays so until [`PASSTHROUGH_TIMEOUT_FRAMES`]
+    /// frames pass without one.
+    pub fn scan(&mut self, samples: &[f32], channels: usize) -> bool {
+        let channels = channels.max(2);
+        for frame in samples.chunks_exact(channels) {
+            self.frames_since_burst = self.frames_since_burst.saturating_add(1);
+            self.push_frame(sample_to_word(frame[0]), sample_to_word(frame[1]));
+        }
+        if self.frames_since_burst >= PASSTHROUGH_TIMEOUT_FRAMES {
+            self.active = false;
+            self.data_type = None;
+        }
+        self.active
+    }
+
+    fn push_frame(&mut self, left: u16, right: u16) {
+        if left == PREAMBLE_PA && right == PREAMBLE_PB {
+            if let BurstPhase::Stuffing { data_type } = self.phase {
+                self.accept(data_type);
+            }
+            self.phase = BurstPhase::Header;
+            return;
+        }
+        self.push_word(left, true);
+        self.push_word(right, false);
+    }
+
+    fn push_word(&mut self, word: u16, is_left: bool) {
+        self.phase = match self.phase {
+            BurstPhase::Search => BurstPhase::Search,
+            BurstPhase::Header if is_left => {
+                self.header_data_type = Some(word & 0x1f);
+                BurstPhase::Header
+            }
+            BurstPhase::Header => {
+                let data_type = self.header_data_type.take().unwrap_or_default();
+                match payload_words(data_type, word) {
+                    Some(0) => BurstPhase::Stuffing { data_type },
+                    Some(remaining) => BurstPhase::Payload {
+                        data_type,
+                        remaining,
+                    },
+                    None => BurstPhase::Search,
+                }
+            }
+            BurstPhase::Payload {
+                data_type,
+                remaining,
+            } => match remaining - 1 {
+                0 => BurstPhase::Stuffing { data_type },
+                remaining => BurstPhase::Payload {
+                    data_type,
+                    remaining,
+                },
+            },
+            BurstPhase::Stuffing { .. } if word != 0 => BurstPhase::Search,
+            phase @ BurstPhase::Stuffing { .. } => phase,
+        };
+    }
+
+    fn accept(&mut self, data_type: u16) {
+        self.active = true;
+        self.frames_since_burst = 0;
+        if !matches!(data_type, DATA_TYPE_NULL | DATA_TYPE_PAUSE) {
+            self.data_type = Some(data_type);
+        }
+    }
+}
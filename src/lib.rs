@@ -1,3 +1,17 @@
+pub mod acmod;
 pub mod alsa_control;
+pub mod compressor;
+pub mod control;
+pub mod convolution;
+pub mod default_sink;
+pub mod dialogue;
+pub mod dsp;
 pub mod encoder;
+pub mod equalizer;
+pub mod graph;
+pub mod iec61937;
 pub mod pipewire_client;
+pub mod resampler;
+pub mod voice;
+pub mod volume_guard;
+pub mod wav;
@@ -1,14 +1,26 @@
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use log::{info, warn};
 use rtrb::RingBuffer;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::thread;
 
 // Module declarations
+use pw_ac3_live::acmod::{AutoLayoutConfig, ChannelLayoutMode, ALL_CHANNELS_MASK};
+use pw_ac3_live::alsa_control::{self, Iec958StatusSwitch};
+use pw_ac3_live::compressor::NightModePreset;
+use pw_ac3_live::control;
+use pw_ac3_live::convolution;
+use pw_ac3_live::default_sink;
+use pw_ac3_live::dialogue::DialogueEnhance;
+use pw_ac3_live::dsp::{DspConfig, DspControls};
 use pw_ac3_live::encoder;
+use pw_ac3_live::equalizer;
 use pw_ac3_live::pipewire_client;
+use pw_ac3_live::voice::{VoiceDuckingConfig, VOICE_CHANNELS};
+use pw_ac3_live::volume_guard::VolumeGuardMode;
 
 /// AC-3 Real-time Encoder for PipeWire
 ///
@@ -17,7 +29,8 @@ use pw_ac3_live::pipewire_client;
 #[command(version, about, long_about = None)]
 struct Args {
     /// Playback target:
-    /// - PipeWire mode: Node ID or node name
+    /// - PipeWire mode: Node ID, node name, `auto` for the best AC-3 capable HDMI/IEC958 sink,
+    ///   or `default` to follow the desktop's default sink
     /// - --alsa-direct mode: ALSA device (e.g. hw:0,8)
     #[arg(short, long)]
     target: Option<String>,
@@ -58,6 +71,53 @@ struct Args {
     #[arg(long, default_value = "64/48000")]
     latency: String,
 
+    /// AC-3 encode and IEC61937 output rate: 48000, 44100 or 32000 (also 44.1k, 32k).
+    /// Capture input at any other rate is resampled to it.
+    #[arg(long, default_value = "48000", value_parser = encoder::parse_sample_rate)]
+    rate: u32,
+
+    /// Channel layout of the pw-ac3-live-input sink: 5.1 or 7.1. A 7.1 input is
+    /// downmixed to 5.1 before encoding.
+    #[arg(long, default_value = "5.1")]
+    input_layout: pipewire_client::InputLayout,
+
+    /// Sample format the capture stream asks PipeWire for first: f32, s16, s24,
+    /// s24-32, s32 or f64. s16 keeps IEC 61937 input from passthrough players
+    /// bit-exact; the other decodable formats are still accepted.
+    #[arg(long, default_value = "f32")]
+    capture_format: pipewire_client::CaptureSampleFormat,
+
+    /// Gain applied to the 7.1 side pair (SL/SR) when folding into the 5.1 surrounds
+    #[arg(long, default_value_t = std::f32::consts::FRAC_1_SQRT_2)]
+    downmix_side_gain: f32,
+
+    /// Gain applied to the 7.1 back pair (RL/RR) when folding into the 5.1 surrounds
+    #[arg(long, default_value_t = std::f32::consts::FRAC_1_SQRT_2)]
+    downmix_back_gain: f32,
+
+    /// Channel layout of the AC-3 stream: 5.1, 2.0 or auto. Auto encodes 2.0
+    /// while the source only feeds FL/FR and 5.1 otherwise.
+    #[arg(long, default_value = "5.1")]
+    channel_layout: ChannelLayoutMode,
+
+    /// In auto layout mode, also treat connected but silent FC/LFE/SL/SR as stereo.
+    /// Pass `--auto-layout-activity false` to only follow the negotiated channels.
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    auto_layout_activity: bool,
+
+    /// In auto layout mode, how long (ms) the source must look stereo before 2.0 is encoded
+    #[arg(long, default_value_t = 4000)]
+    auto_layout_hold_ms: u32,
+
+    /// Send plain stereo PCM instead of AC-3 while only FL/FR carry signal.
+    /// In --alsa-direct mode the IEC958 status is switched to audio meanwhile.
+    #[arg(long, default_value_t = false)]
+    pcm_bypass: bool,
+
+    /// How long (ms) FC/LFE/SL/SR must stay silent before --pcm-bypass kicks in
+    #[arg(long, default_value_t = 5000)]
+    pcm_bypass_hold_ms: u32,
+
     /// FFmpeg input thread queue size
     #[arg(long, default_value_t = 128)]
     ffmpeg_thread_queue_size: usize,
@@ -65,11 +125,132 @@ struct Args {
     /// Number of interleaved frames pushed to FFmpeg per write
     #[arg(long, default_value_t = 128)]
     ffmpeg_chunk_frames: usize,
+
+    /// Night-mode dynamic range compression preset: off, light or night.
+    /// Can be changed at runtime through --control-file.
+    #[arg(long, default_value = "off")]
+    night_mode: NightModePreset,
+
+    /// Dialogue enhancement level: off, low or high. Boosts the speech band in the
+    /// center channel and ducks the other speakers while dialogue is detected.
+    /// Can be changed at runtime through --control-file.
+    #[arg(long, default_value = "off")]
+    dialogue_enhance: DialogueEnhance,
+
+    /// Per-channel parametric EQ file in Equalizer APO / REW text format,
+    /// applied before encoding (e.g. room correction).
+    #[arg(long)]
+    eq_config: Option<PathBuf>,
+
+    /// FIR room-correction filter as CHANNELS=impulse.wav (e.g. L=left.wav, SUB=sub.wav,
+    /// all=six_channel.wav). Repeat for several channels.
+    #[arg(long = "fir", value_name = "CHANNELS=WAV")]
+    fir: Vec<String>,
+
+    /// FIR convolution partition size in frames (power of two). Sets the added latency.
+    #[arg(long, default_value_t = convolution::DEFAULT_FIR_PARTITION_FRAMES)]
+    fir_partition_frames: usize,
+
+    /// Create a second stereo sink (pw-ac3-live-voice) for voice chat. It is mixed into
+    /// the center/fronts and ducks the main 5.1 input while someone is talking.
+    #[arg(long)]
+    voice_sink: bool,
+
+    /// Attenuation of the main input while voice chat is active, in dB
+    #[arg(long, default_value_t = 12.0)]
+    voice_duck_db: f32,
+
+    /// Voice ducking attack time in milliseconds
+    #[arg(long, default_value_t = 20.0)]
+    voice_duck_attack_ms: f32,
+
+    /// Voice ducking release time in milliseconds
+    #[arg(long, default_value_t = 600.0)]
+    voice_duck_release_ms: f32,
+
+    /// Runtime control file, re-read whenever it changes
+    /// (e.g. `echo "night-mode = night" > <file>`).
+    #[arg(long)]
+    control_file: Option<PathBuf>,
+
+    /// Make `pw-ac3-live-input` the default sink and move playback streams to it;
+    /// the previous default is restored on exit
+    #[arg(long, default_value_t = false)]
+    take_default_sink: bool,
+
+    /// State file remembering the default sink replaced by --take-default-sink
+    /// [default: $XDG_STATE_HOME/pw-ac3-live/default-sink.state]
+    #[arg(long)]
+    default_sink_state: Option<PathBuf>,
+
+    /// Switch the target's card profile for the output (`off` with --alsa-direct,
+    /// the HDMI profile of the target sink otherwise); the original is restored on exit
+    #[arg(long, default_value_t = false)]
+    manage_card_profile: bool,
+
+    /// What to do when the target sink or the playback stream leaves 100% volume
+    /// (which turns the AC-3 stream into noise): off, warn or force (reset to 100%).
+    #[arg(long, default_value = "warn")]
+    volume_guard: VolumeGuardMode,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List PipeWire sinks usable as `--target`, with their IEC958 capabilities
+    ListTargets,
+    /// Restore the default sink after a --take-default-sink run that did not exit cleanly
+    RestoreDefaultSink,
+}
+
+fn list_targets() -> Result<()> {
+    let candidates = pipewire_client::list_sink_candidates()?;
+    let preferred = pw_ac3_live::graph::pick_ac3_target(&candidates).map(|sink| sink.id);
+    if candidates.is_empty() {
+        println!("No PipeWire audio sinks found.");
+    }
+    for sink in &candidates {
+        println!(
+            "{} {:>4}  {}  [{}]{}",
+            if Some(sink.id) == preferred { "*" } else { " " },
+            sink.id,
+            sink.name,
+            sink.capabilities(),
+            if sink.description.is_empty() {
+                String::new()
+            } else {
+                format!("  {}", sink.description)
+            }
+        );
+    }
+    if preferred.is_some() {
+        println!("(* = picked by --target auto)");
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     let args = Args::parse();
+    let default_sink_state = args
+        .default_sink_state
+        .clone()
+        .unwrap_or_else(default_sink::default_state_path);
+    match args.command {
+        Some(Command::ListTargets) => return list_targets(),
+        Some(Command::RestoreDefaultSink) => {
+            if !pipewire_client::restore_default_sink(&default_sink_state)? {
+                println!(
+                    "No default sink takeover to undo ({}).",
+                    default_sink_state.display()
+                );
+            }
+            return Ok(());
+        }
+        None => {}
+    }
     let target = args
         .target
         .as_deref()
@@ -97,10 +278,39 @@ fn main() -> Result<()> {
         args.output_buffer_size.unwrap_or(args.buffer_size)
     );
     info!("PipeWire node latency: {}", args.latency);
+    info!("Encoder/output rate: {} Hz", args.rate);
+    info!("Input layout: {}", args.input_layout.as_str());
+    info!("Capture format: {}", args.capture_format.as_str());
+    info!("AC-3 channel layout: {}", args.channel_layout.as_str());
+    if args.pcm_bypass {
+        info!(
+            "PCM bypass: enabled after {} ms of stereo-only input",
+            args.pcm_bypass_hold_ms
+        );
+        info!("PCM bypass: PipeWire output stays raw S16LE (no IEC958 passthrough format)");
+    }
     info!(
         "FFmpeg queue/chunk: {} / {}",
         args.ffmpeg_thread_queue_size, args.ffmpeg_chunk_frames
     );
+    if args.take_default_sink {
+        info!(
+            "Default sink takeover: enabled (state file {})",
+            default_sink_state.display()
+        );
+    }
+    if args.manage_card_profile {
+        info!("Card profile management: enabled");
+    }
+    info!("Volume guard: {}", args.volume_guard.as_str());
+    info!("Night mode: {}", args.night_mode.as_str());
+    info!("Dialogue enhancement: {}", args.dialogue_enhance.as_str());
+    if args.voice_sink {
+        info!(
+            "Voice chat sink: enabled (duck={} dB, attack={} ms, release={} ms)",
+            args.voice_duck_db, args.voice_duck_attack_ms, args.voice_duck_release_ms
+        );
+    }
     if args.stdout {
         info!("Output mode: stdout");
     } else if args.alsa_direct {
@@ -124,6 +334,24 @@ fn main() -> Result<()> {
             "--alsa-direct requires --target <alsa-device>, e.g. --target hw:0,8"
         ));
     }
+    if args.alsa_direct
+        && target
+            .as_deref()
+            .is_some_and(pw_ac3_live::graph::is_auto_target)
+    {
+        return Err(anyhow!(
+            "--target auto selects a PipeWire sink; --alsa-direct needs an ALSA device"
+        ));
+    }
+    if args.alsa_direct
+        && target
+            .as_deref()
+            .is_some_and(pw_ac3_live::graph::is_default_target)
+    {
+        return Err(anyhow!(
+            "--target default follows a PipeWire sink; --alsa-direct needs an ALSA device"
+        ));
+    }
     if args.alsa_direct && alsa_iec_card.is_none() {
         return Err(anyhow!(
             "--alsa-direct requires --alsa-iec-card <card-id>, e.g. --alsa-iec-card 0"
@@ -137,14 +365,17 @@ fn main() -> Result<()> {
 
     // In direct ALSA mode, apply the same best-effort IEC958/mixer setup
     // that was previously done by the launcher script.
-    let _direct_alsa_hw_guard = if args.alsa_direct {
-        Some(pw_ac3_live::alsa_control::DirectAlsaHardwareGuard::setup(
-            alsa_iec_card
-                .clone()
-                .ok_or_else(|| anyhow!("--alsa-direct requires --alsa-iec-card"))?,
-            alsa_iec_index
-                .clone()
-                .ok_or_else(|| anyhow!("--alsa-direct requires --alsa-iec-index"))?,
+    let direct_alsa_hw_guard = if args.alsa_direct {
+        Some(Arc::new(
+            pw_ac3_live::alsa_control::DirectAlsaHardwareGuard::setup(
+                alsa_iec_card
+                    .clone()
+                    .ok_or_else(|| anyhow!("--alsa-direct requires --alsa-iec-card"))?,
+                alsa_iec_index
+                    .clone()
+                    .ok_or_else(|| anyhow!("--alsa-direct requires --alsa-iec-index"))?,
+                args.rate,
+            ),
         ))
     } else {
         None
@@ -161,6 +392,14 @@ fn main() -> Result<()> {
     let capacity_samples = args.buffer_size * 6;
     let (input_producer, input_consumer) = RingBuffer::<f32>::new(capacity_samples);
 
+    // Voice: optional second capture stream -> Encoder (interleaved stereo f32)
+    let (voice_producer, voice_consumer) = if args.voice_sink {
+        let (producer, consumer) = RingBuffer::<f32>::new(args.buffer_size * VOICE_CHANNELS);
+        (Some(producer), Some(consumer))
+    } else {
+        (None, None)
+    };
+
     // Output: Encoder -> Playback (u8 bytes for IEC61937 stream)
     // AC-3 frames are small, but IEC61937 frames match the PCM rate.
     // Allocating enough for output buffering.
@@ -177,14 +416,90 @@ fn main() -> Result<()> {
     .context("Error setting Ctrl-C handler")?;
 
     // 3. Spawn Encoder Thread
+    let eq = match args.eq_config.as_deref() {
+        Some(path) => {
+            let eq = equalizer::load_eq_config(path)?;
+            info!(
+                "EQ loaded from {}: {} filter(s) per channel (FL/FR/FC/LFE/SL/SR)",
+                path.display(),
+                eq.channels
+                    .iter()
+                    .map(|channel| channel.filters.len().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            );
+            Some(eq)
+        }
+        None => None,
+    };
+    let fir = if args.fir.is_empty() {
+        None
+    } else {
+        let fir = convolution::load_fir_config(&args.fir, args.fir_partition_frames, args.rate)?;
+        info!(
+            "FIR convolution enabled on {} channel(s): partition={} frames, added latency={:.2} ms",
+            fir.filters.iter().filter(|filter| filter.is_some()).count(),
+            fir.partition_frames,
+            fir.partition_frames as f64 * 1000.0 / f64::from(args.rate)
+        );
+        Some(fir)
+    };
+    let dsp_controls = Arc::new(DspControls::new(args.night_mode, args.dialogue_enhance));
+    let _control_watcher = args.control_file.clone().map(|path| {
+        control::spawn_control_file_watcher(path, dsp_controls.clone(), running.clone())
+    });
+
+    // Capture rate follows the PipeWire graph; the encoder resamples to its own rate.
+    let capture_rate_hz = Arc::new(AtomicU32::new(args.rate));
+    // Output rate as negotiated by the PipeWire playback stream; ffmpeg follows it.
+    let output_rate_hz = Arc::new(AtomicU32::new(0));
+    // 5.1 channels the negotiated capture format feeds; drives the auto layout.
+    let capture_channel_mask = Arc::new(AtomicU32::new(ALL_CHANNELS_MASK));
+    // The encoder asks for the IEC958 audio flag whenever it starts or stops
+    // bypassing AC-3, and waits until the follower has applied it.
+    let iec958_status = Arc::new(Iec958StatusSwitch::new());
+    let iec958_status_follower = match &direct_alsa_hw_guard {
+        Some(guard) if args.pcm_bypass => Some(alsa_control::spawn_iec958_status_follower(
+            guard.clone(),
+            iec958_status.clone(),
+        )),
+        _ => None,
+    };
+
     let encoder_running = running.clone();
     let encoder_config = encoder::EncoderConfig {
         ffmpeg_thread_queue_size: args.ffmpeg_thread_queue_size,
         feeder_chunk_frames: args.ffmpeg_chunk_frames,
+        dsp: DspConfig { eq, fir },
+        dsp_controls,
+        voice_ducking: VoiceDuckingConfig {
+            duck_db: args.voice_duck_db,
+            attack_ms: args.voice_duck_attack_ms,
+            release_ms: args.voice_duck_release_ms,
+        },
+        capture_rate_hz: capture_rate_hz.clone(),
+        sample_rate_hz: args.rate,
+        output_rate_hz: output_rate_hz.clone(),
+        channel_layout: args.channel_layout,
+        auto_layout: AutoLayoutConfig {
+            use_activity: args.auto_layout_activity,
+            hold_ms: args.auto_layout_hold_ms,
+        },
+        capture_channel_mask: capture_channel_mask.clone(),
+        pcm_bypass: encoder::PcmBypassConfig {
+            enabled: args.pcm_bypass,
+            hold_ms: args.pcm_bypass_hold_ms,
+        },
+        iec958_status: iec958_status_follower
+            .is_some()
+            .then(|| iec958_status.clone()),
+        output_latency: pipewire_client::alsa_output_latency(args.alsa_latency_us),
+        ..Default::default()
     };
     let encoder_handle = thread::spawn(move || {
         encoder::run_encoder_loop_with_config(
             input_consumer,
+            voice_consumer,
             output_producer,
             encoder_running,
             encoder_config,
@@ -195,6 +510,20 @@ fn main() -> Result<()> {
     // logic to connect to PipeWire...
     let pipewire_config = pipewire_client::PipewireConfig {
         node_latency: args.latency,
+        capture_rate_hz,
+        sample_rate_hz: args.rate,
+        input_layout: args.input_layout,
+        capture_format: args.capture_format,
+        downmix: pipewire_client::SurroundDownmix {
+            side_gain: args.downmix_side_gain,
+            back_gain: args.downmix_back_gain,
+        },
+        output_rate_hz,
+        capture_channel_mask,
+        default_sink_takeover: args.take_default_sink.then_some(default_sink_state),
+        manage_card_profile: args.manage_card_profile,
+        volume_guard: args.volume_guard,
+        iec958_format: !args.pcm_bypass,
     };
     let (pipewire_target, output_mode) = if args.alsa_direct {
         let device = target
@@ -214,6 +543,7 @@ fn main() -> Result<()> {
     };
     let pipewire_result = pipewire_client::run_pipewire_loop_with_config(
         input_producer,
+        voice_producer,
         output_consumer,
         pipewire_target,
         output_mode,
@@ -223,11 +553,17 @@ fn main() -> Result<()> {
 
     // Always request shutdown and join the encoder thread, even if PipeWire init failed.
     running.store(false, Ordering::SeqCst);
+    // An encoder waiting on a status switch must not wait for the follower now.
+    iec958_status.close();
 
     let encoder_result = match encoder_handle.join() {
         Ok(result) => result,
         Err(e) => Err(anyhow!("Encoder thread panicked: {e:?}")),
     };
+    // The follower holds a guard reference; join it so the guard's restore runs.
+    if let Some(follower) = iec958_status_follower {
+        let _ = follower.join();
+    }
 
     if let Err(e) = pipewire_result {
         if let Err(encoder_err) = encoder_result {
@@ -1,19 +1,32 @@
 use anyhow::{anyhow, Context, Result};
-use log::info;
+use log::{info, warn};
 use pipewire as pw;
 use pipewire::main_loop::MainLoop;
 use pipewire::properties::properties;
-use pipewire::spa::param::audio::{AudioFormat, AudioInfoRaw};
+use pipewire::spa::param::audio::{AudioFormat, AudioInfoRaw, AudioInfoRawFlags};
 use pipewire::spa::utils::Direction;
 use pipewire::stream::{StreamFlags, StreamRef};
 use rtrb::{Consumer, Producer};
 
+use crate::default_sink::{
+    self, TakeoverState, CONFIGURED_DEFAULT_SINK_KEY, INPUT_NODE_NAME, TARGET_OBJECT_KEY,
+};
+use crate::encoder::SUPPORTED_SAMPLE_RATES_HZ;
+use crate::graph::{
+    self, DeviceProfile, Graph, GraphObject, ObjectKind, PortPair, ProfileRequest, SinkCandidate,
+    TargetIdentity,
+};
+use crate::volume_guard::{NodeVolume, VolumeGuardMode};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
 use std::mem::size_of;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 #[cfg(target_os = "linux")]
 use std::{
     ffi::{CStr, CString},
@@ -21,22 +34,396 @@ use std::{
 };
 
 const INPUT_CHANNELS: usize = 6;
+/// Widest layout the virtual sink can advertise (7.1).
+const MAX_CAPTURE_CHANNELS: usize = 8;
+const VOICE_CHANNELS: usize = 2;
 const OUTPUT_CHANNELS: usize = 2;
-const SAMPLE_RATE: &str = "48000";
 const SAMPLE_RATE_HZ: u32 = 48_000;
 const STDOUT_READ_BUFFER_SIZE: usize = 4096;
 const OUTPUT_FRAME_BYTES: usize = OUTPUT_CHANNELS * size_of::<i16>();
 const DEFAULT_ALSA_LATENCY_US: u32 = 60_000;
+/// Name of the playback stream node.
+const PLAYBACK_NODE_NAME: &str = "pw-ac3-live-output";
+/// Name of the voice-chat sink node.
+const VOICE_NODE_NAME: &str = "pw-ac3-live-voice";
+const PLAYBACK_LINK_CHANNELS: [&str; OUTPUT_CHANNELS] = ["FL", "FR"];
 
 #[derive(Debug, Clone)]
 pub struct PipewireConfig {
     pub node_latency: String,
+    /// Updated with the rate negotiated by the capture stream, which follows the
+    /// graph instead of forcing 48 kHz. Shared with the encoder's resampler.
+    pub capture_rate_hz: Arc<AtomicU32>,
+    /// Rate of the encoded IEC61937 output (32000, 44100 or 48000).
+    pub sample_rate_hz: u32,
+    /// Channel layout advertised by the `pw-ac3-live-input` sink.
+    pub input_layout: InputLayout,
+    /// Sample format the capture stream asks for first; every other format
+    /// the capture parsers decode is offered after it.
+    pub capture_format: CaptureSampleFormat,
+    /// Coefficients used to fold a 7.1 input down to 5.1.
+    pub downmix: SurroundDownmix,
+    /// Updated with the rate negotiated by the PipeWire playback stream. Shared
+    /// with the encoder, which restarts ffmpeg when it changes.
+    pub output_rate_hz: Arc<AtomicU32>,
+    /// Updated with the 5.1 channels (bit per FL,FR,FC,LFE,SL,SR) the negotiated
+    /// capture format feeds. Shared with the encoder's automatic layout mode.
+    pub capture_channel_mask: Arc<AtomicU32>,
+    /// State file of the default sink takeover; `None` leaves the default sink
+    /// and other streams alone.
+    pub default_sink_takeover: Option<PathBuf>,
+    /// Switch the target's card to the profile the output mode needs (`off`
+    /// for direct ALSA) and restore the original profile on exit.
+    pub manage_card_profile: bool,
+    /// Reaction to the target sink or the playback stream leaving unity gain.
+    pub volume_guard: VolumeGuardMode,
+    /// Offer an IEC958 AC-3 format before raw S16LE on the playback stream.
+    /// Off with PCM bypass, whose PCM must not carry the non-audio bit.
+    pub iec958_format: bool,
 }
 
 impl Default for PipewireConfig {
     fn default() -> Self {
         Self {
             node_latency: "64/48000".to_string(),
+            capture_rate_hz: Arc::new(AtomicU32::new(SAMPLE_RATE_HZ)),
+            sample_rate_hz: SAMPLE_RATE_HZ,
+            input_layout: InputLayout::default(),
+            capture_format: CaptureSampleFormat::default(),
+            downmix: SurroundDownmix::default(),
+            output_rate_hz: Arc::new(AtomicU32::new(0)),
+            capture_channel_mask: Arc::new(AtomicU32::new((1 << INPUT_CHANNELS) - 1)),
+            default_sink_takeover: None,
+            manage_card_profile: false,
+            volume_guard: VolumeGuardMode::default(),
+            iec958_format: true,
+        }
+    }
+}
+
+/// Channel layout of the virtual sink. AC-3 carries at most 5.1, so a 7.1 sink
+/// is folded down before encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputLayout {
+    #[default]
+    Surround51,
+    Surround71,
+}
+
+impl InputLayout {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Surround51 => "5.1",
+            Self::Surround71 => "7.1",
+        }
+    }
+
+    pub fn channels(self) -> usize {
+        match self {
+            Self::Surround51 => INPUT_CHANNELS,
+            Self::Surround71 => MAX_CAPTURE_CHANNELS,
+        }
+    }
+
+    /// SPA channel positions of the sink, in port order.
+    fn positions(self) -> &'static [u32] {
+        const SURROUND_51: [u32; INPUT_CHANNELS] = [
+            libspa::sys::SPA_AUDIO_CHANNEL_FL,
+            libspa::sys::SPA_AUDIO_CHANNEL_FR,
+            libspa::sys::SPA_AUDIO_CHANNEL_FC,
+            libspa::sys::SPA_AUDIO_CHANNEL_LFE,
+            libspa::sys::SPA_AUDIO_CHANNEL_SL,
+            libspa::sys::SPA_AUDIO_CHANNEL_SR,
+        ];
+        const SURROUND_71: [u32; MAX_CAPTURE_CHANNELS] = [
+            libspa::sys::SPA_AUDIO_CHANNEL_FL,
+            libspa::sys::SPA_AUDIO_CHANNEL_FR,
+            libspa::sys::SPA_AUDIO_CHANNEL_FC,
+            libspa::sys::SPA_AUDIO_CHANNEL_LFE,
+            libspa::sys::SPA_AUDIO_CHANNEL_RL,
+            libspa::sys::SPA_AUDIO_CHANNEL_RR,
+            libspa::sys::SPA_AUDIO_CHANNEL_SL,
+            libspa::sys::SPA_AUDIO_CHANNEL_SR,
+        ];
+        match self {
+            Self::Surround51 => &SURROUND_51,
+            Self::Surround71 => &SURROUND_71,
+        }
+    }
+
+    fn audio_position(self) -> &'static str {
+        match self {
+            Self::Surround51 => "FL,FR,FC,LFE,SL,SR",
+            Self::Surround71 => "FL,FR,FC,LFE,RL,RR,SL,SR",
+        }
+    }
+}
+
+impl std::str::FromStr for InputLayout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "5.1" => Ok(Self::Surround51),
+            "7.1" => Ok(Self::Surround71),
+            other => Err(format!(
+                "invalid input layout '{other}' (expected 5.1 or 7.1)"
+            )),
+        }
+    }
+}
+
+/// Capture sample format preference. PipeWire converts to the first format
+/// of the capture EnumFormat it can provide, so the preferred one wins unless
+/// the peer restricts the choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptureSampleFormat {
+    #[default]
+    F32,
+    S16,
+    S24,
+    S24In32,
+    S32,
+    F64,
+}
+
+impl CaptureSampleFormat {
+    /// Every preference, in the order offered after the preferred one.
+    const ALL: [Self; 6] = [
+        Self::F32,
+        Self::S32,
+        Self::S24In32,
+        Self::S24,
+        Self::S16,
+        Self::F64,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::F32 => "f32",
+            Self::S16 => "s16",
+            Self::S24 => "s24",
+            Self::S24In32 => "s24-32",
+            Self::S32 => "s32",
+            Self::F64 => "f64",
+        }
+    }
+
+    /// The interleaved little-endian, interleaved big-endian and planar
+    /// (native-endian) variants of this format.
+    fn audio_formats(self) -> [AudioFormat; 3] {
+        match self {
+            Self::F32 => [AudioFormat::F32LE, AudioFormat::F32BE, AudioFormat::F32P],
+            Self::S16 => [AudioFormat::S16LE, AudioFormat::S16BE, AudioFormat::S16P],
+            Self::S24 => [AudioFormat::S24LE, AudioFormat::S24BE, AudioFormat::S24P],
+            Self::S24In32 => [
+                AudioFormat::S24_32LE,
+                AudioFormat::S24_32BE,
+                AudioFormat::S24_32P,
+            ],
+            Self::S32 => [AudioFormat::S32LE, AudioFormat::S32BE, AudioFormat::S32P],
+            Self::F64 => [AudioFormat::F64LE, AudioFormat::F64BE, AudioFormat::F64P],
+        }
+    }
+}
+
+impl std::str::FromStr for CaptureSampleFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "f32" => Ok(Self::F32),
+            "s16" => Ok(Self::S16),
+            "s24" => Ok(Self::S24),
+            "s24-32" => Ok(Self::S24In32),
+            "s32" => Ok(Self::S32),
+            "f64" => Ok(Self::F64),
+            other => Err(format!(
+                "invalid capture format '{other}' (expected f32, s16, s24, s24-32, s32 or f64)"
+            )),
+        }
+    }
+}
+
+/// Linear gains applied when folding the 7.1 back (RL/RR) and side (SL/SR)
+/// pairs into the 5.1 surrounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurroundDownmix {
+    pub side_gain: f32,
+    pub back_gain: f32,
+}
+
+impl Default for SurroundDownmix {
+    fn default() -> Self {
+        // -3 dB each keeps a signal panned between side and back at roughly
+        // constant power.
+        Self {
+            side_gain: std::f32::consts::FRAC_1_SQRT_2,
+            back_gain: std::f32::consts::FRAC_1_SQRT_2,
+        }
+    }
+}
+
+/// Folds interleaved 7.1 (FL,FR,FC,LFE,RL,RR,SL,SR) into 5.1 (FL,FR,FC,LFE,SL,SR),
+/// replacing the contents of `out`.
+fn downmix_71_into(input: &[f32], downmix: &SurroundDownmix, out: &mut Vec<f32>) {
+    out.clear();
+    out.reserve(input.len() / MAX_CAPTURE_CHANNELS * INPUT_CHANNELS);
+    for frame in input.chunks_exact(MAX_CAPTURE_CHANNELS) {
+        out.extend_from_slice(&frame[..4]);
+        out.push(frame[4] * downmix.back_gain + frame[6] * downmix.side_gain);
+        out.push(frame[5] * downmix.back_gain + frame[7] * downmix.side_gain);
+    }
+}
+
+/// Maximum number of positions in a negotiated SPA channel map.
+const MAX_SOURCE_CHANNELS: usize = 64;
+
+/// Destination slot (in the sink layout) of every negotiated source channel.
+///
+/// Built from the channel positions of the negotiated Format, so a stream whose
+/// channel order differs from ours (e.g. quad FL,FR,RL,RR) still lands on the
+/// right speakers. Without positions, channels are mapped by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ChannelMap {
+    sources: usize,
+    slots: [Option<u8>; MAX_SOURCE_CHANNELS],
+}
+
+impl ChannelMap {
+    fn identity(channels: usize) -> Self {
+        let sources = channels.min(MAX_SOURCE_CHANNELS);
+        let mut slots = [None; MAX_SOURCE_CHANNELS];
+        for (index, slot) in slots.iter_mut().enumerate().take(sources) {
+            *slot = Some(index as u8);
+        }
+        Self { sources, slots }
+    }
+
+    /// Maps `positions` (SPA_AUDIO_CHANNEL_* values, in source order) onto the
+    /// slots of `layout`. RL/RR and SL/SR stand in for each other when the
+    /// layout only has one of the pairs; positions with no slot are dropped.
+    fn from_positions(positions: &[u32], layout: InputLayout) -> Self {
+        let positions = &positions[..positions.len().min(MAX_SOURCE_CHANNELS)];
+        if positions.is_empty()
+            || positions
+                .iter()
+                .all(|position| *position == libspa::sys::SPA_AUDIO_CHANNEL_UNKNOWN)
+        {
+            return Self::identity(positions.len().max(layout.channels()));
+        }
+
+        let targets = layout.positions();
+        let mut map = Self {
+            sources: positions.len(),
+            slots: [None; MAX_SOURCE_CHANNELS],
+        };
+        // Exact matches first so an alias never steals a slot the source also has.
+        for (source, position) in positions.iter().enumerate() {
+            map.slots[source] = targets
+                .iter()
+                .position(|target| target == position)
+                .map(|slot| slot as u8);
+        }
+        for (source, position) in positions.iter().enumerate() {
+            if map.slots[source].is_some() {
+                continue;
+            }
+            let Some(alias) = surround_alias(*position) else {
+                continue;
+            };
+            let Some(slot) = targets.iter().position(|target| *target == alias) else {
+                continue;
+            };
+            if !map.slots[..map.sources].contains(&Some(slot as u8)) {
+                map.slots[source] = Some(slot as u8);
+            }
+        }
+        map
+    }
+
+    fn sources(&self) -> usize {
+        self.sources
+    }
+
+    fn slot(&self, source: usize) -> Option<usize> {
+        self.slots.get(source).copied().flatten().map(usize::from)
+    }
+
+    /// Bit per 5.1 channel (FL,FR,FC,LFE,SL,SR) fed by at least one source
+    /// channel, after the 7.1 fold.
+    fn encoder_mask(&self, layout: InputLayout) -> u32 {
+        (0..self.sources)
+            .filter_map(|source| self.slot(source))
+            .filter(|slot| *slot < layout.channels())
+            .map(|slot| match layout {
+                // RL/RR and SL/SR both fold into the 5.1 surrounds.
+                InputLayout::Surround71 if slot >= 4 => 4 + slot % 2,
+                _ => slot,
+            })
+            .fold(0, |mask, slot| mask | 1 << slot)
+    }
+
+    /// Human-readable mapping for the log, e.g. `FL->FL, RL->SL`.
+    fn describe(&self, positions: &[u32], layout: InputLayout) -> String {
+        let targets = layout.positions();
+        (0..self.sources)
+            .map(|source| {
+                let from = positions
+                    .get(source)
+                    .map_or("?", |position| channel_position_name(*position));
+                match self.slot(source) {
+                    Some(slot) => format!("{from}->{}", channel_position_name(targets[slot])),
+                    None => format!("{from}->(dropped)"),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn surround_alias(position: u32) -> Option<u32> {
+    match position {
+        libspa::sys::SPA_AUDIO_CHANNEL_RL => Some(libspa::sys::SPA_AUDIO_CHANNEL_SL),
+        libspa::sys::SPA_AUDIO_CHANNEL_RR => Some(libspa::sys::SPA_AUDIO_CHANNEL_SR),
+        libspa::sys::SPA_AUDIO_CHANNEL_SL => Some(libspa::sys::SPA_AUDIO_CHANNEL_RL),
+        libspa::sys::SPA_AUDIO_CHANNEL_SR => Some(libspa::sys::SPA_AUDIO_CHANNEL_RR),
+        _ => None,
+    }
+}
+
+fn channel_position_name(position: u32) -> &'static str {
+    match position {
+        libspa::sys::SPA_AUDIO_CHANNEL_MONO => "MONO",
+        libspa::sys::SPA_AUDIO_CHANNEL_FL => "FL",
+        libspa::sys::SPA_AUDIO_CHANNEL_FR => "FR",
+        libspa::sys::SPA_AUDIO_CHANNEL_FC => "FC",
+        libspa::sys::SPA_AUDIO_CHANNEL_LFE => "LFE",
+        libspa::sys::SPA_AUDIO_CHANNEL_SL => "SL",
+        libspa::sys::SPA_AUDIO_CHANNEL_SR => "SR",
+        libspa::sys::SPA_AUDIO_CHANNEL_RL => "RL",
+        libspa::sys::SPA_AUDIO_CHANNEL_RR => "RR",
+        libspa::sys::SPA_AUDIO_CHANNEL_UNKNOWN => "UNKNOWN",
+        _ => "AUX",
+    }
+}
+
+/// Negotiated capture sample format and channel routing, shared with the RT
+/// callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CaptureFormat {
+    /// `None` for a negotiated format the parsers cannot decode; the capture
+    /// callback then writes silence.
+    sample_format: Option<SampleFormat>,
+    channel_map: ChannelMap,
+}
+
+impl CaptureFormat {
+    /// Assumed until a format is negotiated: F32LE in sink channel order.
+    fn new(channels: usize) -> Self {
+        Self {
+            sample_format: Some(SampleFormat::F32Le),
+            channel_map: ChannelMap::identity(channels),
         }
     }
 }
@@ -52,9 +439,18 @@ pub enum OutputMode {
 struct PlaybackTarget {
     connect_target_id: Option<u32>,
     target_object: Option<String>,
+    /// `--target default`: linked to whatever `default.audio.sink` names.
+    follow_default: bool,
 }
 
 fn resolve_playback_target(target_node: Option<&str>) -> PlaybackTarget {
+    if target_node.is_some_and(graph::is_default_target) {
+        return PlaybackTarget {
+            connect_target_id: None,
+            target_object: None,
+            follow_default: true,
+        };
+    }
     let target_object = target_node
         .map(str::trim)
         .filter(|target| !target.is_empty())
@@ -67,23 +463,30 @@ fn resolve_playback_target(target_node: Option<&str>) -> PlaybackTarget {
     PlaybackTarget {
         connect_target_id,
         target_object,
+        follow_default: false,
     }
 }
 
-fn build_playback_properties(target: &PlaybackTarget) -> pw::properties::Properties {
-    let has_explicit_target = target.target_object.is_some() || target.connect_target_id.is_some();
+fn build_playback_properties(
+    target: &PlaybackTarget,
+    sample_rate_hz: u32,
+) -> pw::properties::Properties {
+    let has_explicit_target = target.target_object.is_some()
+        || target.connect_target_id.is_some()
+        || target.follow_default;
     let mut playback_props = properties! {
-        *pw::keys::NODE_NAME => "pw-ac3-live-output",
+        *pw::keys::NODE_NAME => PLAYBACK_NODE_NAME,
         *pw::keys::NODE_DESCRIPTION => "AC-3 Live Output",
         *pw::keys::APP_NAME => "pw-ac3-live",
         "audio.channels" => OUTPUT_CHANNELS.to_string(),
         "audio.position" => "FL,FR",
-        "audio.rate" => SAMPLE_RATE,
+        "audio.rate" => sample_rate_hz.to_string(),
         "audio.format" => "S16LE",
         "media.name" => "ac3-encoder-playback",
         "stream.is-live" => "true",
         "node.want-driver" => "true",
-        // We link explicitly from the launcher/script to avoid accidental mixed routes.
+        // With a target (or the followed default), PortLinker creates the FL/FR
+        // links itself to avoid mixed routes.
         "node.autoconnect" => if has_explicit_target { "false" } else { "true" },
         // Keep IEC61937 bytes bit-transparent: no remix, no resample, no dither.
         "stream.dont-remix" => "true",
@@ -168,7 +571,7 @@ mod alsa_output {
     }
 
     impl AlsaPlayback {
-        pub(super) fn open(device: &str, latency_us: u32) -> Result<Self> {
+        pub(super) fn open(device: &str, sample_rate_hz: u32, latency_us: u32) -> Result<Self> {
             let mut handle = ptr::null_mut();
             let device_cstr =
                 CString::new(device).context("ALSA device contains interior NUL bytes")?;
@@ -197,7 +600,7 @@ mod alsa_output {
                     SND_PCM_FORMAT_S16_LE,
                     SND_PCM_ACCESS_RW_INTERLEAVED,
                     OUTPUT_CHANNELS as c_uint,
-                    SAMPLE_RATE_HZ,
+                    sample_rate_hz,
                     0,
                     latency_us,
                 )
@@ -208,7 +611,7 @@ mod alsa_output {
                 return Err(alsa_error(
                     &format!(
                         "Failed to configure ALSA device '{device}' ({} Hz, {}ch, S16LE, latency={}us)",
-                        SAMPLE_RATE_HZ, OUTPUT_CHANNELS, latency_us
+                        sample_rate_hz, OUTPUT_CHANNELS, latency_us
                     ),
                     params_result,
                 ));
@@ -287,26 +690,149 @@ mod alsa_output {
     }
 }
 
-fn parse_f32_plane_into(
+/// PCM sample encodings the capture parsers can decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SampleFormat {
+    S16Le,
+    S16Be,
+    /// Packed 24-bit samples (3 bytes).
+    S24Le,
+    S24Be,
+    /// 24-bit samples in the low bits of a 32-bit word.
+    S24In32Le,
+    S24In32Be,
+    S32Le,
+    S32Be,
+    F32Le,
+    F32Be,
+    F64Le,
+    F64Be,
+}
+
+impl SampleFormat {
+    /// Maps a negotiated SPA format. Planar formats are native-endian.
+    fn from_audio_format(format: AudioFormat) -> Option<Self> {
+        let native = |little: Self, big: Self| {
+            if cfg!(target_endian = "big") {
+                big
+            } else {
+                little
+            }
+        };
+        Some(match format {
+            AudioFormat::S16LE => Self::S16Le,
+            AudioFormat::S16BE => Self::S16Be,
+            AudioFormat::S16P => native(Self::S16Le, Self::S16Be),
+            AudioFormat::S24LE => Self::S24Le,
+            AudioFormat::S24BE => Self::S24Be,
+            AudioFormat::S24P => native(Self::S24Le, Self::S24Be),
+            AudioFormat::S24_32LE => Self::S24In32Le,
+            AudioFormat::S24_32BE => Self::S24In32Be,
+            AudioFormat::S24_32P => native(Self::S24In32Le, Self::S24In32Be),
+            AudioFormat::S32LE => Self::S32Le,
+            AudioFormat::S32BE => Self::S32Be,
+            AudioFormat::S32P => native(Self::S32Le, Self::S32Be),
+            AudioFormat::F32LE => Self::F32Le,
+            AudioFormat::F32BE => Self::F32Be,
+            AudioFormat::F32P => native(Self::F32Le, Self::F32Be),
+            AudioFormat::F64LE => Self::F64Le,
+            AudioFormat::F64BE => Self::F64Be,
+            AudioFormat::F64P => native(Self::F64Le, Self::F64Be),
+            _ => return None,
+        })
+    }
+
+    /// Size of one sample in bytes.
+    fn bytes(self) -> usize {
+        match self {
+            Self::S16Le | Self::S16Be => 2,
+            Self::S24Le | Self::S24Be => 3,
+            Self::S24In32Le | Self::S24In32Be | Self::S32Le | Self::S32Be => 4,
+            Self::F32Le | Self::F32Be => 4,
+            Self::F64Le | Self::F64Be => 8,
+        }
+    }
+
+    /// Decodes one sample from the first `self.bytes()` bytes of `bytes`.
+    /// Integer formats map to [-1.0, 1.0) without overshooting on the minimum.
+    fn decode(self, bytes: &[u8]) -> f32 {
+        const S16_SCALE: f32 = 1.0 / 32_768.0;
+        const S24_SCALE: f32 = 1.0 / 8_388_608.0;
+        const S32_SCALE: f64 = 1.0 / 2_147_483_648.0;
+        match self {
+            Self::S16Le => f32::from(i16::from_le_bytes(sample_bytes(bytes))) * S16_SCALE,
+            Self::S16Be => f32::from(i16::from_be_bytes(sample_bytes(bytes))) * S16_SCALE,
+            // Place the 3 bytes in the top of an i32 so the shift sign-extends.
+            Self::S24Le => {
+                let value = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+                value as f32 * S24_SCALE
+            }
+            Self::S24Be => {
+                let value = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0]) >> 8;
+                value as f32 * S24_SCALE
+            }
+            Self::S24In32Le => {
+                let value = (i32::from_le_bytes(sample_bytes(bytes)) << 8) >> 8;
+                value as f32 * S24_SCALE
+            }
+            Self::S24In32Be => {
+                let value = (i32::from_be_bytes(sample_bytes(bytes)) << 8) >> 8;
+                value as f32 * S24_SCALE
+            }
+            Self::S32Le => (f64::from(i32::from_le_bytes(sample_bytes(bytes))) * S32_SCALE) as f32,
+            Self::S32Be => (f64::from(i32::from_be_bytes(sample_bytes(bytes))) * S32_SCALE) as f32,
+            Self::F32Le => f32::from_le_bytes(sample_bytes(bytes)),
+            Self::F32Be => f32::from_be_bytes(sample_bytes(bytes)),
+            Self::F64Le => f64::from_le_bytes(sample_bytes(bytes)) as f32,
+            Self::F64Be => f64::from_be_bytes(sample_bytes(bytes)) as f32,
+        }
+    }
+}
+
+fn sample_bytes<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut sample = [0u8; N];
+    sample.copy_from_slice(&bytes[..N]);
+    sample
+}
+
+/// Parses one planar channel buffer of `format` samples.
+fn parse_plane_into(
     raw_data: &[u8],
     offset: usize,
     size: usize,
+    format: SampleFormat,
     out: &mut Vec<f32>,
 ) -> Option<()> {
+    let sample_size = format.bytes();
+    // Packed 24-bit samples have no natural alignment.
+    let alignment = if sample_size.is_power_of_two() {
+        sample_size
+    } else {
+        1
+    };
     let end = offset.checked_add(size)?;
     let bytes = raw_data.get(offset..end)?;
-    if !offset.is_multiple_of(size_of::<f32>()) || !bytes.len().is_multiple_of(size_of::<f32>()) {
+    if !offset.is_multiple_of(alignment) || !bytes.len().is_multiple_of(sample_size) {
         return None;
     }
 
     out.clear();
-    out.reserve(bytes.len() / size_of::<f32>());
-    for chunk in bytes.chunks_exact(size_of::<f32>()) {
-        out.push(f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+    out.reserve(bytes.len() / sample_size);
+    for sample in bytes.chunks_exact(sample_size) {
+        out.push(format.decode(sample));
     }
     Some(())
 }
 
+fn parse_f32_plane_into(
+    raw_data: &[u8],
+    offset: usize,
+    size: usize,
+    out: &mut Vec<f32>,
+) -> Option<()> {
+    parse_plane_into(raw_data, offset, size, SampleFormat::F32Le, out)
+}
+
 fn parse_f32_interleaved_into(
     raw_data: &[u8],
     offset: usize,
@@ -324,11 +850,18 @@ fn parse_f32_interleaved_into(
     Some(())
 }
 
+/// Parses a single interleaved buffer of `format` samples whose channel count is
+/// implied by `stride`. Each source channel is routed to its `map` slot in frames
+/// of `out_channels`; unmapped slots stay silent.
+#[allow(clippy::too_many_arguments)]
 fn parse_interleaved_from_stride_into(
     raw_data: &[u8],
     offset: usize,
     size: usize,
     stride: usize,
+    format: SampleFormat,
+    map: &ChannelMap,
+    out_channels: usize,
     out: &mut Vec<f32>,
 ) -> Option<()> {
     if stride == 0 {
@@ -342,60 +875,27 @@ fn parse_interleaved_from_stride_into(
     }
 
     let frame_count = bytes.len() / stride;
-    if frame_count == 0 {
+    if frame_count == 0 || !stride.is_multiple_of(format.bytes()) {
         return None;
     }
 
-    if stride.is_multiple_of(size_of::<f32>()) {
-        let channels = stride / size_of::<f32>();
-        if (1..=INPUT_CHANNELS).contains(&channels) {
-            out.clear();
-            out.reserve(frame_count * INPUT_CHANNELS);
-            for frame in 0..frame_count {
-                let frame_offset = frame * stride;
-                for ch in 0..INPUT_CHANNELS {
-                    let sample = if ch < channels {
-                        let base = frame_offset + ch * size_of::<f32>();
-                        f32::from_le_bytes([
-                            bytes[base],
-                            bytes[base + 1],
-                            bytes[base + 2],
-                            bytes[base + 3],
-                        ])
-                    } else {
-                        0.0
-                    };
-                    out.push(sample);
-                }
-            }
-            return Some(());
-        }
+    let channels = stride / format.bytes();
+    if !(1..=map.sources()).contains(&channels) {
+        return None;
     }
 
-    if stride.is_multiple_of(size_of::<i16>()) {
-        let channels = stride / size_of::<i16>();
-        if (1..=INPUT_CHANNELS).contains(&channels) {
-            out.clear();
-            out.reserve(frame_count * INPUT_CHANNELS);
-            for frame in 0..frame_count {
-                let frame_offset = frame * stride;
-                for ch in 0..INPUT_CHANNELS {
-                    let sample = if ch < channels {
-                        let base = frame_offset + ch * size_of::<i16>();
-                        let value = i16::from_le_bytes([bytes[base], bytes[base + 1]]);
-                        // Map i16 PCM to [-1.0, 1.0) without overshooting on i16::MIN.
-                        (value as f32) / 32768.0
-                    } else {
-                        0.0
-                    };
-                    out.push(sample);
-                }
-            }
-            return Some(());
+    out.clear();
+    out.resize(frame_count * out_channels, 0.0);
+    for (frame, samples) in out.chunks_exact_mut(out_channels).enumerate() {
+        let frame_offset = frame * stride;
+        for ch in 0..channels {
+            let Some(slot) = map.slot(ch).filter(|slot| *slot < out_channels) else {
+                continue;
+            };
+            samples[slot] = format.decode(&bytes[frame_offset + ch * format.bytes()..]);
         }
     }
-
-    None
+    Some(())
 }
 
 fn run_stdout_output_loop<W: Write>(
@@ -422,6 +922,7 @@ fn run_alsa_output_loop(
     output_consumer: &mut Consumer<u8>,
     running: &AtomicBool,
     device: &str,
+    sample_rate_hz: u32,
     latency_us: u32,
 ) -> Result<()> {
     #[cfg(not(target_os = "linux"))]
@@ -429,13 +930,14 @@ fn run_alsa_output_loop(
         let _ = output_consumer;
         let _ = running;
         let _ = device;
+        let _ = sample_rate_hz;
         let _ = latency_us;
         return Err(anyhow!("--alsa-direct is only supported on Linux"));
     }
 
     #[cfg(target_os = "linux")]
     {
-        let mut alsa = alsa_output::AlsaPlayback::open(device, latency_us)?;
+        let mut alsa = alsa_output::AlsaPlayback::open(device, sample_rate_hz, latency_us)?;
         let mut read_buffer = [0u8; STDOUT_READ_BUFFER_SIZE];
         let mut staging_buffer = [0u8; STDOUT_READ_BUFFER_SIZE + OUTPUT_FRAME_BYTES];
         let mut staged_len = 0usize;
@@ -480,10 +982,87 @@ fn run_alsa_output_loop(
     }
 }
 
-fn build_audio_raw_format_param(format: AudioFormat, channels: u32) -> Result<Vec<u8>> {
+/// Longest time output spends between the output ring and the device in direct
+/// ALSA mode: the device buffer for `latency_us` (0 for the default) plus one
+/// read-ahead chunk of the output thread, at the lowest AC-3 rate.
+pub fn alsa_output_latency(latency_us: u32) -> Duration {
+    let latency_us = if latency_us == 0 {
+        DEFAULT_ALSA_LATENCY_US
+    } else {
+        latency_us
+    };
+    let read_ahead_frames = (STDOUT_READ_BUFFER_SIZE / OUTPUT_FRAME_BYTES) as u64;
+    let slowest_rate_hz = u64::from(SUPPORTED_SAMPLE_RATES_HZ[0]);
+    Duration::from_micros(u64::from(latency_us))
+        + Duration::from_micros(read_ahead_frames * 1_000_000 / slowest_rate_hz)
+}
+
+/// Builds an EnumFormat pod. A `rate` of 0 leaves the rate open so the stream
+/// follows the graph.
+fn build_audio_raw_format_param(format: AudioFormat, rate: u32, channels: u32) -> Result<Vec<u8>> {
+    serialize_enum_format(audio_raw_format_properties(format, rate, channels))
+}
+
+/// Builds the raw EnumFormat of the playback stream: 2ch S16LE at `rate`, with
+/// the other AC-3 rates as alternatives so the graph can renegotiate the stream
+/// and the encoder follows it.
+///
+/// The channel count stays fixed. IEC 61937 is always carried on two channels,
+/// and any channel mixing would destroy the bursts, so there is no other
+/// channel count to follow.
+fn build_playback_format_param(rate: u32) -> Result<Vec<u8>> {
+    use pw::spa::param::format::FormatProperties;
+    use pw::spa::pod::Property;
+
+    let mut properties = audio_raw_format_properties(AudioFormat::S16LE, 0, OUTPUT_CHANNELS as u32);
+    properties.push(Property::new(
+        FormatProperties::AudioRate.as_raw(),
+        supported_rate_choice(rate),
+    ));
+    serialize_enum_format(properties)
+}
+
+/// `rate` as the preferred value of a choice between all AC-3 rates.
+fn supported_rate_choice(rate: u32) -> pw::spa::pod::Value {
+    use pw::spa::pod::{ChoiceValue, Value};
+    use pw::spa::utils::{Choice, ChoiceEnum, ChoiceFlags};
+
+    Value::Choice(ChoiceValue::Int(Choice(
+        ChoiceFlags::empty(),
+        ChoiceEnum::Enum {
+            default: rate as i32,
+            alternatives: SUPPORTED_SAMPLE_RATES_HZ
+                .iter()
+                .map(|rate| *rate as i32)
+                .collect(),
+        },
+    )))
+}
+
+fn serialize_enum_format(properties: Vec<pw::spa::pod::Property>) -> Result<Vec<u8>> {
+    let obj = pw::spa::pod::Object {
+        type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: pw::spa::param::ParamType::EnumFormat.as_raw(),
+        properties,
+    };
+
+    let serialized = pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &pw::spa::pod::Value::Object(obj),
+    )
+    .context("Failed to serialize PipeWire format pod")?;
+
+    Ok(serialized.0.into_inner())
+}
+
+fn audio_raw_format_properties(
+    format: AudioFormat,
+    rate: u32,
+    channels: u32,
+) -> Vec<pw::spa::pod::Property> {
     let mut audio_info = AudioInfoRaw::new();
     audio_info.set_format(format);
-    audio_info.set_rate(SAMPLE_RATE_HZ);
+    audio_info.set_rate(rate);
     audio_info.set_channels(channels);
 
     // Explicitly set channel map to ensure correct port creation.
@@ -497,6 +1076,17 @@ fn build_audio_raw_format_param(format: AudioFormat, channels: u32) -> Result<Ve
         position[4] = libspa::sys::SPA_AUDIO_CHANNEL_SL;
         position[5] = libspa::sys::SPA_AUDIO_CHANNEL_SR;
         audio_info.set_position(position);
+    } else if channels == 8 {
+        let mut position = [0u32; 64];
+        position[0] = libspa::sys::SPA_AUDIO_CHANNEL_FL;
+        position[1] = libspa::sys::SPA_AUDIO_CHANNEL_FR;
+        position[2] = libspa::sys::SPA_AUDIO_CHANNEL_FC;
+        position[3] = libspa::sys::SPA_AUDIO_CHANNEL_LFE;
+        position[4] = libspa::sys::SPA_AUDIO_CHANNEL_RL;
+        position[5] = libspa::sys::SPA_AUDIO_CHANNEL_RR;
+        position[6] = libspa::sys::SPA_AUDIO_CHANNEL_SL;
+        position[7] = libspa::sys::SPA_AUDIO_CHANNEL_SR;
+        audio_info.set_position(position);
     } else if channels == 2 {
         let mut position = [0u32; 64];
         position[0] = libspa::sys::SPA_AUDIO_CHANNEL_FL;
@@ -504,128 +1094,1762 @@ fn build_audio_raw_format_param(format: AudioFormat, channels: u32) -> Result<Ve
         audio_info.set_position(position);
     }
 
-    let obj = pw::spa::pod::Object {
+    audio_info.into()
+}
+
+/// Capture EnumFormats, most wanted first: `preferred`, the other decodable
+/// formats (little-endian interleaved, then big-endian, then planar), and last
+/// plain stereo S16LE, which carries an IEC 61937 stream from a passthrough
+/// player untouched.
+fn build_capture_format_params(
+    preferred: CaptureSampleFormat,
+    channels: u32,
+) -> Result<Vec<Vec<u8>>> {
+    let mut order = vec![preferred];
+    order.extend(
+        CaptureSampleFormat::ALL
+            .iter()
+            .copied()
+            .filter(|format| *format != preferred),
+    );
+    let formats = (0..3).flat_map(|variant| {
+        order
+            .iter()
+            .map(move |format| format.audio_formats()[variant])
+    });
+
+    let mut params = formats
+        .map(|format| build_audio_raw_format_param(format, 0, channels))
+        .collect::<Result<Vec<_>>>()?;
+    if channels != OUTPUT_CHANNELS as u32 {
+        params.push(build_audio_raw_format_param(
+            AudioFormat::S16LE,
+            0,
+            OUTPUT_CHANNELS as u32,
+        )?);
+    }
+    Ok(params)
+}
+
+/// Builds the IEC958 EnumFormat offered first by the playback stream: AC-3
+/// passthrough, preferably at `rate` but at any AC-3 rate. Sinks with AC-3 in
+/// their IEC958 codecs then treat the stream as compressed (no mixing,
+/// non-audio status bit); the others fall back to the raw S16LE format.
+fn build_iec958_format_param(rate: u32) -> Result<Vec<u8>> {
+    use pw::spa::param::format::{FormatProperties, MediaSubtype, MediaType};
+    use pw::spa::pod::{Object, Property, Value};
+    use pw::spa::utils::Id;
+
+    let obj = Object {
         type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
         id: pw::spa::param::ParamType::EnumFormat.as_raw(),
-        properties: audio_info.into(),
+        properties: vec![
+            Property::new(
+                FormatProperties::MediaType.as_raw(),
+                Value::Id(Id(MediaType::Audio.as_raw())),
+            ),
+            Property::new(
+                FormatProperties::MediaSubtype.as_raw(),
+                Value::Id(Id(MediaSubtype::Iec958.as_raw())),
+            ),
+            Property::new(
+                FormatProperties::AudioIec958Codec.as_raw(),
+                Value::Id(Id(libspa::sys::SPA_AUDIO_IEC958_CODEC_AC3)),
+            ),
+            Property::new(
+                FormatProperties::AudioRate.as_raw(),
+                supported_rate_choice(rate),
+            ),
+        ],
     };
 
     let serialized = pw::spa::pod::serialize::PodSerializer::serialize(
         std::io::Cursor::new(Vec::new()),
         &pw::spa::pod::Value::Object(obj),
     )
-    .context("Failed to serialize PipeWire format pod")?;
+    .context("Failed to serialize PipeWire IEC958 format pod")?;
 
     Ok(serialized.0.into_inner())
 }
 
-/// Runs the main PipeWire event loop.
-pub fn run_pipewire_loop(
-    input_producer: Producer<f32>,
-    output_consumer: Consumer<u8>,
-    target_node: Option<String>,
-    use_stdout: bool,
-    running: Arc<AtomicBool>,
-) -> Result<()> {
-    let output_mode = if use_stdout {
-        OutputMode::Stdout
-    } else {
-        OutputMode::Pipewire
-    };
-
-    run_pipewire_loop_with_config(
-        input_producer,
-        output_consumer,
-        target_node,
-        output_mode,
-        running,
-        PipewireConfig::default(),
-    )
-}
+/// Codec and rate of a negotiated IEC958 Format (the preferred rate of an
+/// EnumFormat); `None` for other formats.
+fn parse_iec958_format(param: &pw::spa::pod::Pod) -> Option<(String, u32)> {
+    use pw::spa::param::format::{FormatProperties, MediaSubtype};
+    use pw::spa::pod::{deserialize::PodDeserializer, ChoiceValue, Value};
+    use pw::spa::utils::{Choice, ChoiceEnum};
 
-pub fn run_pipewire_loop_with_config(
-    input_producer: Producer<f32>,
-    mut output_consumer: Consumer<u8>,
-    target_node: Option<String>,
-    output_mode: OutputMode,
-    running: Arc<AtomicBool>,
-    config: PipewireConfig,
-) -> Result<()> {
-    info!("Initializing PipeWire client...");
-    let node_latency = if config.node_latency.trim().is_empty() {
-        "64/48000"
-    } else {
-        config.node_latency.as_str()
+    let (_, subtype) = pw::spa::param::format_utils::parse_format(param).ok()?;
+    if subtype != MediaSubtype::Iec958 {
+        return None;
+    }
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return None;
     };
-    let requested_latency_frames = node_latency
-        .split('/')
+    let rate = object.properties.iter().find_map(|prop| {
+        if prop.key != FormatProperties::AudioRate.as_raw() {
+            return None;
+        }
+        match &prop.value {
+            Value::Int(rate) => u32::try_from(*rate).ok(),
+            Value::Choice(ChoiceValue::Int(Choice(_, ChoiceEnum::Enum { default, .. }))) => {
+                u32::try_from(*default).ok()
+            }
+            _ => None,
+        }
+    })?;
+    let codec = iec958_codecs_from_param(param)
+        .into_iter()
         .next()
-        .and_then(|v| v.parse::<usize>().ok())
-        .filter(|frames| *frames > 0);
-
-    pw::init();
-
-    let mainloop = MainLoop::new(None)?;
-    let context = pw::context::Context::new(&mainloop)?;
-    let core = context.connect(None)?;
-
-    // ------------------------------------------------------------------
-    // 1. Create Capture Stream (Virtual Sink)
-    // ------------------------------------------------------------------
+        .unwrap_or_else(|| "unknown".to_string());
+    Some((codec, rate))
+}
 
-    let mut props = properties! {
+/// Creates the optional stereo voice-chat sink (`pw-ac3-live-voice`) whose samples
+/// are pushed, interleaved, into `voice_producer` for the encoder to mix in.
+fn connect_voice_capture_stream(
+    core: &pw::core::Core,
+    node_latency: &str,
+    voice_data: Arc<Mutex<Producer<f32>>>,
+) -> Result<(pw::stream::Stream, pw::stream::StreamListener<()>)> {
+    let props = properties! {
         *pw::keys::MEDIA_CLASS => "Audio/Sink",
-        *pw::keys::NODE_NAME => "pw-ac3-live-input",
-        *pw::keys::NODE_DESCRIPTION => "AC-3 Encoder Input",
+        *pw::keys::NODE_NAME => VOICE_NODE_NAME,
+        *pw::keys::NODE_DESCRIPTION => "AC-3 Encoder Voice Chat",
         *pw::keys::APP_NAME => "pw-ac3-live",
-        "audio.channels" => INPUT_CHANNELS.to_string(),
-        "audio.position" => "FL,FR,FC,LFE,SL,SR",
-        "audio.rate" => SAMPLE_RATE,
+        "audio.channels" => VOICE_CHANNELS.to_string(),
+        "audio.position" => "FL,FR",
         "audio.format" => "F32LE",
         "node.latency" => node_latency,
     };
-    if let Some(frames) = requested_latency_frames {
-        let force_quantum = frames.to_string();
-        props.insert("node.force-quantum", force_quantum.as_str());
-        props.insert("node.lock-quantum", "true");
-        props.insert("node.force-rate", SAMPLE_RATE);
-        props.insert("node.lock-rate", "true");
-        info!(
-            "Capture stream requesting forced quantum/rate: {} frames @ {} Hz",
-            frames, SAMPLE_RATE_HZ
-        );
-    }
 
-    let data = Arc::new(Mutex::new(input_producer));
-    let capture_layout_logged = Arc::new(AtomicBool::new(false));
     let mut interleaved_scratch = Vec::<f32>::new();
-    let mut planar_channel_scratch: [Vec<f32>; INPUT_CHANNELS] =
+    let mut planar_channel_scratch: [Vec<f32>; VOICE_CHANNELS] =
         std::array::from_fn(|_| Vec::new());
 
-    // Create stream first
-    let capture_stream = pw::stream::Stream::new(&core, "ac3-encoder-capture", props)?;
-
-    // Add listener for process callback
-    let _capture_listener = capture_stream
+    let voice_stream = pw::stream::Stream::new(core, "ac3-encoder-voice-capture", props)?;
+    let voice_listener = voice_stream
         .add_local_listener::<()>()
         .state_changed(|_stream, _data, old, new| {
-            info!("Capture Stream state changed: {:?} -> {:?}", old, new);
+            info!("Voice Stream state changed: {:?} -> {:?}", old, new);
         })
-        .param_changed(|_stream, _data, id, param| {
-            if id != pw::spa::param::ParamType::Format.as_raw() {
-                return;
-            }
-            let Some(param) = param else {
+        .process(move |stream: &StreamRef, _data| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
                 return;
             };
-            let mut info = AudioInfoRaw::new();
-            if info.parse(param).is_ok() {
+            let datas = buffer.datas_mut();
+            if datas.is_empty() {
+                return;
+            }
+
+            interleaved_scratch.clear();
+            if datas.len() == 1 {
+                let chunk = datas[0].chunk();
+                let offset = chunk.offset() as usize;
+                let size = chunk.size() as usize;
+                if let Some(raw_data) = datas[0].data() {
+                    let _ = parse_f32_interleaved_into(
+                        raw_data,
+                        offset,
+                        size,
+                        VOICE_CHANNELS,
+                        &mut interleaved_scratch,
+                    );
+                }
+            } else {
+                for (data, samples) in datas.iter_mut().zip(planar_channel_scratch.iter_mut()) {
+                    samples.clear();
+                    let chunk = data.chunk();
+                    let offset = chunk.offset() as usize;
+                    let size = chunk.size() as usize;
+                    if let Some(raw_data) = data.data() {
+                        let _ = parse_f32_plane_into(raw_data, offset, size, samples);
+                    }
+                }
+                let [left, right] = &planar_channel_scratch;
+                for (l, r) in left.iter().zip(right) {
+                    interleaved_scratch.push(*l);
+                    interleaved_scratch.push(*r);
+                }
+            }
+
+            if interleaved_scratch.is_empty() {
+                return;
+            }
+            // Voice is best effort: whatever does not fit in the ring is dropped.
+            if let Ok(mut producer) = voice_data.try_lock() {
+                let writable = producer.slots().min(interleaved_scratch.len());
+                let writable = writable - (writable % VOICE_CHANNELS);
+                if writable > 0 {
+                    if let Ok(chunk) = producer.write_chunk_uninit(writable) {
+                        chunk.fill_from_iter(interleaved_scratch.iter().take(writable).copied());
+                    }
+                }
+            }
+        })
+        .register()?;
+
+    let voice_format_bytes =
+        build_audio_raw_format_param(AudioFormat::F32LE, 0, VOICE_CHANNELS as u32)?;
+    let voice_format_pod = pw::spa::pod::Pod::from_bytes(&voice_format_bytes)
+        .ok_or_else(|| anyhow!("Failed to parse voice format pod bytes"))?;
+    let mut voice_params = [voice_format_pod];
+    voice_stream.connect(
+        Direction::Input,
+        None,
+        StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS | StreamFlags::RT_PROCESS,
+        &mut voice_params,
+    )?;
+
+    info!("PipeWire voice chat stream connected (pw-ac3-live-voice).");
+    Ok((voice_stream, voice_listener))
+}
+
+/// Live mirror of the PipeWire registry. Audio sinks are bound so their
+/// EnumFormat params can reveal IEC958 codecs missing from the properties;
+/// they and our playback node also report their volume Props. Audio devices
+/// are bound to follow their card profiles.
+struct RegistryWatch {
+    graph: Rc<RefCell<Graph>>,
+    registry: Rc<pw::registry::Registry>,
+    _listener: pw::registry::Listener,
+    nodes: Rc<RefCell<BTreeMap<u32, (pw::node::Node, pw::node::NodeListener)>>>,
+    devices: Rc<RefCell<BTreeMap<u32, (pw::device::Device, pw::device::DeviceListener)>>>,
+    /// The `default` metadata object (default sink, per-stream targets).
+    default_metadata: Rc<RefCell<Option<DefaultMetadata>>>,
+}
+
+struct DefaultMetadata {
+    id: u32,
+    metadata: pw::metadata::Metadata,
+    _listener: pw::metadata::MetadataListener,
+}
+
+impl RegistryWatch {
+    /// Sets (or with `None`, clears) a `default` metadata entry. Returns false
+    /// when the metadata object is not there (no session manager).
+    fn set_metadata(
+        &self,
+        subject: u32,
+        key: &str,
+        type_: Option<&str>,
+        value: Option<&str>,
+    ) -> bool {
+        match &*self.default_metadata.borrow() {
+            Some(default) => {
+                default.metadata.set_property(subject, key, type_, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Switches `device_id` to the profile with `index`. Returns false when the
+    /// device is not bound (gone, or not an audio device).
+    fn set_profile(&self, device_id: u32, index: i32) -> Result<bool> {
+        let devices = self.devices.borrow();
+        let Some((device, _)) = devices.get(&device_id) else {
+            return Ok(false);
+        };
+        let param = build_profile_param(index)?;
+        let pod =
+            pw::spa::pod::Pod::from_bytes(&param).ok_or_else(|| anyhow!("Invalid profile pod"))?;
+        device.set_param(pw::spa::param::ParamType::Profile, 0, pod);
+        Ok(true)
+    }
+
+    /// Sets the volume entries of a bound node's Props. Returns false when the
+    /// node is not bound.
+    fn set_node_volume(&self, node_id: u32, volume: &NodeVolume) -> Result<bool> {
+        let nodes = self.nodes.borrow();
+        let Some((node, _)) = nodes.get(&node_id) else {
+            return Ok(false);
+        };
+        let param = build_props_param(volume)?;
+        let pod =
+            pw::spa::pod::Pod::from_bytes(&param).ok_or_else(|| anyhow!("Invalid props pod"))?;
+        node.set_param(pw::spa::param::ParamType::Props, 0, pod);
+        Ok(true)
+    }
+}
+
+fn watch_registry(core: &pw::core::Core) -> Result<RegistryWatch> {
+    let registry = Rc::new(core.get_registry()?);
+    let graph = Rc::new(RefCell::new(Graph::new()));
+    let nodes = Rc::new(RefCell::new(BTreeMap::new()));
+    let devices = Rc::new(RefCell::new(BTreeMap::new()));
+
+    let registry_weak = Rc::downgrade(&registry);
+    let global_graph = graph.clone();
+    let global_nodes = nodes.clone();
+    let remove_graph = graph.clone();
+    let remove_nodes = nodes.clone();
+    let global_devices = devices.clone();
+    let remove_devices = devices.clone();
+    let default_metadata = Rc::new(RefCell::new(None::<DefaultMetadata>));
+    let global_metadata = default_metadata.clone();
+    let remove_metadata = default_metadata.clone();
+    let listener = registry
+        .add_listener_local()
+        .global(move |global| {
+            let props = global
+                .props
+                .map(|props| {
+                    props
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            let object = GraphObject::new(
+                global.id,
+                ObjectKind::from_type_name(global.type_.to_str()),
+                props,
+            );
+            let is_sink =
+                object.kind == ObjectKind::Node && object.prop("media.class") == Some("Audio/Sink");
+            let is_playback_node = object.kind == ObjectKind::Node
+                && object.prop("node.name") == Some(PLAYBACK_NODE_NAME);
+            let is_default_metadata = object.kind == ObjectKind::Metadata
+                && object.prop("metadata.name") == Some("default");
+            let is_audio_device = object.kind == ObjectKind::Device
+                && object.prop("media.class") == Some("Audio/Device");
+            global_graph.borrow_mut().insert(object);
+            if !is_sink && !is_playback_node && !is_default_metadata && !is_audio_device {
+                return;
+            }
+            let Some(registry) = registry_weak.upgrade() else {
+                return;
+            };
+            if is_audio_device {
+                let device = match registry.bind::<pw::device::Device, _>(global) {
+                    Ok(device) => device,
+                    Err(e) => {
+                        warn!("Could not bind audio device {}: {}", global.id, e);
+                        return;
+                    }
+                };
+                let device_id = global.id;
+                let param_graph = global_graph.clone();
+                let device_listener = device
+                    .add_listener_local()
+                    .param(move |_seq, id, _index, _next, param| {
+                        let Some(profile) = param.and_then(device_profile_from_param) else {
+                            return;
+                        };
+                        match id {
+                            pw::spa::param::ParamType::EnumProfile => param_graph
+                                .borrow_mut()
+                                .add_device_profile(device_id, profile),
+                            pw::spa::param::ParamType::Profile => param_graph
+                                .borrow_mut()
+                                .set_active_profile(device_id, profile),
+                            _ => {}
+                        }
+                    })
+                    .register();
+                // Emits the current values now and again on every change.
+                device.subscribe_params(&[
+                    pw::spa::param::ParamType::EnumProfile,
+                    pw::spa::param::ParamType::Profile,
+                ]);
+                global_devices
+                    .borrow_mut()
+                    .insert(device_id, (device, device_listener));
+                return;
+            }
+            if is_default_metadata {
+                let metadata = match registry.bind::<pw::metadata::Metadata, _>(global) {
+                    Ok(metadata) => metadata,
+                    Err(e) => {
+                        warn!("Could not bind default metadata {}: {}", global.id, e);
+                        return;
+                    }
+                };
+                let metadata_graph = global_graph.clone();
+                let metadata_listener = metadata
+                    .add_listener_local()
+                    .property(move |subject, key, _type, value| {
+                        metadata_graph
+                            .borrow_mut()
+                            .set_metadata(subject, key, value);
+                        0
+                    })
+                    .register();
+                *global_metadata.borrow_mut() = Some(DefaultMetadata {
+                    id: global.id,
+                    metadata,
+                    _listener: metadata_listener,
+                });
+                return;
+            }
+            let node = match registry.bind::<pw::node::Node, _>(global) {
+                Ok(node) => node,
+                Err(e) => {
+                    warn!("Could not bind node {}: {}", global.id, e);
+                    return;
+                }
+            };
+            let node_id = global.id;
+            let param_graph = global_graph.clone();
+            let node_listener = node
+                .add_listener_local()
+                .param(move |_seq, id, _index, _next, param| {
+                    let Some(param) = param else {
+                        return;
+                    };
+                    match id {
+                        pw::spa::param::ParamType::EnumFormat => {
+                            let codecs = iec958_codecs_from_param(param);
+                            if !codecs.is_empty() {
+                                param_graph.borrow_mut().add_enum_codecs(node_id, &codecs);
+                            }
+                        }
+                        pw::spa::param::ParamType::Props => {
+                            let volume = node_volume_from_param(param);
+                            if !volume.is_empty() {
+                                param_graph.borrow_mut().update_node_volume(node_id, volume);
+                            }
+                        }
+                        _ => {}
+                    }
+                })
+                .register();
+            if is_sink {
+                node.enum_params(0, Some(pw::spa::param::ParamType::EnumFormat), 0, u32::MAX);
+            }
+            node.subscribe_params(&[pw::spa::param::ParamType::Props]);
+            global_nodes
+                .borrow_mut()
+                .insert(node_id, (node, node_listener));
+        })
+        .global_remove(move |id| {
+            remove_nodes.borrow_mut().remove(&id);
+            remove_devices.borrow_mut().remove(&id);
+            let mut metadata = remove_metadata.borrow_mut();
+            if metadata.as_ref().is_some_and(|default| default.id == id) {
+                *metadata = None;
+            }
+            remove_graph.borrow_mut().remove(id);
+        })
+        .register();
+
+    Ok(RegistryWatch {
+        graph,
+        registry,
+        _listener: listener,
+        nodes,
+        devices,
+        default_metadata,
+    })
+}
+
+/// Runs `mainloop` until the server has answered everything sent so far.
+fn roundtrip(mainloop: &MainLoop, core: &pw::core::Core) -> Result<()> {
+    let done = Rc::new(Cell::new(false));
+    let pending = core.sync(0)?;
+    let done_flag = done.clone();
+    let mainloop_clone = mainloop.clone();
+    let _listener = core
+        .add_listener_local()
+        .done(move |id, seq| {
+            if id == pw::core::PW_ID_CORE && seq == pending {
+                done_flag.set(true);
+                mainloop_clone.quit();
+            }
+        })
+        .register();
+    while !done.get() {
+        mainloop.run();
+    }
+    Ok(())
+}
+
+/// Waits for the registry globals, then for the EnumFormat params of the sinks
+/// bound while they were announced.
+fn collect_sink_candidates(
+    mainloop: &MainLoop,
+    core: &pw::core::Core,
+    watch: &RegistryWatch,
+) -> Result<Vec<SinkCandidate>> {
+    roundtrip(mainloop, core)?;
+    roundtrip(mainloop, core)?;
+    Ok(watch.graph.borrow().sink_candidates())
+}
+
+/// IEC958 codecs named by the `iec958Codec` property of an EnumFormat param.
+fn iec958_codecs_from_param(param: &pw::spa::pod::Pod) -> Vec<String> {
+    use pw::spa::pod::{deserialize::PodDeserializer, ChoiceValue, Value};
+    use pw::spa::utils::ChoiceEnum;
+
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return Vec::new();
+    };
+    let codec_key = pw::spa::param::format::FormatProperties::AudioIec958Codec.as_raw();
+    let Some(property) = object.properties.iter().find(|prop| prop.key == codec_key) else {
+        return Vec::new();
+    };
+    let ids = match &property.value {
+        Value::Id(id) => vec![id.0],
+        Value::Choice(ChoiceValue::Id(choice)) => match &choice.1 {
+            ChoiceEnum::None(id) => vec![id.0],
+            ChoiceEnum::Enum {
+                default,
+                alternatives,
+            } => std::iter::once(default)
+                .chain(alternatives)
+                .map(|id| id.0)
+                .collect(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    let mut codecs = Vec::new();
+    for name in ids.into_iter().filter_map(iec958_codec_name) {
+        if !codecs.iter().any(|codec| codec == name) {
+            codecs.push(name.to_string());
+        }
+    }
+    codecs
+}
+
+fn iec958_codec_name(codec: u32) -> Option<&'static str> {
+    match codec {
+        libspa::sys::SPA_AUDIO_IEC958_CODEC_PCM => Some("PCM"),
+        libspa::sys::SPA_AUDIO_IEC958_CODEC_DTS => Some("DTS"),
+        libspa::sys::SPA_AUDIO_IEC958_CODEC_AC3 => Some("AC3"),
+        libspa::sys::SPA_AUDIO_IEC958_CODEC_MPEG => Some("MPEG"),
+        libspa::sys::SPA_AUDIO_IEC958_CODEC_MPEG2_AAC => Some("MPEG2-AAC"),
+        libspa::sys::SPA_AUDIO_IEC958_CODEC_EAC3 => Some("EAC3"),
+        libspa::sys::SPA_AUDIO_IEC958_CODEC_TRUEHD => Some("TrueHD"),
+        libspa::sys::SPA_AUDIO_IEC958_CODEC_DTSHD => Some("DTS-HD"),
+        _ => None,
+    }
+}
+
+/// Volume entries of a Props param.
+fn node_volume_from_param(param: &pw::spa::pod::Pod) -> NodeVolume {
+    use pw::spa::pod::{deserialize::PodDeserializer, Value, ValueArray};
+
+    let mut volume = NodeVolume::default();
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return volume;
+    };
+    for property in &object.properties {
+        match (property.key, &property.value) {
+            (libspa::sys::SPA_PROP_volume, Value::Float(value)) => volume.volume = Some(*value),
+            (libspa::sys::SPA_PROP_mute, Value::Bool(mute)) => volume.mute = Some(*mute),
+            (
+                libspa::sys::SPA_PROP_channelVolumes,
+                Value::ValueArray(ValueArray::Float(values)),
+            ) => volume.channel_volumes = Some(values.clone()),
+            (libspa::sys::SPA_PROP_softVolumes, Value::ValueArray(ValueArray::Float(values))) => {
+                volume.soft_volumes = Some(values.clone())
+            }
+            (libspa::sys::SPA_PROP_softMute, Value::Bool(mute)) => volume.soft_mute = Some(*mute),
+            _ => {}
+        }
+    }
+    volume
+}
+
+/// Builds the Props pod carrying the entries `volume` has.
+fn build_props_param(volume: &NodeVolume) -> Result<Vec<u8>> {
+    use pw::spa::pod::{Object, Property, Value, ValueArray};
+
+    let mut properties = Vec::new();
+    if let Some(value) = volume.volume {
+        properties.push(Property::new(
+            libspa::sys::SPA_PROP_volume,
+            Value::Float(value),
+        ));
+    }
+    if let Some(mute) = volume.mute {
+        properties.push(Property::new(libspa::sys::SPA_PROP_mute, Value::Bool(mute)));
+    }
+    if let Some(values) = &volume.channel_volumes {
+        properties.push(Property::new(
+            libspa::sys::SPA_PROP_channelVolumes,
+            Value::ValueArray(ValueArray::Float(values.clone())),
+        ));
+    }
+    if let Some(values) = &volume.soft_volumes {
+        properties.push(Property::new(
+            libspa::sys::SPA_PROP_softVolumes,
+            Value::ValueArray(ValueArray::Float(values.clone())),
+        ));
+    }
+    if let Some(mute) = volume.soft_mute {
+        properties.push(Property::new(
+            libspa::sys::SPA_PROP_softMute,
+            Value::Bool(mute),
+        ));
+    }
+    let obj = Object {
+        type_: pw::spa::utils::SpaTypes::ObjectParamProps.as_raw(),
+        id: pw::spa::param::ParamType::Props.as_raw(),
+        properties,
+    };
+    let serialized = pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &Value::Object(obj),
+    )
+    .context("Failed to serialize PipeWire props pod")?;
+
+    Ok(serialized.0.into_inner())
+}
+
+/// Profile entry of an EnumProfile or Profile param.
+fn device_profile_from_param(param: &pw::spa::pod::Pod) -> Option<DeviceProfile> {
+    use pw::spa::pod::{deserialize::PodDeserializer, Value};
+
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return None;
+    };
+    let mut profile = DeviceProfile {
+        index: -1,
+        name: String::new(),
+        description: String::new(),
+        priority: 0,
+        available: true,
+    };
+    for property in &object.properties {
+        match (property.key, &property.value) {
+            (libspa::sys::SPA_PARAM_PROFILE_index, Value::Int(index)) => profile.index = *index,
+            (libspa::sys::SPA_PARAM_PROFILE_name, Value::String(name)) => {
+                profile.name = name.clone()
+            }
+            (libspa::sys::SPA_PARAM_PROFILE_description, Value::String(description)) => {
+                profile.description = description.clone()
+            }
+            (libspa::sys::SPA_PARAM_PROFILE_priority, Value::Int(priority)) => {
+                profile.priority = *priority
+            }
+            (libspa::sys::SPA_PARAM_PROFILE_available, Value::Id(available)) => {
+                profile.available = available.0 != libspa::sys::SPA_PARAM_AVAILABILITY_no
+            }
+            _ => {}
+        }
+    }
+    (profile.index >= 0 && !profile.name.is_empty()).then_some(profile)
+}
+
+/// Builds the Profile pod selecting profile `index`. `save` stays false so the
+/// session manager does not remember our temporary choice.
+fn build_profile_param(index: i32) -> Result<Vec<u8>> {
+    use pw::spa::pod::{Object, Property, Value};
+
+    let obj = Object {
+        type_: pw::spa::utils::SpaTypes::ObjectParamProfile.as_raw(),
+        id: pw::spa::param::ParamType::Profile.as_raw(),
+        properties: vec![
+            Property::new(libspa::sys::SPA_PARAM_PROFILE_index, Value::Int(index)),
+            Property::new(libspa::sys::SPA_PARAM_PROFILE_save, Value::Bool(false)),
+        ],
+    };
+    let serialized = pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &Value::Object(obj),
+    )
+    .context("Failed to serialize PipeWire profile pod")?;
+
+    Ok(serialized.0.into_inner())
+}
+
+/// Resolves `--target auto` to the node name of the best AC-3 capable sink.
+fn select_auto_target(
+    mainloop: &MainLoop,
+    core: &pw::core::Core,
+    watch: &RegistryWatch,
+) -> Result<String> {
+    let candidates = collect_sink_candidates(mainloop, core, watch)?;
+    let target = graph::pick_ac3_target(&candidates).ok_or_else(|| {
+        anyhow!("--target auto found no HDMI/IEC958 sink (see `pw-ac3-live list-targets`)")
+    })?;
+    if target.supports_ac3() {
+        info!(
+            "Auto target: {} (id {}, {})",
+            target.name,
+            target.id,
+            target.capabilities()
+        );
+    } else {
+        warn!(
+            "Auto target: {} (id {}) does not advertise AC-3 passthrough ({}); enable AC3 in its IEC958 codecs",
+            target.name,
+            target.id,
+            target.capabilities()
+        );
+    }
+    Ok(if target.name.is_empty() {
+        target.id.to_string()
+    } else {
+        target.name.clone()
+    })
+}
+
+/// Owns the FL->FL / FR->FR links from the playback stream to an explicit
+/// target (autoconnect is off then) and keeps them exactly that way.
+struct PortLinker {
+    core: pw::core::Core,
+    registry: Rc<pw::registry::Registry>,
+    graph: Rc<RefCell<Graph>>,
+    /// Node name or ID; with `follow_default`, the current default sink (empty
+    /// until one is known).
+    target: RefCell<String>,
+    follow_default: bool,
+    /// Recorded once the target is seen, so it is found again under a new
+    /// node ID after a replug.
+    identity: RefCell<Option<TargetIdentity>>,
+    present: Cell<bool>,
+    links: RefCell<Vec<(PortPair, pw::link::Link)>>,
+    verified: Cell<bool>,
+    /// The target routes back into our input; no links are made.
+    refused: Cell<bool>,
+}
+
+impl PortLinker {
+    fn new(core: &pw::core::Core, watch: &RegistryWatch, target: String) -> Self {
+        Self {
+            core: core.clone(),
+            registry: watch.registry.clone(),
+            graph: watch.graph.clone(),
+            target: RefCell::new(target),
+            follow_default: false,
+            identity: RefCell::new(None),
+            present: Cell::new(false),
+            links: RefCell::new(Vec::new()),
+            verified: Cell::new(false),
+            refused: Cell::new(false),
+        }
+    }
+
+    /// Links to the sink in `default.audio.sink` and moves along when it changes.
+    fn following_default(core: &pw::core::Core, watch: &RegistryWatch) -> Self {
+        Self {
+            follow_default: true,
+            ..Self::new(core, watch, String::new())
+        }
+    }
+
+    fn target_present(&self) -> bool {
+        self.present.get()
+    }
+
+    /// Switches to a new default sink, dropping the links to the old one.
+    fn retarget_to_default(&self) {
+        if !self.follow_default {
+            return;
+        }
+        let Some(sink) = self.graph.borrow().default_sink_target() else {
+            // Unset, our own input (takeover) or not in the graph yet.
+            return;
+        };
+        if *self.target.borrow() == sink {
+            return;
+        }
+        let previous = self.target.replace(sink);
+        if previous.is_empty() {
+            info!("Following the default sink {}", self.target.borrow());
+        } else {
+            info!(
+                "Default sink changed from {} to {}; moving {}",
+                previous,
+                self.target.borrow(),
+                PLAYBACK_NODE_NAME
+            );
+        }
+        for (_, link) in self.links.borrow_mut().drain(..) {
+            if let Err(e) = self.core.destroy_object(link) {
+                warn!("Failed to remove playback link to {}: {}", previous, e);
+            }
+        }
+        *self.identity.borrow_mut() = None;
+        self.present.set(false);
+        self.verified.set(false);
+    }
+
+    fn resolve_target(&self, graph: &Graph) -> Option<u32> {
+        let target = self.target.borrow();
+        if target.is_empty() {
+            return None;
+        }
+        let mut identity = self.identity.borrow_mut();
+        match &*identity {
+            Some(known) => graph.find_target(known),
+            None => {
+                let node = graph.resolve_node(&target)?;
+                *identity = graph.target_identity(node);
+                Some(node)
+            }
+        }
+    }
+
+    /// Creates missing links and removes stray ones; called from the loop timer.
+    fn reconcile(&self) {
+        self.retarget_to_default();
+        let target = self.target.borrow().clone();
+        let (actions, output_node, target_node) = {
+            let graph = self.graph.borrow();
+            let target_node = self.resolve_target(&graph);
+            match (target_node, self.present.replace(target_node.is_some())) {
+                (None, true) => {
+                    warn!(
+                        "Playback target {} disappeared; holding until it comes back",
+                        target
+                    );
+                    // The server removed the links together with the sink.
+                    self.links.borrow_mut().clear();
+                    self.verified.set(false);
+                }
+                (Some(node), false) => {
+                    info!("Playback target {} is present (node {})", target, node)
+                }
+                _ => {}
+            }
+            let (Some(output_node), Some(target_node)) = (
+                graph.node_by_name(PLAYBACK_NODE_NAME).map(|node| node.id),
+                target_node,
+            ) else {
+                return;
+            };
+            if let Some(route) = route_into_input(&graph, target_node) {
+                if !self.refused.replace(true) {
+                    log::error!(
+                        "Refusing to link {} to {}: {} feeds back into the encoder",
+                        PLAYBACK_NODE_NAME,
+                        target,
+                        route_description(&graph, &route)
+                    );
+                }
+                // Links made before the loop appeared are removed by LoopGuard.
+                self.links.borrow_mut().clear();
+                self.verified.set(false);
+                return;
+            }
+            if self.refused.replace(false) {
+                info!("{} no longer routes back into the encoder", target);
+            }
+            let Some(plan) = graph.plan_links(output_node, target_node, &PLAYBACK_LINK_CHANNELS)
+            else {
+                return;
+            };
+            let owned: Vec<PortPair> = self.links.borrow().iter().map(|(pair, _)| *pair).collect();
+            (
+                graph.reconcile_links(output_node, target_node, &plan, &owned),
+                output_node,
+                target_node,
+            )
+        };
+        if !actions.create.is_empty() {
+            info!(
+                "Linking {} FL/FR to {} (node {})",
+                PLAYBACK_NODE_NAME, target, target_node
+            );
+        }
+
+        let mut links = self.links.borrow_mut();
+        let (stale, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut *links)
+            .into_iter()
+            .partition(|(pair, _)| actions.drop_owned.contains(pair));
+        *links = kept;
+        for (_, link) in stale {
+            if let Err(e) = self.core.destroy_object(link) {
+                warn!("Failed to remove stale playback link: {}", e);
+            }
+        }
+        for pair in &actions.create {
+            let props = properties! {
+                "link.output.node" => output_node.to_string(),
+                "link.output.port" => pair.output_port.to_string(),
+                "link.input.node" => target_node.to_string(),
+                "link.input.port" => pair.input_port.to_string(),
+                "object.linger" => "false",
+            };
+            match self
+                .core
+                .create_object::<pw::link::Link>("link-factory", &props)
+            {
+                Ok(link) => links.push((*pair, link)),
+                Err(e) => warn!(
+                    "Failed to link port {} -> {}: {}",
+                    pair.output_port, pair.input_port, e
+                ),
+            }
+        }
+        for link_id in &actions.destroy {
+            warn!(
+                "Removing unexpected link {} from {}",
+                link_id, PLAYBACK_NODE_NAME
+            );
+            if let Err(e) = self.registry.destroy_global(*link_id).into_result() {
+                warn!("Could not remove link {}: {}", link_id, e);
+            }
+        }
+
+        if actions.verified != self.verified.replace(actions.verified) {
+            if actions.verified {
+                info!(
+                    "Verified links {} FL->FL, FR->FR to {}",
+                    PLAYBACK_NODE_NAME, target
+                );
+            } else {
+                warn!(
+                    "Links from {} to {} changed; restoring FL->FL, FR->FR",
+                    PLAYBACK_NODE_NAME, target
+                );
+            }
+        }
+    }
+
+    /// Destroys the links created by us.
+    fn unlink(&self, mainloop: &MainLoop) {
+        let links = std::mem::take(&mut *self.links.borrow_mut());
+        if links.is_empty() {
+            return;
+        }
+        for (_, link) in links {
+            if let Err(e) = self.core.destroy_object(link) {
+                warn!("Failed to remove playback link: {}", e);
+            }
+        }
+        if let Err(e) = roundtrip(mainloop, &self.core) {
+            warn!("Failed to flush playback link removal: {}", e);
+        } else {
+            info!("Removed playback links to {}", self.target.borrow());
+        }
+    }
+}
+
+/// Keeps the encoder running while the playback stream has nowhere to go:
+/// encoded output is discarded and the stream plays silence until its target
+/// is back. A stream that failed or got disconnected is connected again.
+struct PlaybackHold {
+    stream: Rc<pw::stream::Stream>,
+    formats: Vec<Vec<u8>>,
+    flags: StreamFlags,
+    /// Read by the process callback, which plays silence while it is set.
+    holding: Arc<AtomicBool>,
+    output: Arc<Mutex<Consumer<u8>>>,
+    /// Set by `state_changed` on the error and unconnected states.
+    stream_failed: Rc<Cell<bool>>,
+    last_connect: Cell<Instant>,
+}
+
+impl PlaybackHold {
+    /// Minimum spacing between two reconnection attempts.
+    const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+    /// Connects the stream; `target_id` is only known for the first connect,
+    /// a replugged target gets a new ID and is linked by `PortLinker`.
+    fn connect(&self, target_id: Option<u32>) -> Result<()> {
+        let mut params = self
+            .formats
+            .iter()
+            .map(|bytes| {
+                pw::spa::pod::Pod::from_bytes(bytes)
+                    .ok_or_else(|| anyhow!("Failed to parse playback format pod bytes"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.last_connect.set(Instant::now());
+        self.stream
+            .connect(Direction::Output, target_id, self.flags, &mut params)?;
+        Ok(())
+    }
+
+    /// Updates the hold state and drains the output while holding; called from
+    /// the loop timer.
+    fn reconcile(&self, target_present: bool) {
+        let failed = self.stream_failed.get();
+        if failed && self.last_connect.get().elapsed() >= Self::RECONNECT_INTERVAL {
+            warn!("Playback stream is not connected; reconnecting");
+            if let Err(e) = self.stream.disconnect() {
+                warn!("Failed to disconnect the playback stream: {}", e);
+            }
+            if let Err(e) = self.connect(None) {
+                warn!("Failed to reconnect the playback stream: {e:#}");
+            }
+        }
+
+        let hold = failed || !target_present;
+        if hold != self.holding.swap(hold, Ordering::Relaxed) {
+            if hold {
+                warn!("Playback on hold: encoder keeps running, output is discarded");
+            } else {
+                info!("Playback resumed");
+            }
+        }
+        if hold {
+            if let Ok(mut consumer) = self.output.try_lock() {
+                discard_output(&mut consumer);
+            }
+        }
+    }
+}
+
+/// Drops everything queued in the output ring.
+fn discard_output(consumer: &mut Consumer<u8>) {
+    let queued = consumer.slots();
+    if let Ok(chunk) = consumer.read_chunk(queued) {
+        chunk.commit_all();
+    }
+}
+
+/// Keeps the playback stream and the sinks it feeds at unity gain while the
+/// encoder runs; any software volume turns the IEC 61937 stream into noise.
+struct VolumeGuard {
+    watch: Rc<RegistryWatch>,
+    mode: VolumeGuardMode,
+    /// Last reported deviation and last forced reset, per node.
+    deviating: RefCell<BTreeMap<u32, (String, Option<Instant>)>>,
+}
+
+impl VolumeGuard {
+    /// Minimum spacing between two resets of the same node, so a session
+    /// manager fighting back does not flood the daemon.
+    const FORCE_INTERVAL: Duration = Duration::from_secs(1);
+
+    fn new(watch: Rc<RegistryWatch>, mode: VolumeGuardMode) -> Self {
+        Self {
+            watch,
+            mode,
+            deviating: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Checks the guarded nodes; called from the loop timer.
+    fn reconcile(&self) {
+        let checks: Vec<(u32, String, NodeVolume)> = {
+            let graph = self.watch.graph.borrow();
+            let Some(output) = graph.node_by_name(PLAYBACK_NODE_NAME) else {
+                return;
+            };
+            std::iter::once(output.id)
+                .chain(graph.linked_sinks(output.id))
+                .filter_map(|id| {
+                    let node = graph.get(id)?;
+                    let label = node.prop("node.name").unwrap_or_default().to_string();
+                    Some((id, label, graph.node_volume(id)?.clone()))
+                })
+                .collect()
+        };
+
+        let mut deviating = self.deviating.borrow_mut();
+        deviating.retain(|id, _| checks.iter().any(|(checked, _, _)| checked == id));
+        for (id, label, volume) in checks {
+            let deviations = volume.deviations();
+            if deviations.is_empty() {
+                if deviating.remove(&id).is_some() {
+                    info!("Volume of {} ({}) is back at unity", label, id);
+                }
+                continue;
+            }
+            let description = deviations.join(", ");
+            let (reported, forced_at) =
+                deviating.entry(id).or_insert_with(|| (String::new(), None));
+            let is_new = *reported != description;
+            match self.mode {
+                VolumeGuardMode::Off => {}
+                VolumeGuardMode::Warn => {
+                    if is_new {
+                        log::error!(
+                            "{} ({}) is not at unity gain ({}); the AC-3 stream will be noise. Set it to 100% and unmute it.",
+                            label, id, description
+                        );
+                    }
+                }
+                VolumeGuardMode::Force => {
+                    let now = Instant::now();
+                    if !forced_at.is_some_and(|at| now.duration_since(at) < Self::FORCE_INTERVAL) {
+                        *forced_at = Some(now);
+                        warn!(
+                            "{} ({}) left unity gain ({}); forcing it back to 100%",
+                            label, id, description
+                        );
+                        if let Err(e) = self.watch.set_node_volume(id, &volume.to_unity()) {
+                            warn!("Failed to reset the volume of {}: {e:#}", label);
+                        }
+                    }
+                }
+            }
+            *reported = description;
+        }
+    }
+}
+
+/// Our capture nodes; nothing played by `pw-ac3-live-output` may reach them.
+const LOOP_GUARDED_NODES: [&str; 2] = [INPUT_NODE_NAME, VOICE_NODE_NAME];
+
+/// Shortest route from `node_id` into one of our capture nodes, if any.
+fn route_into_input(graph: &Graph, node_id: u32) -> Option<Vec<u32>> {
+    LOOP_GUARDED_NODES
+        .iter()
+        .filter_map(|name| graph.node_by_name(name).map(|node| node.id))
+        .filter_map(|input| {
+            if input == node_id {
+                Some(vec![node_id])
+            } else {
+                graph.route_path(node_id, input)
+            }
+        })
+        .min_by_key(Vec::len)
+}
+
+/// `a -> b -> c` with node names, falling back to IDs.
+fn route_description(graph: &Graph, route: &[u32]) -> String {
+    route
+        .iter()
+        .map(|id| {
+            graph
+                .get(*id)
+                .and_then(|node| node.prop("node.name"))
+                .map_or_else(|| id.to_string(), str::to_string)
+        })
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// Removes links that route the playback stream back into our capture nodes
+/// (e.g. autoconnect to a default sink that is our own input): the encoded
+/// stream would be encoded again and again into a storm of noise.
+struct LoopGuard {
+    watch: Rc<RegistryWatch>,
+    /// Links whose removal was requested but that are still in the graph.
+    removing: RefCell<Vec<u32>>,
+}
+
+impl LoopGuard {
+    fn new(watch: Rc<RegistryWatch>) -> Self {
+        Self {
+            watch,
+            removing: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Looks for routes back into our input; called from the loop timer.
+    fn reconcile(&self) {
+        let mut loops = Vec::new();
+        {
+            let graph = self.watch.graph.borrow();
+            let Some(output) = graph.node_by_name(PLAYBACK_NODE_NAME).map(|node| node.id) else {
+                return;
+            };
+            self.removing
+                .borrow_mut()
+                .retain(|link| graph.get(*link).is_some());
+            for input in LOOP_GUARDED_NODES
+                .iter()
+                .filter_map(|name| graph.node_by_name(name).map(|node| node.id))
+            {
+                let Some(route) = graph.route_path(output, input) else {
+                    continue;
+                };
+                let route = route_description(&graph, &route);
+                for link in graph.feedback_links(output, input) {
+                    loops.push((link, route.clone()));
+                }
+            }
+        }
+
+        let mut removing = self.removing.borrow_mut();
+        for (link, route) in loops {
+            if removing.contains(&link) {
+                continue;
+            }
+            log::error!(
+                "Routing loop {} feeds the AC-3 output back into the encoder; removing link {}. Check the default sink and the target of {}.",
+                route, link, PLAYBACK_NODE_NAME
+            );
+            match self.watch.registry.destroy_global(link).into_result() {
+                Ok(_) => removing.push(link),
+                Err(e) => warn!("Could not remove link {}: {}", link, e),
+            }
+        }
+    }
+}
+
+/// Card profile switched for the output path, put back on exit.
+struct CardProfileGuard {
+    watch: Rc<RegistryWatch>,
+    device_id: u32,
+    original: DeviceProfile,
+}
+
+impl CardProfileGuard {
+    /// Switches the card behind the output to the profile `output_mode` needs:
+    /// `off` for direct ALSA, so PipeWire releases the PCM, or the profile
+    /// providing the target sink. Returns `None` when nothing was switched.
+    fn apply(
+        mainloop: &MainLoop,
+        core: &pw::core::Core,
+        watch: Rc<RegistryWatch>,
+        output_mode: &OutputMode,
+        target_node: Option<&str>,
+    ) -> Result<Option<Self>> {
+        // Globals first, then the profile params of the devices bound meanwhile.
+        roundtrip(mainloop, core)?;
+        roundtrip(mainloop, core)?;
+
+        let (request, device_id) = {
+            let graph = watch.graph.borrow();
+            match (output_mode, target_node) {
+                (OutputMode::AlsaDirect { device, .. }, _) => {
+                    let card = graph::alsa_card_of(device).ok_or_else(|| {
+                        anyhow!("Cannot tell the ALSA card of '{device}' to switch its profile")
+                    })?;
+                    let device_id = graph
+                        .device_for_alsa_card(&card)
+                        .ok_or_else(|| anyhow!("No PipeWire device for ALSA card {card}"))?;
+                    (ProfileRequest::Off, device_id)
+                }
+                (OutputMode::Pipewire, Some(target)) => {
+                    let device_id = graph
+                        .device_for_sink(target)
+                        .ok_or_else(|| anyhow!("No PipeWire device found for target '{target}'"))?;
+                    let sink_name = graph
+                        .resolve_node(target)
+                        .and_then(|id| graph.get(id))
+                        .and_then(|node| node.prop("node.name"))
+                        .unwrap_or(target);
+                    let output = graph::output_profile_for_sink(sink_name).ok_or_else(|| {
+                        anyhow!("Cannot tell the card profile providing '{sink_name}'")
+                    })?;
+                    (ProfileRequest::Output(output), device_id)
+                }
+                _ => {
+                    info!("No explicit output device; leaving card profiles alone.");
+                    return Ok(None);
+                }
+            }
+        };
+
+        let (original, wanted) = {
+            let graph = watch.graph.borrow();
+            let original = graph
+                .active_profile(device_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("PipeWire device {device_id} reported no active profile"))?;
+            let wanted =
+                graph::pick_profile(graph.device_profiles(device_id), Some(&original), &request)
+                    .cloned()
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "PipeWire device {device_id} has no available profile for {request:?}"
+                        )
+                    })?;
+            (original, wanted)
+        };
+        if wanted.index == original.index {
+            info!(
+                "Card profile of device {} already suits the output: {}",
+                device_id, original.name
+            );
+            return Ok(None);
+        }
+
+        if !watch.set_profile(device_id, wanted.index)? {
+            return Err(anyhow!("PipeWire device {device_id} disappeared"));
+        }
+        // The profile change, then the nodes it removes or adds.
+        roundtrip(mainloop, core)?;
+        roundtrip(mainloop, core)?;
+        info!(
+            "Card profile of device {} switched from {} to {}",
+            device_id, original.name, wanted.name
+        );
+        Ok(Some(Self {
+            watch,
+            device_id,
+            original,
+        }))
+    }
+
+    fn restore(&self, mainloop: &MainLoop, core: &pw::core::Core) {
+        match self.watch.set_profile(self.device_id, self.original.index) {
+            Ok(true) => {
+                if let Err(e) = roundtrip(mainloop, core) {
+                    warn!("Failed to restore the card profile: {e:#}");
+                    return;
+                }
+                info!(
+                    "Card profile of device {} restored to {}",
+                    self.device_id, self.original.name
+                );
+            }
+            Ok(false) => warn!(
+                "PipeWire device {} is gone; card profile {} not restored",
+                self.device_id, self.original.name
+            ),
+            Err(e) => warn!("Failed to restore the card profile: {e:#}"),
+        }
+    }
+}
+
+/// Makes `pw-ac3-live-input` the default sink and moves playback streams to
+/// it, then puts the previous default back. The previous default is kept in a
+/// state file while the takeover is active, so a crashed run can be undone.
+struct DefaultSinkTakeover {
+    watch: Rc<RegistryWatch>,
+    state_path: PathBuf,
+    state: TakeoverState,
+    moved: RefCell<Vec<u32>>,
+}
+
+impl DefaultSinkTakeover {
+    fn start(
+        mainloop: &MainLoop,
+        core: &pw::core::Core,
+        watch: Rc<RegistryWatch>,
+        state_path: PathBuf,
+    ) -> Result<Self> {
+        // Globals first, then the properties of the metadata bound meanwhile.
+        roundtrip(mainloop, core)?;
+        roundtrip(mainloop, core)?;
+
+        let state = match default_sink::load_state(&state_path)? {
+            Some(state) => {
+                warn!(
+                    "Found default sink state from an earlier run ({}); keeping its previous default {:?}",
+                    state_path.display(),
+                    state.previous_default
+                );
+                state
+            }
+            None => TakeoverState {
+                previous_default: watch
+                    .graph
+                    .borrow()
+                    .metadata(0, CONFIGURED_DEFAULT_SINK_KEY)
+                    .and_then(default_sink::parse_sink_name)
+                    .filter(|name| name != INPUT_NODE_NAME),
+            },
+        };
+        default_sink::save_state(&state_path, &state)?;
+
+        let json = default_sink::sink_name_json(INPUT_NODE_NAME);
+        if !watch.set_metadata(
+            0,
+            CONFIGURED_DEFAULT_SINK_KEY,
+            Some("Spa:String:JSON"),
+            Some(&json),
+        ) {
+            default_sink::clear_state(&state_path)?;
+            return Err(anyhow!(
+                "No PipeWire 'default' metadata object; is a session manager running?"
+            ));
+        }
+        info!(
+            "Default sink set to {} (previous: {})",
+            INPUT_NODE_NAME,
+            state.previous_default.as_deref().unwrap_or("<none>")
+        );
+
+        Ok(Self {
+            watch,
+            state_path,
+            state,
+            moved: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Moves playback streams that appeared since the last call; called from
+    /// the loop timer.
+    fn reconcile(&self) {
+        let (target, streams) = {
+            let graph = self.watch.graph.borrow();
+            let Some(input) = graph.node_by_name(INPUT_NODE_NAME) else {
+                return;
+            };
+            let target = input
+                .prop("object.serial")
+                .map(str::to_string)
+                .unwrap_or_else(|| input.id.to_string());
+            let mut moved = self.moved.borrow_mut();
+            moved.retain(|id| graph.get(*id).is_some());
+            let streams: Vec<(u32, String)> = graph
+                .streams_to_move(&moved)
+                .into_iter()
+                .map(|id| {
+                    let node = graph.get(id).expect("stream listed by the graph");
+                    let name = node
+                        .prop("application.name")
+                        .or_else(|| node.prop("node.name"))
+                        .unwrap_or_default()
+                        .to_string();
+                    (id, name)
+                })
+                .collect();
+            (target, streams)
+        };
+
+        for (id, name) in streams {
+            if self
+                .watch
+                .set_metadata(id, TARGET_OBJECT_KEY, Some("Spa:Id"), Some(&target))
+            {
+                info!("Moved stream {} ({}) to {}", id, name, INPUT_NODE_NAME);
+                self.moved.borrow_mut().push(id);
+            }
+        }
+    }
+
+    /// Restores the previous default, releases the moved streams and removes
+    /// the state file.
+    fn restore(&self, mainloop: &MainLoop, core: &pw::core::Core) {
+        let still_present: Vec<u32> = {
+            let graph = self.watch.graph.borrow();
+            self.moved
+                .borrow()
+                .iter()
+                .copied()
+                .filter(|id| graph.get(*id).is_some())
+                .collect()
+        };
+        for id in still_present {
+            self.watch.set_metadata(id, TARGET_OBJECT_KEY, None, None);
+        }
+        if let Err(e) =
+            restore_previous_default(mainloop, core, &self.watch, &self.state, &self.state_path)
+        {
+            warn!("Failed to restore the default sink: {e:#}");
+        }
+    }
+}
+
+/// Puts back the default sink recorded in `state` and removes the state file.
+fn restore_previous_default(
+    mainloop: &MainLoop,
+    core: &pw::core::Core,
+    watch: &RegistryWatch,
+    state: &TakeoverState,
+    state_path: &Path,
+) -> Result<()> {
+    let json = state
+        .previous_default
+        .as_deref()
+        .map(default_sink::sink_name_json);
+    let type_ = json.as_ref().map(|_| "Spa:String:JSON");
+    if !watch.set_metadata(0, CONFIGURED_DEFAULT_SINK_KEY, type_, json.as_deref()) {
+        return Err(anyhow!(
+            "No PipeWire 'default' metadata object; state kept in {}",
+            state_path.display()
+        ));
+    }
+    roundtrip(mainloop, core)?;
+    default_sink::clear_state(state_path)?;
+    info!(
+        "Default sink restored to {}",
+        state
+            .previous_default
+            .as_deref()
+            .unwrap_or("<session manager choice>")
+    );
+    Ok(())
+}
+
+/// Undoes the default sink takeover of a run that did not exit cleanly.
+/// Returns false when `state_path` holds no takeover state.
+pub fn restore_default_sink(state_path: &Path) -> Result<bool> {
+    let Some(state) = default_sink::load_state(state_path)? else {
+        return Ok(false);
+    };
+    pw::init();
+    let mainloop = MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+    let watch = watch_registry(&core)?;
+    roundtrip(&mainloop, &core)?;
+    roundtrip(&mainloop, &core)?;
+    restore_previous_default(&mainloop, &core, &watch, &state, state_path)?;
+    Ok(true)
+}
+
+/// Lists the PipeWire sinks with their IEC958 passthrough capabilities.
+pub fn list_sink_candidates() -> Result<Vec<SinkCandidate>> {
+    pw::init();
+    let mainloop = MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+    let watch = watch_registry(&core)?;
+    collect_sink_candidates(&mainloop, &core, &watch)
+}
+
+/// Runs the main PipeWire event loop.
+pub fn run_pipewire_loop(
+    input_producer: Producer<f32>,
+    output_consumer: Consumer<u8>,
+    target_node: Option<String>,
+    use_stdout: bool,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    let output_mode = if use_stdout {
+        OutputMode::Stdout
+    } else {
+        OutputMode::Pipewire
+    };
+
+    run_pipewire_loop_with_config(
+        input_producer,
+        None,
+        output_consumer,
+        target_node,
+        output_mode,
+        running,
+        PipewireConfig::default(),
+    )
+}
+
+/// Ring ends and output threads that outlive one PipeWire connection, so the
+/// encoder keeps running while the client reconnects.
+struct SharedEnds {
+    input: Arc<Mutex<Producer<f32>>>,
+    voice: Option<Arc<Mutex<Producer<f32>>>>,
+    /// Until the stdout/ALSA thread or the first playback stream takes it.
+    output: Option<Consumer<u8>>,
+    playback_output: Option<Arc<Mutex<Consumer<u8>>>>,
+    alsa_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl SharedEnds {
+    fn playback_output(&mut self) -> Arc<Mutex<Consumer<u8>>> {
+        if self.playback_output.is_none() {
+            let consumer = self.output.take().expect("output ring taken twice");
+            self.playback_output = Some(Arc::new(Mutex::new(consumer)));
+        }
+        self.playback_output
+            .clone()
+            .expect("playback output set above")
+    }
+}
+
+/// How a connection to the PipeWire daemon ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionEnd {
+    Stopped,
+    Disconnected,
+}
+
+/// Wait before reconnection attempt `attempt` (0-based): doubling from
+/// 500 ms, capped at 5 s.
+fn reconnect_delay(attempt: u32) -> Duration {
+    Duration::from_millis(500u64.saturating_mul(1 << attempt.min(4))).min(Duration::from_secs(5))
+}
+
+/// Runs the main PipeWire event loop. When `voice_producer` is set, a second
+/// stereo sink for voice chat is created next to `pw-ac3-live-input`.
+///
+/// When the daemon goes away (restart, update), both streams are recreated on
+/// a new connection with the same configuration; the rings, and with them the
+/// encoder, stay alive meanwhile.
+pub fn run_pipewire_loop_with_config(
+    input_producer: Producer<f32>,
+    voice_producer: Option<Producer<f32>>,
+    output_consumer: Consumer<u8>,
+    target_node: Option<String>,
+    output_mode: OutputMode,
+    running: Arc<AtomicBool>,
+    config: PipewireConfig,
+) -> Result<()> {
+    info!("Initializing PipeWire client...");
+    if config.input_layout == InputLayout::Surround71 {
+        info!(
+            "Input layout 7.1: folding RL/RR (x{:.3}) and SL/SR (x{:.3}) into 5.1 surrounds",
+            config.downmix.back_gain, config.downmix.side_gain
+        );
+    }
+
+    pw::init();
+
+    let mut shared = SharedEnds {
+        input: Arc::new(Mutex::new(input_producer)),
+        voice: voice_producer.map(|producer| Arc::new(Mutex::new(producer))),
+        output: Some(output_consumer),
+        playback_output: None,
+        alsa_thread: None,
+    };
+    // Reconnection attempts since the connection was lost; `None` until then.
+    let mut attempt: Option<u32> = None;
+    loop {
+        match run_session(
+            &mut shared,
+            target_node.clone(),
+            output_mode.clone(),
+            &running,
+            &config,
+        ) {
+            Ok(SessionEnd::Stopped) => return Ok(()),
+            Ok(SessionEnd::Disconnected) => attempt = Some(0),
+            // The first connection reports setup errors; later ones retry.
+            Err(e) => match attempt {
+                Some(failed) => {
+                    warn!("PipeWire reconnection failed: {e:#}");
+                    attempt = Some(failed.saturating_add(1));
+                }
+                None => return Err(e),
+            },
+        }
+        let delay = reconnect_delay(attempt.unwrap_or_default());
+        info!("Reconnecting to PipeWire in {} ms", delay.as_millis());
+        let deadline = Instant::now() + delay;
+        while Instant::now() < deadline {
+            if !running.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            // Nobody plays the output meanwhile; keep the encoder from blocking.
+            if let Some(output) = &shared.playback_output {
+                if let Ok(mut consumer) = output.try_lock() {
+                    discard_output(&mut consumer);
+                }
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+/// One connection to the PipeWire daemon: creates the streams and runs the
+/// loop until shutdown or until the connection is lost.
+fn run_session(
+    shared: &mut SharedEnds,
+    target_node: Option<String>,
+    output_mode: OutputMode,
+    running: &Arc<AtomicBool>,
+    config: &PipewireConfig,
+) -> Result<SessionEnd> {
+    let sample_rate_hz = config.sample_rate_hz;
+    let input_layout = config.input_layout;
+    let node_latency = if config.node_latency.trim().is_empty() {
+        "64/48000"
+    } else {
+        config.node_latency.as_str()
+    };
+    let requested_latency_frames = node_latency
+        .split('/')
+        .next()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|frames| *frames > 0);
+
+    let mainloop = MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+
+    let disconnected = Rc::new(Cell::new(false));
+    let error_disconnected = disconnected.clone();
+    let error_mainloop = mainloop.clone();
+    let _core_listener = core
+        .add_listener_local()
+        .error(move |id, _seq, res, message| {
+            if id == pw::core::PW_ID_CORE {
+                warn!("PipeWire connection error ({}): {}", res, message);
+                error_disconnected.set(true);
+                error_mainloop.quit();
+            }
+        })
+        .register();
+
+    // ------------------------------------------------------------------
+    // 1. Create Capture Stream (Virtual Sink)
+    // ------------------------------------------------------------------
+
+    let mut props = properties! {
+        *pw::keys::MEDIA_CLASS => "Audio/Sink",
+        *pw::keys::NODE_NAME => INPUT_NODE_NAME,
+        *pw::keys::NODE_DESCRIPTION => "AC-3 Encoder Input",
+        *pw::keys::APP_NAME => "pw-ac3-live",
+        "audio.channels" => input_layout.channels().to_string(),
+        "audio.position" => input_layout.audio_position(),
+        "audio.format" => "F32LE",
+        "node.latency" => node_latency,
+    };
+    // No forced/locked rate: the sink runs at whatever rate the graph picks and the
+    // encoder resamples, so we do not dictate the rate for every other client.
+    if let Some(frames) = requested_latency_frames {
+        let force_quantum = frames.to_string();
+        props.insert("node.force-quantum", force_quantum.as_str());
+        props.insert("node.lock-quantum", "true");
+        info!(
+            "Capture stream requesting forced quantum: {} frames",
+            frames
+        );
+    }
+
+    let data = shared.input.clone();
+    let capture_layout_logged = Arc::new(AtomicBool::new(false));
+    let capture_layout_renegotiated = capture_layout_logged.clone();
+    let capture_rate_hz = config.capture_rate_hz.clone();
+    let capture_channel_mask = config.capture_channel_mask.clone();
+    let capture_channels = input_layout.channels();
+    let downmix = config.downmix;
+    // Written by param_changed, copied by the RT callback when the lock is free.
+    let negotiated_format = Arc::new(Mutex::new(CaptureFormat::new(capture_channels)));
+    let process_format = negotiated_format.clone();
+    let mut capture_format = CaptureFormat::new(capture_channels);
+    let mut capture_scratch = Vec::<f32>::new();
+    let mut interleaved_scratch = Vec::<f32>::new();
+    let mut planar_channel_scratch: [Vec<f32>; MAX_CAPTURE_CHANNELS] =
+        std::array::from_fn(|_| Vec::new());
+
+    // Create stream first
+    let capture_stream = pw::stream::Stream::new(&core, "ac3-encoder-capture", props)?;
+
+    // Add listener for process callback
+    let _capture_listener = capture_stream
+        .add_local_listener::<()>()
+        .state_changed(|_stream, _data, old, new| {
+            info!("Capture Stream state changed: {:?} -> {:?}", old, new);
+        })
+        .param_changed(move |_stream, _data, id, param| {
+            if id != pw::spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+            let Some(param) = param else {
+                return;
+            };
+            let mut info = AudioInfoRaw::new();
+            if info.parse(param).is_ok() {
                 info!(
                     "Capture format negotiated: {:?}, rate={}, channels={}",
                     info.format(),
                     info.rate(),
                     info.channels()
                 );
+                if info.rate() > 0 {
+                    capture_rate_hz.store(info.rate(), Ordering::Relaxed);
+                }
+                // Log the buffer layout again for the new format.
+                capture_layout_renegotiated.store(false, Ordering::Relaxed);
+
+                let channels = if info.flags().contains(AudioInfoRawFlags::UNPOSITIONED) {
+                    0
+                } else {
+                    (info.channels() as usize).min(MAX_SOURCE_CHANNELS)
+                };
+                let position = info.position();
+                let positions = &position[..channels];
+                let map = ChannelMap::from_positions(positions, input_layout);
+                capture_channel_mask.store(map.encoder_mask(input_layout), Ordering::Relaxed);
+                let sample_format = SampleFormat::from_audio_format(info.format());
+                if sample_format.is_none() {
+                    log::error!(
+                        "Unsupported capture sample format {:?}; writing silence until a supported format is negotiated",
+                        info.format()
+                    );
+                }
+                if let Ok(mut current) = negotiated_format.lock() {
+                    if current.channel_map != map {
+                        info!(
+                            "Capture channel map: {}",
+                            map.describe(positions, input_layout)
+                        );
+                    }
+                    *current = CaptureFormat {
+                        sample_format,
+                        channel_map: map,
+                    };
+                }
             }
         })
         .process(move |stream: &StreamRef, _data| {
@@ -642,97 +2866,142 @@ pub fn run_pipewire_loop_with_config(
                         return;
                     }
 
-                    interleaved_scratch.clear();
-
-                    // PipeWire often exposes a single interleaved port even for 5.1.
-                    if n_datas == 1 {
-                        let chunk = datas[0].chunk();
-                        let offset = chunk.offset() as usize;
-                        let size = chunk.size() as usize;
-                        let stride = chunk.stride().max(0) as usize;
-                        if !capture_layout_logged.swap(true, Ordering::Relaxed) {
-                            info!(
-                                "Capture buffer layout: datas={}, size={}, stride={}",
-                                n_datas, size, stride
-                            );
-                        }
-                        if size == 0 {
-                            return;
+                    if let Ok(format) = process_format.try_lock() {
+                        capture_format = *format;
+                    }
+                    let CaptureFormat {
+                        sample_format,
+                        channel_map,
+                    } = capture_format;
+                    capture_scratch.clear();
+
+                    match sample_format {
+                        None => {
+                            // Never decode bytes of an unknown format: feed silence
+                            // for the frames this buffer covers.
+                            let chunk = datas[0].chunk();
+                            let stride = chunk.stride().max(0) as usize;
+                            if stride == 0 {
+                                return;
+                            }
+                            let frames = chunk.size() as usize / stride;
+                            capture_scratch.resize(frames * capture_channels, 0.0);
                         }
+                        // PipeWire often exposes a single interleaved port even for 5.1.
+                        Some(sample_format) if n_datas == 1 => {
+                            let chunk = datas[0].chunk();
+                            let offset = chunk.offset() as usize;
+                            let size = chunk.size() as usize;
+                            let stride = chunk.stride().max(0) as usize;
+                            if !capture_layout_logged.swap(true, Ordering::Relaxed) {
+                                info!(
+                                    "Capture buffer layout: datas={}, size={}, stride={}",
+                                    n_datas, size, stride
+                                );
+                            }
+                            if size == 0 {
+                                return;
+                            }
 
-                        if let Some(raw_data) = datas[0].data() {
-                            if parse_interleaved_from_stride_into(
-                                raw_data,
-                                offset,
-                                size,
-                                stride,
-                                &mut interleaved_scratch,
-                            )
-                            .is_none()
-                            {
-                                let _ = parse_f32_interleaved_into(
+                            // Without a stride, assume every negotiated channel is present.
+                            let stride = if stride == 0 {
+                                channel_map.sources() * sample_format.bytes()
+                            } else {
+                                stride
+                            };
+                            if let Some(raw_data) = datas[0].data() {
+                                let _ = parse_interleaved_from_stride_into(
                                     raw_data,
                                     offset,
                                     size,
-                                    INPUT_CHANNELS,
-                                    &mut interleaved_scratch,
+                                    stride,
+                                    sample_format,
+                                    &channel_map,
+                                    capture_channels,
+                                    &mut capture_scratch,
                                 );
                             }
                         }
-                    } else {
-                        if !capture_layout_logged.swap(true, Ordering::Relaxed) {
-                            let stride = datas[0].chunk().stride().max(0);
-                            let size = datas[0].chunk().size();
-                            info!(
-                                "Capture buffer layout: datas={}, first_size={}, first_stride={}",
-                                n_datas, size, stride
-                            );
-                        }
-                        // Planar input path: gather channels and interleave.
-                        for samples in &mut planar_channel_scratch {
-                            samples.clear();
-                        }
-                        let mut samples_per_channel: Option<usize> = None;
-
-                        for (i, samples) in planar_channel_scratch
-                            .iter_mut()
-                            .enumerate()
-                            .take(INPUT_CHANNELS.min(n_datas))
-                        {
-                            let chunk = datas[i].chunk();
-                            let offset = chunk.offset() as usize;
-                            let size = chunk.size() as usize;
-                            if size == 0 {
-                                continue;
+                        Some(sample_format) => {
+                            if !capture_layout_logged.swap(true, Ordering::Relaxed) {
+                                let stride = datas[0].chunk().stride().max(0);
+                                let size = datas[0].chunk().size();
+                                info!(
+                                    "Capture buffer layout: datas={}, first_size={}, first_stride={}",
+                                    n_datas, size, stride
+                                );
+                            }
+                            // Planar input path: gather channels by position and interleave.
+                            for samples in &mut planar_channel_scratch {
+                                samples.clear();
                             }
+                            let mut samples_per_channel: Option<usize> = None;
 
-                            if let Some(raw_data) = datas[i].data() {
-                                if parse_f32_plane_into(raw_data, offset, size, samples).is_some() {
-                                    if samples.is_empty() {
-                                        continue;
+                            for (i, data) in
+                                datas.iter_mut().enumerate().take(channel_map.sources())
+                            {
+                                let Some(slot) =
+                                    channel_map.slot(i).filter(|slot| *slot < capture_channels)
+                                else {
+                                    continue;
+                                };
+                                let samples = &mut planar_channel_scratch[slot];
+                                let chunk = data.chunk();
+                                let offset = chunk.offset() as usize;
+                                let size = chunk.size() as usize;
+                                if size == 0 {
+                                    continue;
+                                }
+
+                                if let Some(raw_data) = data.data() {
+                                    if parse_plane_into(
+                                        raw_data,
+                                        offset,
+                                        size,
+                                        sample_format,
+                                        samples,
+                                    )
+                                    .is_some()
+                                    {
+                                        if samples.is_empty() {
+                                            continue;
+                                        }
+                                        samples_per_channel = Some(
+                                            samples_per_channel
+                                                .map(|n| n.min(samples.len()))
+                                                .unwrap_or(samples.len()),
+                                        );
                                     }
-                                    samples_per_channel = Some(
-                                        samples_per_channel
-                                            .map(|n| n.min(samples.len()))
-                                            .unwrap_or(samples.len()),
-                                    );
                                 }
                             }
-                        }
 
-                        let n_samples = match samples_per_channel {
-                            Some(0) | None => return,
-                            Some(n) => n,
-                        };
+                            let n_samples = match samples_per_channel {
+                                Some(0) | None => return,
+                                Some(n) => n,
+                            };
 
-                        interleaved_scratch.reserve(n_samples * INPUT_CHANNELS);
-                        for s in 0..n_samples {
-                            for channel in planar_channel_scratch.iter().take(INPUT_CHANNELS) {
-                                interleaved_scratch.push(channel.get(s).copied().unwrap_or(0.0));
+                            capture_scratch.reserve(n_samples * capture_channels);
+                            for s in 0..n_samples {
+                                for channel in
+                                    planar_channel_scratch.iter().take(capture_channels)
+                                {
+                                    capture_scratch
+                                        .push(channel.get(s).copied().unwrap_or(0.0));
+                                }
                             }
                         }
                     }
 
+                    // The encoder always receives 5.1; a 7.1 sink is folded down here.
+                    match input_layout {
+                        InputLayout::Surround51 => {
+                            std::mem::swap(&mut capture_scratch, &mut interleaved_scratch);
+                        }
+                        InputLayout::Surround71 => {
+                            downmix_71_into(&capture_scratch, &downmix, &mut interleaved_scratch);
+                        }
+                    }
+
                     if interleaved_scratch.is_empty() {
                         return;
                     }
@@ -773,10 +3042,14 @@ pub fn run_pipewire_loop_with_config(
     // Connect Capture Stream
     // Connect Capture Stream
     let capture_format_bytes =
-        build_audio_raw_format_param(AudioFormat::F32LE, INPUT_CHANNELS as u32)?;
-    let capture_format_pod = pw::spa::pod::Pod::from_bytes(&capture_format_bytes)
-        .ok_or_else(|| anyhow!("Failed to parse capture format pod bytes"))?;
-    let mut capture_params = [capture_format_pod];
+        build_capture_format_params(config.capture_format, input_layout.channels() as u32)?;
+    let mut capture_params = capture_format_bytes
+        .iter()
+        .map(|bytes| {
+            pw::spa::pod::Pod::from_bytes(bytes)
+                .ok_or_else(|| anyhow!("Failed to parse capture format pod bytes"))
+        })
+        .collect::<Result<Vec<_>>>()?;
     capture_stream.connect(
         Direction::Input,
         None,
@@ -786,6 +3059,12 @@ pub fn run_pipewire_loop_with_config(
 
     info!("PipeWire capture stream connected.");
 
+    let _voice_stream_handle = shared
+        .voice
+        .clone()
+        .map(|producer| connect_voice_capture_stream(&core, node_latency, producer))
+        .transpose()?;
+
     // ------------------------------------------------------------------
     // 2. Create Playback Stream (Output to HDMI)
     // ------------------------------------------------------------------
@@ -794,74 +3073,110 @@ pub fn run_pipewire_loop_with_config(
     // ------------------------------------------------------------------
 
     // We need to keep the stream alive if created
-    let _playback_stream_handle: Option<pw::stream::Stream>;
+    let _playback_stream_handle: Option<Rc<pw::stream::Stream>>;
     let _playback_listener_handle;
+    // Kept for the whole loop so the graph mirror stays current.
+    let registry_watch = Rc::new(watch_registry(&core)?);
+    let target_node = match target_node {
+        Some(target) if graph::is_auto_target(&target) => {
+            if matches!(output_mode, OutputMode::Pipewire) {
+                Some(select_auto_target(&mainloop, &core, &registry_watch)?)
+            } else {
+                None
+            }
+        }
+        target => target,
+    };
     let playback_target = resolve_playback_target(target_node.as_deref());
+    let mut port_linker = None;
+    let mut playback_hold = None;
+    // Before the output opens: direct ALSA needs PipeWire to release the card.
+    let card_profile = if config.manage_card_profile {
+        CardProfileGuard::apply(
+            &mainloop,
+            &core,
+            registry_watch.clone(),
+            &output_mode,
+            // The followed default sink may sit on any card.
+            target_node
+                .as_deref()
+                .filter(|target| !graph::is_default_target(target)),
+        )?
+    } else {
+        None
+    };
+    let playback_in_graph = matches!(output_mode, OutputMode::Pipewire);
 
     match output_mode {
+        // Output threads are started by the first session and outlive reconnects.
         OutputMode::Stdout => {
-            // Shrink the process stdout pipe buffer to minimize end-to-end buffering.
-            #[cfg(target_os = "linux")]
-            {
-                use std::os::unix::io::AsRawFd;
-                let stdout_fd = std::io::stdout().as_raw_fd();
-                const F_SETPIPE_SZ: libc::c_int = 1031;
-                const F_GETPIPE_SZ: libc::c_int = 1032;
-
-                // SAFETY: `stdout_fd` is owned by this process and valid for `fcntl`.
-                let old = unsafe { libc::fcntl(stdout_fd, F_GETPIPE_SZ) };
-                // SAFETY: same as above; we only request a smaller kernel pipe size.
-                let ret = unsafe { libc::fcntl(stdout_fd, F_SETPIPE_SZ, 4096 as libc::c_int) };
-                if ret > 0 {
-                    info!("Shrunk process stdout pipe from {} to {} bytes", old, ret);
-                } else {
-                    log::warn!(
-                        "Could not shrink process stdout pipe: {}",
-                        std::io::Error::last_os_error()
-                    );
+            if let Some(mut output_consumer) = shared.output.take() {
+                // Shrink the process stdout pipe buffer to minimize end-to-end buffering.
+                #[cfg(target_os = "linux")]
+                {
+                    use std::os::unix::io::AsRawFd;
+                    let stdout_fd = std::io::stdout().as_raw_fd();
+                    const F_SETPIPE_SZ: libc::c_int = 1031;
+                    const F_GETPIPE_SZ: libc::c_int = 1032;
+
+                    // SAFETY: `stdout_fd` is owned by this process and valid for `fcntl`.
+                    let old = unsafe { libc::fcntl(stdout_fd, F_GETPIPE_SZ) };
+                    // SAFETY: same as above; we only request a smaller kernel pipe size.
+                    let ret = unsafe { libc::fcntl(stdout_fd, F_SETPIPE_SZ, 4096 as libc::c_int) };
+                    if ret > 0 {
+                        info!("Shrunk process stdout pipe from {} to {} bytes", old, ret);
+                    } else {
+                        log::warn!(
+                            "Could not shrink process stdout pipe: {}",
+                            std::io::Error::last_os_error()
+                        );
+                    }
                 }
-            }
 
-            // Spawn a thread to read from ring buffer and write to stdout.
-            let running_clone = running.clone();
-            thread::spawn(move || {
-                let mut stdout = std::io::stdout().lock();
-                if let Err(e) = run_stdout_output_loop(
-                    &mut output_consumer,
-                    running_clone.as_ref(),
-                    &mut stdout,
-                ) {
-                    log::error!("Failed to write to stdout: {}", e);
-                    std::process::exit(1);
-                }
-            });
-            info!("Outputting to stdout (playback stream disabled).");
+                // Spawn a thread to read from ring buffer and write to stdout.
+                let running_clone = running.clone();
+                thread::spawn(move || {
+                    let mut stdout = std::io::stdout().lock();
+                    if let Err(e) = run_stdout_output_loop(
+                        &mut output_consumer,
+                        running_clone.as_ref(),
+                        &mut stdout,
+                    ) {
+                        log::error!("Failed to write to stdout: {}", e);
+                        std::process::exit(1);
+                    }
+                });
+                info!("Outputting to stdout (playback stream disabled).");
+            }
             _playback_stream_handle = None;
             _playback_listener_handle = None;
         }
         OutputMode::AlsaDirect { device, latency_us } => {
-            let alsa_latency_us = if latency_us == 0 {
-                DEFAULT_ALSA_LATENCY_US
-            } else {
-                latency_us
-            };
-            let device_for_thread = device.clone();
-            let running_clone = running.clone();
-            thread::spawn(move || {
-                if let Err(e) = run_alsa_output_loop(
-                    &mut output_consumer,
-                    running_clone.as_ref(),
-                    &device_for_thread,
-                    alsa_latency_us,
-                ) {
-                    log::error!("Direct ALSA output loop failed: {e:#}");
-                    std::process::exit(1);
-                }
-            });
-            info!(
-                "Outputting directly to ALSA device '{}' (latency={}us, playback stream disabled).",
-                device, alsa_latency_us
-            );
+            if let Some(mut output_consumer) = shared.output.take() {
+                let alsa_latency_us = if latency_us == 0 {
+                    DEFAULT_ALSA_LATENCY_US
+                } else {
+                    latency_us
+                };
+                let device_for_thread = device.clone();
+                let running_clone = running.clone();
+                shared.alsa_thread = Some(thread::spawn(move || {
+                    if let Err(e) = run_alsa_output_loop(
+                        &mut output_consumer,
+                        running_clone.as_ref(),
+                        &device_for_thread,
+                        sample_rate_hz,
+                        alsa_latency_us,
+                    ) {
+                        log::error!("Direct ALSA output loop failed: {e:#}");
+                        std::process::exit(1);
+                    }
+                }));
+                info!(
+                    "Outputting directly to ALSA device '{}' (latency={}us, playback stream disabled).",
+                    device, alsa_latency_us
+                );
+            }
             _playback_stream_handle = None;
             _playback_listener_handle = None;
         }
@@ -869,21 +3184,26 @@ pub fn run_pipewire_loop_with_config(
             // Create Playback Stream (Output to HDMI/Sink)
 
             // Strategy: Use properties for Audio/Source
-            let mut playback_props = build_playback_properties(&playback_target);
+            let mut playback_props = build_playback_properties(&playback_target, sample_rate_hz);
             playback_props.insert("node.latency", node_latency);
             if let Some(frames) = requested_latency_frames {
                 let force_quantum = frames.to_string();
+                let force_rate = sample_rate_hz.to_string();
                 playback_props.insert("node.force-quantum", force_quantum.as_str());
                 playback_props.insert("node.lock-quantum", "true");
-                playback_props.insert("node.force-rate", SAMPLE_RATE);
+                playback_props.insert("node.force-rate", force_rate.as_str());
                 playback_props.insert("node.lock-rate", "true");
                 info!(
                     "Playback stream requesting forced quantum/rate: {} frames @ {} Hz",
-                    frames, SAMPLE_RATE_HZ
+                    frames, sample_rate_hz
                 );
             }
 
-            let output_ring_capacity_bytes = output_consumer.buffer().capacity();
+            let output_data = shared.playback_output();
+            let output_ring_capacity_bytes = output_data
+                .lock()
+                .map(|consumer| consumer.buffer().capacity())
+                .unwrap_or(0);
             let playback_target_quantum_bytes = requested_latency_frames
                 .map(|frames| frames.saturating_mul(OUTPUT_FRAME_BYTES))
                 .filter(|bytes| *bytes > 0)
@@ -898,35 +3218,70 @@ pub fn run_pipewire_loop_with_config(
                 );
             }
 
-            let output_data = Arc::new(Mutex::new(output_consumer));
             let playback_primed = Arc::new(AtomicBool::new(false));
             let playback_prefill_logged = Arc::new(AtomicBool::new(false));
             let playback_callback_quantum_logged = Arc::new(AtomicBool::new(false));
+            let negotiated_output_rate_hz = config.output_rate_hz.clone();
+            let playback_holding = Arc::new(AtomicBool::new(false));
+            let playback_failed = Rc::new(Cell::new(false));
 
             // Create stream
-            let playback_stream =
-                pw::stream::Stream::new(&core, "ac3-encoder-playback", playback_props)?;
-
+            let playback_stream = Rc::new(pw::stream::Stream::new(
+                &core,
+                "ac3-encoder-playback",
+                playback_props,
+            )?);
+
+            let state_failed = playback_failed.clone();
+            let process_output = output_data.clone();
+            let process_holding = playback_holding.clone();
             let playback_listener = playback_stream
             .add_local_listener::<()>()
-            .state_changed(|_stream, _data, old, new| {
+            .state_changed(move |_stream, _data, old, new| {
                 info!("Playback Stream state changed: {:?} -> {:?}", old, new);
+                state_failed.set(matches!(
+                    new,
+                    pw::stream::StreamState::Error(_) | pw::stream::StreamState::Unconnected
+                ));
             })
-            .param_changed(|_stream, _data, id, param| {
+            .param_changed(move |_stream, _data, id, param| {
                 if id != pw::spa::param::ParamType::Format.as_raw() {
                     return;
                 }
                 let Some(param) = param else {
                     return;
                 };
+                if let Some((codec, rate)) = parse_iec958_format(param) {
+                    info!(
+                        "Playback format negotiated: IEC958 {} passthrough, rate={}",
+                        codec, rate
+                    );
+                    if rate > 0 {
+                        negotiated_output_rate_hz.store(rate, Ordering::Relaxed);
+                    }
+                    return;
+                }
                 let mut info = AudioInfoRaw::new();
                 if info.parse(param).is_ok() {
                     info!(
-                        "Playback format negotiated: {:?}, rate={}, channels={}",
+                        "Playback format negotiated: {:?}, rate={}, channels={} (target did not accept IEC958 AC3; relying on bit-transparent PCM)",
                         info.format(),
                         info.rate(),
                         info.channels()
                     );
+                    // Only 2ch is offered; anything else cannot carry IEC 61937.
+                    if info.channels() != OUTPUT_CHANNELS as u32 {
+                        warn!(
+                            "Playback stream negotiated {} channels instead of {}; the output will not decode",
+                            info.channels(),
+                            OUTPUT_CHANNELS
+                        );
+                    }
+                    // IEC61937 bytes must leave at the rate they were encoded for;
+                    // the encoder restarts at a renegotiated rate.
+                    if info.rate() > 0 {
+                        negotiated_output_rate_hz.store(info.rate(), Ordering::Relaxed);
+                    }
                 }
             })
             .process(
@@ -978,7 +3333,14 @@ pub fn run_pipewire_loop_with_config(
                             // loopback quantums (e.g. 64 KiB+) makes the ring sit near-full,
                             // which amplifies backpressure and capture drops.
                             let prefill_target = target_write.min(prefill_limit);
-                            if let Ok(mut consumer) = output_data.try_lock() {
+                            if process_holding.load(Ordering::Relaxed) {
+                                // Silence while the target is away; queued output
+                                // would only come back as latency.
+                                playback_primed.store(false, Ordering::Relaxed);
+                                if let Ok(mut consumer) = process_output.try_lock() {
+                                    discard_output(&mut consumer);
+                                }
+                            } else if let Ok(mut consumer) = process_output.try_lock() {
                                 let available = consumer.slots();
 
 
@@ -1023,20 +3385,43 @@ pub fn run_pipewire_loop_with_config(
             )
             .register()?;
 
-            let playback_format_bytes =
-                build_audio_raw_format_param(AudioFormat::S16LE, OUTPUT_CHANNELS as u32)?;
-            let playback_format_pod = pw::spa::pod::Pod::from_bytes(&playback_format_bytes)
-                .ok_or_else(|| anyhow!("Failed to parse playback format pod bytes"))?;
-            let mut playback_params = [playback_format_pod];
+            let playback_format_bytes = build_playback_format_param(sample_rate_hz)?;
+            // Preferred first: real AC-3 passthrough, then raw S16LE.
+            let formats = if config.iec958_format {
+                vec![
+                    build_iec958_format_param(sample_rate_hz)?,
+                    playback_format_bytes,
+                ]
+            } else {
+                vec![playback_format_bytes]
+            };
+            let hold = PlaybackHold {
+                stream: playback_stream.clone(),
+                formats,
+                flags: StreamFlags::MAP_BUFFERS
+                    | StreamFlags::RT_PROCESS
+                    | StreamFlags::AUTOCONNECT,
+                holding: playback_holding,
+                output: output_data,
+                stream_failed: playback_failed,
+                last_connect: Cell::new(Instant::now()),
+            };
             // Connect Playback Stream
-            playback_stream.connect(
-                Direction::Output,
-                playback_target.connect_target_id,
-                StreamFlags::MAP_BUFFERS | StreamFlags::RT_PROCESS | StreamFlags::AUTOCONNECT,
-                &mut playback_params,
-            )?;
+            hold.connect(playback_target.connect_target_id)?;
+            playback_hold = Some(hold);
 
             info!("PipeWire playback stream connected (Server Node).");
+            if let Some(target) = playback_target.target_object.clone() {
+                port_linker = Some(Rc::new(PortLinker::new(&core, &registry_watch, target)));
+            } else if playback_target.follow_default {
+                let linker = PortLinker::following_default(&core, &registry_watch);
+                // Pick up the current default before a takeover replaces it
+                // with our own input: globals, then the metadata properties.
+                roundtrip(&mainloop, &core)?;
+                roundtrip(&mainloop, &core)?;
+                linker.retarget_to_default();
+                port_linker = Some(Rc::new(linker));
+            }
             _playback_stream_handle = Some(playback_stream);
             _playback_listener_handle = Some(playback_listener);
         }
@@ -1046,10 +3431,43 @@ pub fn run_pipewire_loop_with_config(
 
     // Timer to check running
     let loop_ = mainloop.loop_();
+    let default_sink_takeover = match config.default_sink_takeover.clone() {
+        Some(state_path) => Some(Rc::new(DefaultSinkTakeover::start(
+            &mainloop,
+            &core,
+            registry_watch.clone(),
+            state_path,
+        )?)),
+        None => None,
+    };
+
+    let volume_guard = (playback_in_graph && config.volume_guard != VolumeGuardMode::Off)
+        .then(|| VolumeGuard::new(registry_watch.clone(), config.volume_guard));
+    let loop_guard = playback_in_graph.then(|| LoopGuard::new(registry_watch.clone()));
+
     let mainloop_clone = mainloop.clone();
+    let timer_running = running.clone();
+    let timer_linker = port_linker.clone();
+    let timer_takeover = default_sink_takeover.clone();
     let _timer = loop_.add_timer(move |_| {
-        if !running.load(Ordering::Relaxed) {
+        if !timer_running.load(Ordering::Relaxed) {
             mainloop_clone.quit();
+            return;
+        }
+        if let Some(linker) = &timer_linker {
+            linker.reconcile();
+        }
+        if let Some(takeover) = &timer_takeover {
+            takeover.reconcile();
+        }
+        if let Some(guard) = &loop_guard {
+            guard.reconcile();
+        }
+        if let Some(guard) = &volume_guard {
+            guard.reconcile();
+        }
+        if let Some(hold) = &playback_hold {
+            hold.reconcile(timer_linker.as_ref().is_none_or(|l| l.target_present()));
         }
     });
 
@@ -1063,5 +3481,26 @@ pub fn run_pipewire_loop_with_config(
 
     mainloop.run();
 
-    Ok(())
+    if disconnected.get() && running.load(Ordering::Relaxed) {
+        // Nothing can be restored on a dead connection; the next session
+        // picks up the takeover state file and re-applies the card profile.
+        warn!("Lost the connection to PipeWire; the encoder keeps running");
+        return Ok(SessionEnd::Disconnected);
+    }
+
+    if let Some(linker) = &port_linker {
+        linker.unlink(&mainloop);
+    }
+    if let Some(takeover) = &default_sink_takeover {
+        takeover.restore(&mainloop, &core);
+    }
+    if let Some(guard) = &card_profile {
+        // The card must not be handed back while our PCM is still open.
+        if let Some(handle) = shared.alsa_thread.take() {
+            let _ = handle.join();
+        }
+        guard.restore(&mainloop, &core);
+    }
+
+    Ok(SessionEnd::Stopped)
 }
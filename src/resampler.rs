@@ -0,0 +1,204 @@
+use std::f64::consts::PI;
+
+/// Zero crossings of the sinc kernel on each side (at the lower of the two rates).
+const ZERO_CROSSINGS: f64 = 24.0;
+/// Passband edge as a fraction of the lower Nyquist frequency.
+const ROLLOFF: f64 = 0.94;
+/// Kaiser window shape; ~85 dB stopband attenuation.
+const KAISER_BETA: f64 = 8.6;
+/// Upper bound on the polyphase table. Rate pairs needing more phases (no
+/// useful common divisor) use the nearest lower phase.
+const MAX_PHASES: usize = 1024;
+
+/// Streaming polyphase windowed-sinc resampler for interleaved frames.
+///
+/// The rate ratio is reduced to a fraction, so common pairs (44.1/48/96 kHz) are
+/// resampled with an exact, drift-free phase. Equal rates are a plain copy.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    channels: usize,
+    input_rate: u32,
+    output_rate: u32,
+    /// Input frames advanced per output frame: `step_int + step_frac / denominator`.
+    step_int: usize,
+    step_frac: usize,
+    denominator: usize,
+    half_taps: usize,
+    phases: usize,
+    /// `phases` rows of `2 * half_taps` coefficients.
+    table: Vec<f32>,
+    /// Interleaved input frames not yet fully consumed.
+    history: Vec<f32>,
+    pos_int: usize,
+    pos_frac: usize,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32, channels: usize) -> Self {
+        let input_rate = input_rate.max(1);
+        let output_rate = output_rate.max(1);
+        let channels = channels.max(1);
+        let divisor = gcd(input_rate, output_rate);
+        let numerator = (input_rate / divisor) as usize;
+        let denominator = (output_rate / divisor) as usize;
+
+        let mut resampler = Self {
+            channels,
+            input_rate,
+            output_rate,
+            step_int: numerator / denominator,
+            step_frac: numerator % denominator,
+            denominator,
+            half_taps: 0,
+            phases: 0,
+            table: Vec::new(),
+            history: Vec::new(),
+            pos_int: 0,
+            pos_frac: 0,
+        };
+        if input_rate != output_rate {
+            resampler.design_filter();
+        }
+        resampler
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        self.input_rate == self.output_rate
+    }
+
+    /// Look-ahead the filter needs before an output frame can be produced, in
+    /// output frames. Output instants themselves are aligned with the input.
+    pub fn latency_frames(&self) -> usize {
+        if self.is_passthrough() {
+            0
+        } else {
+            (self.half_taps as u64 * u64::from(self.output_rate) / u64::from(self.input_rate))
+                as usize
+        }
+    }
+
+    fn design_filter(&mut self) {
+        // Cut off below the lower Nyquist frequency, measured in input samples.
+        let scale = (f64::from(self.output_rate) / f64::from(self.input_rate)).min(1.0) * ROLLOFF;
+        let half_width = ZERO_CROSSINGS / scale;
+        let half_taps = half_width.ceil() as usize;
+        let taps = 2 * half_taps;
+        let phases = self.denominator.min(MAX_PHASES);
+        let window_norm = bessel_i0(KAISER_BETA);
+
+        let mut table = Vec::with_capacity(phases * taps);
+        for phase in 0..phases {
+            let fraction = phase as f64 / phases as f64;
+            let row_start = table.len();
+            for tap in 0..taps {
+                // Distance from the output instant to input sample `tap` of the window.
+                let tau = fraction + (half_taps - 1) as f64 - tap as f64;
+                let x = tau / half_width;
+                let coefficient = if x.abs() >= 1.0 {
+                    0.0
+                } else {
+                    let window = bessel_i0(KAISER_BETA * (1.0 - x * x).sqrt()) / window_norm;
+                    scale * sinc(scale * tau) * window
+                };
+                table.push(coefficient);
+            }
+            // Normalize every phase to unity DC gain so no phase adds ripple.
+            let sum: f64 = table[row_start..].iter().sum();
+            for coefficient in &mut table[row_start..] {
+                *coefficient /= sum;
+            }
+        }
+
+        self.half_taps = half_taps;
+        self.phases = phases;
+        self.table = table.into_iter().map(|c| c as f32).collect();
+        // Prime the history so the first input frame is the first output instant.
+        self.history = vec![0.0; (half_taps - 1) * self.channels];
+        self.pos_int = half_taps - 1;
+        self.pos_frac = 0;
+    }
+
+    /// Resamples interleaved `input` frames, appending the result to `output`.
+    /// Output frames become available as soon as enough input has been seen.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let usable = input.len() - input.len() % self.channels;
+        if self.is_passthrough() {
+            output.extend_from_slice(&input[..usable]);
+            return;
+        }
+
+        self.history.extend_from_slice(&input[..usable]);
+        let channels = self.channels;
+        let taps = 2 * self.half_taps;
+        let frames = self.history.len() / channels;
+
+        while self.pos_int + self.half_taps < frames {
+            let phase = self.pos_frac * self.phases / self.denominator;
+            let coefficients = &self.table[phase * taps..(phase + 1) * taps];
+            let base = (self.pos_int + 1 - self.half_taps) * channels;
+            let window = &self.history[base..base + taps * channels];
+
+            for ch in 0..channels {
+                let mut acc = 0.0f32;
+                for (coefficient, frame) in coefficients.iter().zip(window.chunks_exact(channels)) {
+                    acc += coefficient * frame[ch];
+                }
+                output.push(acc);
+            }
+
+            self.pos_int += self.step_int;
+            self.pos_frac += self.step_frac;
+            if self.pos_frac >= self.denominator {
+                self.pos_frac -= self.denominator;
+                self.pos_int += 1;
+            }
+        }
+
+        // Drop input frames that no future output can reach.
+        let consumed = (self.pos_int + 1)
+            .saturating_sub(self.half_taps)
+            .min(frames);
+        if consumed > 0 {
+            self.history.drain(..consumed * channels);
+            self.pos_int -= consumed;
+        }
+    }
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1.0e-12 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind (power series).
+fn bessel_i0(x: f64) -> f64 {
+    let half = x / 2.0;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..50 {
+        term *= (half / k as f64).powi(2);
+        sum += term;
+        if term < sum * 1.0e-16 {
+            break;
+        }
+    }
+    sum
+}
@@ -0,0 +1,104 @@
+use crate::dsp::{db_to_gain, time_coefficient, CH_FC, DSP_CHANNELS};
+
+/// Channel count of the voice-chat sink (FL,FR).
+pub const VOICE_CHANNELS: usize = 2;
+
+/// Voice level above which the main mix is ducked (-50 dBFS).
+const VOICE_THRESHOLD: f32 = 0.003_162_3;
+/// Detector release, long enough to bridge the gaps between words.
+const DETECTOR_RELEASE_MS: f32 = 150.0;
+/// Voice L/R into FL/FR (-6 dB) and the mono sum into FC.
+const FRONT_MIX_GAIN: f32 = 0.5;
+const CENTER_MIX_GAIN: f32 = 0.5;
+
+/// Ducking applied to the main 5.1 input while voice chat is active.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoiceDuckingConfig {
+    /// Attenuation of the main input while someone is talking, in dB.
+    pub duck_db: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+impl Default for VoiceDuckingConfig {
+    fn default() -> Self {
+        Self {
+            duck_db: 12.0,
+            attack_ms: 20.0,
+            release_ms: 600.0,
+        }
+    }
+}
+
+/// Mixes the stereo voice-chat stream into the 6-channel (FL,FR,FC,LFE,SL,SR)
+/// stream and ducks the main input while voice is present.
+#[derive(Debug, Clone)]
+pub struct VoiceMixer {
+    duck_db: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    detector_release_coef: f32,
+    detector: f32,
+    reduction_db: f32,
+}
+
+impl VoiceMixer {
+    pub fn new(sample_rate: u32, config: &VoiceDuckingConfig) -> Self {
+        let sample_rate = sample_rate.max(1) as f32;
+        Self {
+            duck_db: config.duck_db.max(0.0),
+            attack_coef: time_coefficient(config.attack_ms, sample_rate),
+            release_coef: time_coefficient(config.release_ms, sample_rate),
+            detector_release_coef: time_coefficient(DETECTOR_RELEASE_MS, sample_rate),
+            detector: 0.0,
+            reduction_db: 0.0,
+        }
+    }
+
+    /// Current attenuation of the main input, in dB.
+    pub fn reduction_db(&self) -> f32 {
+        self.reduction_db
+    }
+
+    /// Ducks `main` (interleaved 6-channel) and mixes `voice` (interleaved stereo)
+    /// into it, frame by frame. Missing voice frames are treated as silence.
+    pub fn process(&mut self, main: &mut [f32], voice: &[f32]) {
+        let mut voice_frames = voice.chunks_exact(VOICE_CHANNELS);
+        for frame in main.chunks_exact_mut(DSP_CHANNELS) {
+            let (left, right) = match voice_frames.next() {
+                Some(voice_frame) => (voice_frame[0], voice_frame[1]),
+                None => (0.0, 0.0),
+            };
+
+            // Instant attack, slow release: a peak detector that holds across syllables.
+            let peak = left.abs().max(right.abs());
+            self.detector = if peak > self.detector {
+                peak
+            } else {
+                peak + self.detector_release_coef * (self.detector - peak)
+            };
+
+            let target_db = if self.detector > VOICE_THRESHOLD {
+                self.duck_db
+            } else {
+                0.0
+            };
+            let coef = if target_db > self.reduction_db {
+                self.attack_coef
+            } else {
+                self.release_coef
+            };
+            self.reduction_db = target_db + coef * (self.reduction_db - target_db);
+
+            if self.reduction_db > 1.0e-3 {
+                let duck_gain = db_to_gain(-self.reduction_db);
+                for sample in frame.iter_mut() {
+                    *sample *= duck_gain;
+                }
+            }
+            frame[0] += left * FRONT_MIX_GAIN;
+            frame[1] += right * FRONT_MIX_GAIN;
+            frame[CH_FC] += (left + right) * CENTER_MIX_GAIN;
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::str::FromStr;
+
+/// Largest distance from 1.0 still treated as unity. PipeWire stores volumes
+/// as f32, and `pactl set-sink-volume 100%` lands exactly on 1.0.
+const UNITY_TOLERANCE: f32 = 1.0e-6;
+
+/// What to do when the target sink or the playback stream leaves unity gain,
+/// which turns the IEC 61937 stream into noise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VolumeGuardMode {
+    Off,
+    /// Log an error on every new deviation.
+    #[default]
+    Warn,
+    /// Set the volume back to unity, logging a warning for every reset.
+    Force,
+}
+
+impl VolumeGuardMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Warn => "warn",
+            Self::Force => "force",
+        }
+    }
+}
+
+impl FromStr for VolumeGuardMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "force" => Ok(Self::Force),
+            other => Err(format!(
+                "unknown volume guard mode '{other}' (expected off, warn or force)"
+            )),
+        }
+    }
+}
+
+/// Volume related entries of a node's Props param. `None` means the node has
+/// not reported the entry (yet).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeVolume {
+    pub volume: Option<f32>,
+    pub mute: Option<bool>,
+    pub channel_volumes: Option<Vec<f32>>,
+    pub soft_volumes: Option<Vec<f32>>,
+    pub soft_mute: Option<bool>,
+}
+
+impl NodeVolume {
+    /// Takes over the entries `update` reports; a node may split its Props
+    /// over several objects.
+    pub fn merge(&mut self, update: NodeVolume) {
+        if update.volume.is_some() {
+            self.volume = update.volume;
+        }
+        if update.mute.is_some() {
+            self.mute = update.mute;
+        }
+        if update.channel_volumes.is_some() {
+            self.channel_volumes = update.channel_volumes;
+        }
+        if update.soft_volumes.is_some() {
+            self.soft_volumes = update.soft_volumes;
+        }
+        if update.soft_mute.is_some() {
+            self.soft_mute = update.soft_mute;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Human readable list of everything that is not unity gain, e.g.
+    /// `["volume 0.740", "mute"]`; empty when the path is bit-perfect.
+    pub fn deviations(&self) -> Vec<String> {
+        let mut deviations = Vec::new();
+        if let Some(volume) = self.volume.filter(|volume| !is_unity(*volume)) {
+            deviations.push(format!("volume {volume:.3}"));
+        }
+        if self.mute == Some(true) {
+            deviations.push("mute".to_string());
+        }
+        for (name, volumes) in [
+            ("channelVolumes", &self.channel_volumes),
+            ("softVolumes", &self.soft_volumes),
+        ] {
+            if let Some(volumes) = volumes {
+                if !volumes.iter().all(|volume| is_unity(*volume)) {
+                    deviations.push(format!("{name} {}", format_volumes(volumes)));
+                }
+            }
+        }
+        if self.soft_mute == Some(true) {
+            deviations.push("softMute".to_string());
+        }
+        deviations
+    }
+
+    /// The reported entries, all set to unity gain and unmuted.
+    pub fn to_unity(&self) -> NodeVolume {
+        NodeVolume {
+            volume: self.volume.map(|_| 1.0),
+            mute: self.mute.map(|_| false),
+            channel_volumes: self.channel_volumes.as_ref().map(|v| vec![1.0; v.len()]),
+            soft_volumes: self.soft_volumes.as_ref().map(|v| vec![1.0; v.len()]),
+            soft_mute: self.soft_mute.map(|_| false),
+        }
+    }
+}
+
+fn is_unity(volume: f32) -> bool {
+    (volume - 1.0).abs() <= UNITY_TOLERANCE
+}
+
+fn format_volumes(volumes: &[f32]) -> String {
+    let values: Vec<String> = volumes.iter().map(|v| format!("{v:.3}")).collect();
+    format!("[{}]", values.join(", "))
+}
@@ -0,0 +1,112 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::Path;
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Decoded WAV file, one `Vec<f32>` per channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavData {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+pub fn read_wav(path: &Path) -> Result<WavData> {
+    let bytes =
+        fs::read(path).with_context(|| format!("Failed to read WAV file {}", path.display()))?;
+    parse_wav(&bytes).with_context(|| format!("Invalid WAV file {}", path.display()))
+}
+
+/// Parses a RIFF/WAVE file holding 16/24/32-bit integer PCM or 32/64-bit float samples.
+pub fn parse_wav(bytes: &[u8]) -> Result<WavData> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(anyhow!("missing RIFF/WAVE header"));
+    }
+
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    let mut data: Option<&[u8]> = None;
+    let mut pos = 12usize;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = u32::from_le_bytes([
+            bytes[pos + 4],
+            bytes[pos + 5],
+            bytes[pos + 6],
+            bytes[pos + 7],
+        ]) as usize;
+        let body_start = pos + 8;
+        let body_end = body_start
+            .checked_add(size)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| anyhow!("truncated '{}' chunk", String::from_utf8_lossy(id)))?;
+        let body = &bytes[body_start..body_end];
+
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err(anyhow!("fmt chunk too short"));
+                }
+                let mut tag = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if tag == WAVE_FORMAT_EXTENSIBLE {
+                    // The sub-format GUID starts with the actual format tag.
+                    if body.len() < 26 {
+                        return Err(anyhow!("extensible fmt chunk too short"));
+                    }
+                    tag = u16::from_le_bytes([body[24], body[25]]);
+                }
+                format = Some((tag, channels, sample_rate, bits));
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+
+        // Chunks are word aligned.
+        pos = body_end + (size & 1);
+    }
+
+    let (tag, channel_count, sample_rate, bits) =
+        format.ok_or_else(|| anyhow!("missing fmt chunk"))?;
+    let data = data.ok_or_else(|| anyhow!("missing data chunk"))?;
+    if channel_count == 0 {
+        return Err(anyhow!("WAV declares zero channels"));
+    }
+
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (WAVE_FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32_768.0,
+        (WAVE_FORMAT_PCM, 24) => {
+            |b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0
+        }
+        (WAVE_FORMAT_PCM, 32) => {
+            |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+        }
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        (WAVE_FORMAT_IEEE_FLOAT, 64) => {
+            |b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+        }
+        _ => {
+            return Err(anyhow!(
+                "unsupported WAV encoding (format tag {tag:#06x}, {bits} bits)"
+            ))
+        }
+    };
+
+    let sample_bytes = usize::from(bits / 8);
+    let frame_bytes = sample_bytes * usize::from(channel_count);
+    let frames = data.len() / frame_bytes;
+    let mut channels = vec![Vec::with_capacity(frames); usize::from(channel_count)];
+    for frame in data.chunks_exact(frame_bytes) {
+        for (channel, sample) in channels.iter_mut().zip(frame.chunks_exact(sample_bytes)) {
+            channel.push(decode(sample));
+        }
+    }
+
+    Ok(WavData {
+        sample_rate,
+        channels,
+    })
+}
@@ -0,0 +1,136 @@
+use pw_ac3_live::acmod::{
+    downmix_to_stereo, AutoLayout, AutoLayoutConfig, ChannelLayoutMode, EncodeLayout,
+    ALL_CHANNELS_MASK, FRONT_PAIR_MASK,
+};
+
+/// 10 ms of interleaved 5.1 at 48 kHz with `levels` on every frame.
+fn chunk(levels: [f32; 6]) -> Vec<f32> {
+    levels.repeat(480)
+}
+
+#[test]
+fn channel_layout_mode_parses_cli_values() {
+    assert_eq!("5.1".parse(), Ok(ChannelLayoutMode::Surround51));
+    assert_eq!("2.0".parse(), Ok(ChannelLayoutMode::Stereo));
+    assert_eq!(" Auto ".parse(), Ok(ChannelLayoutMode::Auto));
+    assert!("7.1".parse::<ChannelLayoutMode>().is_err());
+
+    assert_eq!(
+        ChannelLayoutMode::Stereo.initial_layout(),
+        EncodeLayout::Stereo
+    );
+    assert_eq!(
+        ChannelLayoutMode::Auto.initial_layout(),
+        EncodeLayout::Surround51
+    );
+    assert_eq!(EncodeLayout::Stereo.channels(), 2);
+    assert_eq!(EncodeLayout::Surround51.channels(), 6);
+}
+
+#[test]
+fn auto_layout_switches_to_stereo_after_hold_time() {
+    let mut auto = AutoLayout::new(AutoLayoutConfig {
+        use_activity: false,
+        hold_ms: 50,
+    });
+    let stereo = chunk([0.5, -0.5, 0.0, 0.0, 0.0, 0.0]);
+
+    // 40 ms of a stereo-only source is not enough yet.
+    for _ in 0..4 {
+        assert_eq!(
+            auto.update(&stereo, 48_000, FRONT_PAIR_MASK),
+            EncodeLayout::Surround51
+        );
+    }
+    assert_eq!(
+        auto.update(&stereo, 48_000, FRONT_PAIR_MASK),
+        EncodeLayout::Stereo
+    );
+
+    // A 5.1 source switches back at once.
+    assert_eq!(
+        auto.update(&stereo, 48_000, ALL_CHANNELS_MASK),
+        EncodeLayout::Surround51
+    );
+    assert_eq!(auto.layout(), EncodeLayout::Surround51);
+}
+
+#[test]
+fn auto_layout_activity_ignores_short_surround_silences() {
+    let mut auto = AutoLayout::new(AutoLayoutConfig {
+        use_activity: true,
+        hold_ms: 100,
+    });
+    let surround = chunk([0.5, 0.5, 0.2, 0.1, 0.3, 0.3]);
+    let silent_surround = chunk([0.5, 0.5, 0.0, 0.0, 0.0005, 0.0]);
+
+    assert_eq!(
+        auto.update(&surround, 48_000, ALL_CHANNELS_MASK),
+        EncodeLayout::Surround51
+    );
+    // A 50 ms pause in the surrounds, then content again: no switch.
+    for _ in 0..5 {
+        auto.update(&silent_surround, 48_000, ALL_CHANNELS_MASK);
+    }
+    assert_eq!(
+        auto.update(&surround, 48_000, ALL_CHANNELS_MASK),
+        EncodeLayout::Surround51
+    );
+
+    // Only a sustained silence (below -60 dBFS) selects 2.0.
+    for _ in 0..9 {
+        assert_eq!(
+            auto.update(&silent_surround, 48_000, ALL_CHANNELS_MASK),
+            EncodeLayout::Surround51
+        );
+    }
+    assert_eq!(
+        auto.update(&silent_surround, 48_000, ALL_CHANNELS_MASK),
+        EncodeLayout::Stereo
+    );
+    assert_eq!(
+        auto.update(&surround, 48_000, ALL_CHANNELS_MASK),
+        EncodeLayout::Surround51
+    );
+}
+
+#[test]
+fn auto_layout_default_switches_stereo_content_on_the_51_sink() {
+    // The capture stream always feeds all six channels of the 5.1 sink, so the
+    // default config must go by activity.
+    let mut auto = AutoLayout::new(AutoLayoutConfig::default());
+    let stereo = chunk([0.5, -0.5, 0.0, 0.0, 0.0, 0.0]);
+    for _ in 0..399 {
+        assert_eq!(
+            auto.update(&stereo, 48_000, ALL_CHANNELS_MASK),
+            EncodeLayout::Surround51
+        );
+    }
+    assert_eq!(
+        auto.update(&stereo, 48_000, ALL_CHANNELS_MASK),
+        EncodeLayout::Stereo
+    );
+}
+
+#[test]
+fn auto_layout_without_activity_keeps_connected_silent_channels() {
+    let mut auto = AutoLayout::new(AutoLayoutConfig {
+        use_activity: false,
+        hold_ms: 0,
+    });
+    let stereo = chunk([0.5, -0.5, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(
+        auto.update(&stereo, 48_000, ALL_CHANNELS_MASK),
+        EncodeLayout::Surround51
+    );
+}
+
+#[test]
+fn stereo_downmix_folds_center_and_surrounds() {
+    let half = std::f32::consts::FRAC_1_SQRT_2;
+    let mut out = vec![9.0];
+    downmix_to_stereo(&[0.1, 0.2, 0.4, 1.0, 0.3, -0.3], &mut out);
+    assert_eq!(out.len(), 2);
+    assert!((out[0] - (0.1 + 0.4 * half + 0.3 * half)).abs() < 1e-6);
+    assert!((out[1] - (0.2 + 0.4 * half - 0.3 * half)).abs() < 1e-6);
+}
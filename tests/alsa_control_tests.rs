@@ -4,12 +4,16 @@ mod alsa_control_impl {
     include!("../src/alsa_control.rs");
 
     mod moved_tests {
-        use super::DirectAlsaHardwareGuard;
+        use super::{DirectAlsaHardwareGuard, Iec958StatusSwitch};
+        use std::sync::Arc;
+        use std::thread;
+        use std::time::{Duration, Instant};
 
         fn guard(card: &str, index: &str) -> DirectAlsaHardwareGuard {
             DirectAlsaHardwareGuard {
                 iec_card: card.to_string(),
                 iec_index: index.to_string(),
+                sample_rate_hz: 48_000,
             }
         }
 
@@ -62,6 +66,14 @@ mod alsa_control_impl {
             assert_eq!(commands[3].args[3], "IEC958,2");
         }
 
+        #[test]
+        fn startup_iec958_rate_follows_encoder_rate() {
+            let mut guard = guard("0", "2");
+            guard.sample_rate_hz = 44_100;
+            let commands = guard.startup_commands();
+            assert_eq!(commands[0].args[7], "44100");
+        }
+
         #[test]
         fn shutdown_commands_restore_pcm_audio_mode() {
             let guard = guard("4", "8");
@@ -77,5 +89,47 @@ mod alsa_control_impl {
                     .collect::<Vec<_>>()
             );
         }
+
+        #[test]
+        fn iec958_status_command_toggles_audio_flag_at_current_rate() {
+            let guard = guard("0", "2");
+            assert_eq!(
+                guard.iec958_status_command(true).args,
+                vec!["-c", "0", "-n", "2", "audio", "on", "rate", "48000"]
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(
+                guard.iec958_status_command(false),
+                guard.startup_commands()[0]
+            );
+        }
+
+        #[test]
+        fn iec958_status_switch_waits_for_the_follower() {
+            let switch = Arc::new(Iec958StatusSwitch::new());
+            let follower_switch = switch.clone();
+            let follower = thread::spawn(move || {
+                let mut applied = Vec::new();
+                while let Some(pcm) = follower_switch.next_request() {
+                    follower_switch.set_applied(pcm);
+                    applied.push(pcm);
+                }
+                applied
+            });
+
+            assert!(switch.request(true, Duration::from_secs(5)));
+            assert!(switch.request(false, Duration::from_secs(5)));
+            // Already applied: no round trip through the follower.
+            assert!(switch.request(false, Duration::from_secs(5)));
+            switch.close();
+            assert_eq!(follower.join().unwrap(), vec![true, false]);
+
+            // Once closed, requests return at once without the flag.
+            let start = Instant::now();
+            assert!(!switch.request(true, Duration::from_secs(5)));
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
     }
 }
@@ -0,0 +1,114 @@
+use pw_ac3_live::compressor::{NightModeCompressor, NightModePreset};
+use pw_ac3_live::dsp::DSP_CHANNELS;
+
+const SAMPLE_RATE: u32 = 48_000;
+
+/// Builds `frames` interleaved 5.1 frames with a constant value per channel.
+fn constant_frames(levels: [f32; DSP_CHANNELS], frames: usize) -> Vec<f32> {
+    levels
+        .iter()
+        .copied()
+        .cycle()
+        .take(frames * DSP_CHANNELS)
+        .collect()
+}
+
+fn last_frame(samples: &[f32]) -> &[f32] {
+    &samples[samples.len() - DSP_CHANNELS..]
+}
+
+#[test]
+fn night_mode_preset_parses_known_names() {
+    assert_eq!("off".parse::<NightModePreset>(), Ok(NightModePreset::Off));
+    assert_eq!(
+        " Light ".parse::<NightModePreset>(),
+        Ok(NightModePreset::Light)
+    );
+    assert_eq!(
+        "NIGHT".parse::<NightModePreset>(),
+        Ok(NightModePreset::Night)
+    );
+    assert!("loud".parse::<NightModePreset>().is_err());
+}
+
+#[test]
+fn off_preset_is_bit_exact_bypass() {
+    let mut compressor = NightModeCompressor::new(SAMPLE_RATE, NightModePreset::Off);
+    let input = constant_frames([0.9, -0.9, 0.5, 0.7, 0.3, -0.3], 480);
+    let mut output = input.clone();
+    compressor.process(&mut output);
+    assert_eq!(output, input);
+}
+
+#[test]
+fn night_preset_reduces_loud_effects_more_than_light() {
+    let loud = constant_frames([0.9, 0.9, 0.0, 0.0, 0.9, 0.9], SAMPLE_RATE as usize / 2);
+
+    let mut light = loud.clone();
+    NightModeCompressor::new(SAMPLE_RATE, NightModePreset::Light).process(&mut light);
+    let mut night = loud.clone();
+    NightModeCompressor::new(SAMPLE_RATE, NightModePreset::Night).process(&mut night);
+
+    let light_level = last_frame(&light)[0];
+    let night_level = last_frame(&night)[0];
+    assert!(
+        light_level < 0.9,
+        "light preset should compress: {light_level}"
+    );
+    assert!(
+        night_level < light_level,
+        "night ({night_level}) should be quieter than light ({light_level})"
+    );
+}
+
+#[test]
+fn quiet_dialogue_is_raised_by_makeup_gain() {
+    // -40 dBFS dialogue in FC only: below both thresholds, so only makeup applies.
+    let mut samples = constant_frames([0.0, 0.0, 0.01, 0.0, 0.0, 0.0], 4800);
+    NightModeCompressor::new(SAMPLE_RATE, NightModePreset::Night).process(&mut samples);
+    assert!(last_frame(&samples)[2] > 0.02);
+}
+
+#[test]
+fn center_keeps_more_level_than_fronts_under_compression() {
+    let mut samples = constant_frames([0.8, 0.8, 0.8, 0.0, 0.0, 0.0], SAMPLE_RATE as usize / 2);
+    NightModeCompressor::new(SAMPLE_RATE, NightModePreset::Night).process(&mut samples);
+    let frame = last_frame(&samples);
+    assert!(
+        frame[2] > frame[0],
+        "FC {} should exceed FL {}",
+        frame[2],
+        frame[0]
+    );
+}
+
+#[test]
+fn lfe_is_not_pumped_by_loud_mains() {
+    let mut samples = constant_frames([0.9, 0.9, 0.9, 0.01, 0.9, 0.9], SAMPLE_RATE as usize / 2);
+    NightModeCompressor::new(SAMPLE_RATE, NightModePreset::Night).process(&mut samples);
+    // Quiet LFE gets makeup gain from its own detector instead of the mains' reduction.
+    assert!(last_frame(&samples)[3] > 0.01);
+}
+
+#[test]
+fn output_never_exceeds_limiter_ceiling() {
+    let mut samples = constant_frames([1.0, -1.0, 1.0, 1.0, -1.0, 1.0], 9600);
+    NightModeCompressor::new(SAMPLE_RATE, NightModePreset::Night).process(&mut samples);
+    for sample in samples {
+        assert!(sample.abs() <= 0.8913, "sample {sample} exceeds -1 dBFS");
+    }
+}
+
+#[test]
+fn switching_to_off_restores_bypass() {
+    let mut compressor = NightModeCompressor::new(SAMPLE_RATE, NightModePreset::Night);
+    let mut warmup = constant_frames([0.9; DSP_CHANNELS], 4800);
+    compressor.process(&mut warmup);
+
+    compressor.set_preset(NightModePreset::Off);
+    assert_eq!(compressor.preset(), NightModePreset::Off);
+    let input = constant_frames([0.9; DSP_CHANNELS], 480);
+    let mut output = input.clone();
+    compressor.process(&mut output);
+    assert_eq!(output, input);
+}
@@ -0,0 +1,50 @@
+use pw_ac3_live::compressor::NightModePreset;
+use pw_ac3_live::control::{apply_control_file_contents, apply_control_line};
+use pw_ac3_live::dialogue::DialogueEnhance;
+use pw_ac3_live::dsp::DspControls;
+
+#[test]
+fn control_line_sets_night_mode() {
+    let controls = DspControls::default();
+    apply_control_line("night-mode = night", &controls).expect("valid line");
+    assert_eq!(controls.night_mode(), NightModePreset::Night);
+
+    apply_control_line("night-mode=light", &controls).expect("valid line");
+    assert_eq!(controls.night_mode(), NightModePreset::Light);
+}
+
+#[test]
+fn control_line_ignores_blank_lines_and_comments() {
+    let controls = DspControls::new(NightModePreset::Light, DialogueEnhance::Off);
+    apply_control_line("", &controls).expect("blank line");
+    apply_control_line("   # night-mode = night", &controls).expect("comment");
+    apply_control_line("night-mode = off # quiet hours over", &controls).expect("trailing comment");
+    assert_eq!(controls.night_mode(), NightModePreset::Off);
+}
+
+#[test]
+fn control_line_rejects_unknown_keys_and_values() {
+    let controls = DspControls::default();
+    assert!(apply_control_line("volume = 11", &controls).is_err());
+    assert!(apply_control_line("night-mode = loud", &controls).is_err());
+    assert!(apply_control_line("night-mode", &controls).is_err());
+    assert_eq!(controls.night_mode(), NightModePreset::Off);
+}
+
+#[test]
+fn control_file_contents_skip_invalid_lines() {
+    let controls = DspControls::default();
+    apply_control_file_contents("bogus\nnight-mode = night\n", &controls);
+    assert_eq!(controls.night_mode(), NightModePreset::Night);
+}
+
+#[test]
+fn control_line_sets_dialogue_enhance() {
+    let controls = DspControls::default();
+    apply_control_line("dialogue-enhance = high", &controls).expect("valid line");
+    assert_eq!(controls.dialogue_enhance(), DialogueEnhance::High);
+    assert_eq!(controls.night_mode(), NightModePreset::Off);
+
+    assert!(apply_control_line("dialogue-enhance = max", &controls).is_err());
+    assert_eq!(controls.dialogue_enhance(), DialogueEnhance::High);
+}
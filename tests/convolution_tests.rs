@@ -0,0 +1,156 @@
+use pw_ac3_live::convolution::{load_fir_config, FirConfig, FirConvolver};
+use pw_ac3_live::dsp::DSP_CHANNELS;
+use pw_ac3_live::wav::parse_wav;
+use std::path::PathBuf;
+
+/// Minimal WAV writer for test fixtures.
+fn wav_bytes(
+    format_tag: u16,
+    bits: u16,
+    sample_rate: u32,
+    channel_count: u16,
+    data: &[u8],
+) -> Vec<u8> {
+    let block_align = channel_count * bits / 8;
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&format_tag.to_le_bytes());
+    bytes.extend_from_slice(&channel_count.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&bits.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+fn float_wav(sample_rate: u32, channels: &[Vec<f32>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for frame in 0..channels[0].len() {
+        for channel in channels {
+            data.extend_from_slice(&channel[frame].to_le_bytes());
+        }
+    }
+    wav_bytes(3, 32, sample_rate, channels.len() as u16, &data)
+}
+
+fn write_fixture(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("pw-ac3-live-{}-{name}", std::process::id()));
+    std::fs::write(&path, bytes).expect("fixture should be writable");
+    path
+}
+
+/// Direct-form reference convolution.
+fn convolve(signal: &[f32], ir: &[f32]) -> Vec<f32> {
+    (0..signal.len())
+        .map(|n| {
+            ir.iter()
+                .enumerate()
+                .filter(|(k, _)| *k <= n)
+                .map(|(k, h)| h * signal[n - k])
+                .sum()
+        })
+        .collect()
+}
+
+#[test]
+fn parse_wav_reads_int16_pcm() {
+    let mut data = Vec::new();
+    for sample in [16_384i16, -16_384, 0, i16::MIN] {
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+    let bytes = wav_bytes(1, 16, 44_100, 2, &data);
+
+    let wav = parse_wav(&bytes).expect("16-bit PCM should parse");
+    assert_eq!(wav.sample_rate, 44_100);
+    assert_eq!(wav.channels, vec![vec![0.5, 0.0], vec![-0.5, -1.0]]);
+}
+
+#[test]
+fn parse_wav_rejects_garbage() {
+    assert!(parse_wav(b"not a wav file").is_err());
+}
+
+#[test]
+fn convolver_matches_direct_convolution_with_partition_latency() {
+    // IR longer than several partitions, to exercise the frequency-domain delay line.
+    let partition = 16;
+    let ir: Vec<f32> = (0..70)
+        .map(|n| ((n * 7 % 11) as f32 - 5.0) / 10.0)
+        .collect();
+    let signal: Vec<f32> = (0..400)
+        .map(|n| ((n * 13 % 17) as f32 - 8.0) / 8.0)
+        .collect();
+
+    let mut config = FirConfig::new(partition);
+    config.filters[2] = Some(ir.clone());
+    let mut convolver = FirConvolver::new(&config);
+    assert_eq!(convolver.latency_frames(), partition);
+
+    let mut samples = Vec::with_capacity(signal.len() * DSP_CHANNELS);
+    for value in &signal {
+        samples.extend(std::iter::repeat_n(*value, DSP_CHANNELS));
+    }
+    // Feed in uneven chunks, as the feeder thread would.
+    for chunk in samples.chunks_mut(7 * DSP_CHANNELS) {
+        convolver.process(chunk);
+    }
+
+    let expected = convolve(&signal, &ir);
+    for n in 0..signal.len() - partition {
+        let fc = samples[(n + partition) * DSP_CHANNELS + 2];
+        assert!(
+            (fc - expected[n]).abs() < 1e-3,
+            "frame {n}: got {fc}, expected {}",
+            expected[n]
+        );
+        // Channels without a filter are only delayed.
+        let fl = samples[(n + partition) * DSP_CHANNELS];
+        assert_eq!(fl, signal[n]);
+    }
+}
+
+#[test]
+fn load_fir_config_maps_mono_and_multichannel_files() {
+    let mono = write_fixture("mono.wav", &float_wav(48_000, &[vec![1.0, 0.5]]));
+    let stereo = write_fixture("stereo.wav", &float_wav(48_000, &[vec![0.25], vec![0.75]]));
+
+    let specs = vec![
+        format!("SUB={}", mono.display()),
+        format!("L,R={}", stereo.display()),
+    ];
+    let config = load_fir_config(&specs, 64, 48_000).expect("specs should load");
+    assert_eq!(config.partition_frames, 64);
+    assert_eq!(config.filters[3].as_deref(), Some(&[1.0, 0.5][..]));
+    assert_eq!(config.filters[0].as_deref(), Some(&[0.25][..]));
+    assert_eq!(config.filters[1].as_deref(), Some(&[0.75][..]));
+    assert!(config.filters[2].is_none());
+
+    let _ = std::fs::remove_file(mono);
+    let _ = std::fs::remove_file(stereo);
+}
+
+#[test]
+fn load_fir_config_rejects_bad_specs() {
+    let ir = write_fixture("rate.wav", &float_wav(44_100, &[vec![1.0]]));
+    let spec = vec![format!("C={}", ir.display())];
+
+    // Sample rate mismatch.
+    assert!(load_fir_config(&spec, 256, 48_000).is_err());
+    // Partition size must be a power of two.
+    assert!(load_fir_config(&spec, 300, 44_100).is_err());
+    // Missing '='.
+    assert!(load_fir_config(&[ir.display().to_string()], 256, 44_100).is_err());
+    // Channel count mismatch.
+    let stereo = write_fixture("two.wav", &float_wav(44_100, &[vec![1.0], vec![1.0]]));
+    let spec = vec![format!("L,R,C={}", stereo.display())];
+    assert!(load_fir_config(&spec, 256, 44_100).is_err());
+
+    let _ = std::fs::remove_file(stereo);
+    let _ = std::fs::remove_file(ir);
+}
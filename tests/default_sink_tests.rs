@@ -0,0 +1,59 @@
+use pw_ac3_live::default_sink::{
+    clear_state, load_state, parse_sink_name, save_state, sink_name_json, TakeoverState,
+};
+
+#[test]
+fn sink_name_parses_metadata_json() {
+    assert_eq!(
+        parse_sink_name("{ \"name\": \"alsa_output.pci-0000_00_1f.3.hdmi-stereo\" }").as_deref(),
+        Some("alsa_output.pci-0000_00_1f.3.hdmi-stereo")
+    );
+    assert_eq!(
+        parse_sink_name("{\"name\":\"a\\\"b\"}").as_deref(),
+        Some("a\"b")
+    );
+    assert_eq!(parse_sink_name("{ \"name\": \"\" }"), None);
+    assert_eq!(parse_sink_name("{ }"), None);
+}
+
+#[test]
+fn sink_name_json_round_trips() {
+    for name in ["pw-ac3-live-input", "odd \"quoted\" \\ name"] {
+        assert_eq!(
+            parse_sink_name(&sink_name_json(name)).as_deref(),
+            Some(name)
+        );
+    }
+    assert_eq!(
+        sink_name_json("pw-ac3-live-input"),
+        "{ \"name\": \"pw-ac3-live-input\" }"
+    );
+}
+
+#[test]
+fn takeover_state_round_trips() {
+    for previous_default in [Some("alsa_output.hdmi-stereo#1".to_string()), None] {
+        let state = TakeoverState { previous_default };
+        assert_eq!(TakeoverState::parse(&state.to_contents()), Ok(state));
+    }
+    assert!(TakeoverState::parse("# empty\n").is_err());
+    assert!(TakeoverState::parse("garbage").is_err());
+}
+
+#[test]
+fn takeover_state_file_survives_until_cleared() {
+    let dir = std::env::temp_dir().join(format!("pw-ac3-live-state-{}", std::process::id()));
+    let path = dir.join("nested").join("default-sink.state");
+    let state = TakeoverState {
+        previous_default: Some("alsa_output.hdmi-stereo".to_string()),
+    };
+
+    assert_eq!(load_state(&path).expect("missing file is fine"), None);
+    save_state(&path, &state).expect("save");
+    assert_eq!(load_state(&path).expect("load"), Some(state));
+
+    clear_state(&path).expect("clear");
+    clear_state(&path).expect("clearing twice is fine");
+    assert_eq!(load_state(&path).expect("load after clear"), None);
+    std::fs::remove_dir_all(&dir).ok();
+}
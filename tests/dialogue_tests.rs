@@ -0,0 +1,100 @@
+use pw_ac3_live::dialogue::{DialogueEnhance, DialogueEnhancer};
+use pw_ac3_live::dsp::DSP_CHANNELS;
+
+const SAMPLE_RATE: u32 = 48_000;
+
+/// Builds one second of 5.1 frames: a sine of `center_hz` in FC (0 = silent FC)
+/// and a constant `bed` level on every other channel.
+fn test_frames(center_hz: f32, center_amplitude: f32, bed: f32) -> Vec<f32> {
+    let frames = SAMPLE_RATE as usize;
+    let mut samples = Vec::with_capacity(frames * DSP_CHANNELS);
+    for n in 0..frames {
+        let t = n as f32 / SAMPLE_RATE as f32;
+        let center = if center_hz > 0.0 {
+            center_amplitude * (2.0 * std::f32::consts::PI * center_hz * t).sin()
+        } else {
+            0.0
+        };
+        samples.extend_from_slice(&[bed, bed, center, bed, bed, bed]);
+    }
+    samples
+}
+
+/// Peak of channel `ch` over the last 100 ms.
+fn tail_peak(samples: &[f32], ch: usize) -> f32 {
+    let tail_frames = SAMPLE_RATE as usize / 10;
+    samples[samples.len() - tail_frames * DSP_CHANNELS..]
+        .chunks_exact(DSP_CHANNELS)
+        .map(|frame| frame[ch].abs())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn dialogue_enhance_parses_known_levels() {
+    assert_eq!("off".parse::<DialogueEnhance>(), Ok(DialogueEnhance::Off));
+    assert_eq!(" Low ".parse::<DialogueEnhance>(), Ok(DialogueEnhance::Low));
+    assert_eq!("HIGH".parse::<DialogueEnhance>(), Ok(DialogueEnhance::High));
+    assert!("max".parse::<DialogueEnhance>().is_err());
+}
+
+#[test]
+fn off_is_bit_exact_bypass() {
+    let mut enhancer = DialogueEnhancer::new(SAMPLE_RATE, DialogueEnhance::Off);
+    let input = test_frames(1_000.0, 0.3, 0.2);
+    let mut output = input.clone();
+    enhancer.process(&mut output);
+    assert_eq!(output, input);
+}
+
+#[test]
+fn speech_band_center_is_boosted_and_other_channels_ducked() {
+    let mut enhancer = DialogueEnhancer::new(SAMPLE_RATE, DialogueEnhance::High);
+    let mut samples = test_frames(1_000.0, 0.1, 0.2);
+    enhancer.process(&mut samples);
+
+    assert!(enhancer.activity() > 0.99);
+    // +8 dB on the speech band is ~2.5x for a 1 kHz tone.
+    let center = tail_peak(&samples, 2);
+    assert!(center > 0.22 && center < 0.27, "center peak {center}");
+    // -4 dB on FL/FR/SL/SR, LFE untouched.
+    for ch in [0, 1, 4, 5] {
+        let level = tail_peak(&samples, ch);
+        assert!((level - 0.2 * 0.631).abs() < 0.005, "channel {ch}: {level}");
+    }
+    assert_eq!(tail_peak(&samples, 3), 0.2);
+}
+
+#[test]
+fn non_speech_center_content_is_left_alone() {
+    // Quiet center and a low bass tone below the speech band.
+    for (hz, amplitude) in [(0.0, 0.0), (60.0, 0.5)] {
+        let mut enhancer = DialogueEnhancer::new(SAMPLE_RATE, DialogueEnhance::High);
+        let input = test_frames(hz, amplitude, 0.2);
+        let mut output = input.clone();
+        enhancer.process(&mut output);
+
+        assert!(
+            enhancer.activity() < 0.01,
+            "{hz} Hz activity {}",
+            enhancer.activity()
+        );
+        let level = tail_peak(&output, 0);
+        assert!((level - 0.2).abs() < 1.0e-3, "{hz} Hz: FL {level}");
+    }
+}
+
+#[test]
+fn switching_off_restores_bypass() {
+    let mut enhancer = DialogueEnhancer::new(SAMPLE_RATE, DialogueEnhance::Low);
+    let mut samples = test_frames(1_000.0, 0.1, 0.2);
+    enhancer.process(&mut samples);
+    assert!(enhancer.activity() > 0.5);
+
+    enhancer.set_level(DialogueEnhance::Off);
+    assert_eq!(enhancer.level(), DialogueEnhance::Off);
+    assert_eq!(enhancer.activity(), 0.0);
+    let input = test_frames(1_000.0, 0.1, 0.2);
+    let mut output = input.clone();
+    enhancer.process(&mut output);
+    assert_eq!(output, input);
+}
@@ -1,6 +1,9 @@
+use pw_ac3_live::acmod::{self, ChannelLayoutMode};
+use pw_ac3_live::alsa_control::Iec958StatusSwitch;
 use pw_ac3_live::encoder;
+use pw_ac3_live::iec61937;
 use rtrb::{Consumer, RingBuffer};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread;
@@ -354,11 +357,13 @@ fn test_encoder_custom_config() {
     let config = encoder::EncoderConfig {
         ffmpeg_thread_queue_size: 1,
         feeder_chunk_frames: 1,
+        ..Default::default()
     };
 
     let encoder_handle = thread::spawn(move || {
         encoder::run_encoder_loop_with_config(
             input_consumer,
+            None,
             output_producer,
             encoder_running,
             config,
@@ -406,11 +411,13 @@ fn test_encoder_zero_config_values() {
     let config = encoder::EncoderConfig {
         ffmpeg_thread_queue_size: 0,
         feeder_chunk_frames: 0,
+        ..Default::default()
     };
 
     let encoder_handle = thread::spawn(move || {
         encoder::run_encoder_loop_with_config(
             input_consumer,
+            None,
             output_producer,
             encoder_running,
             config,
@@ -680,6 +687,15 @@ fn test_encoder_config_default_values() {
     let config = encoder::EncoderConfig::default();
     assert_eq!(config.ffmpeg_thread_queue_size, 128);
     assert_eq!(config.feeder_chunk_frames, 128);
+    assert_eq!(config.sample_rate_hz, encoder::SAMPLE_RATE_HZ);
+    assert_eq!(config.output_rate_hz.load(Ordering::Relaxed), 0);
+    assert_eq!(config.channel_layout, ChannelLayoutMode::Surround51);
+    assert!(!config.pcm_bypass.enabled);
+    assert!(!config.pcm_bypass_active.load(Ordering::Relaxed));
+    assert_eq!(
+        config.capture_channel_mask.load(Ordering::Relaxed),
+        acmod::ALL_CHANNELS_MASK
+    );
 }
 
 #[test]
@@ -687,4 +703,713 @@ fn test_pipewire_config_default_values() {
     use pw_ac3_live::pipewire_client::PipewireConfig;
     let config = PipewireConfig::default();
     assert_eq!(config.node_latency, "64/48000");
+    assert_eq!(config.sample_rate_hz, 48_000);
+    assert_eq!(config.output_rate_hz.load(Ordering::Relaxed), 0);
+    assert_eq!(
+        config.capture_channel_mask.load(Ordering::Relaxed),
+        acmod::ALL_CHANNELS_MASK
+    );
+}
+
+#[test]
+fn test_parse_sample_rate_accepts_ac3_rates_only() {
+    assert_eq!(encoder::parse_sample_rate("48000"), Ok(48_000));
+    assert_eq!(encoder::parse_sample_rate("44.1k"), Ok(44_100));
+    assert_eq!(encoder::parse_sample_rate(" 32K "), Ok(32_000));
+    assert!(encoder::parse_sample_rate("96000").is_err());
+    assert!(encoder::parse_sample_rate("fast").is_err());
+}
+
+#[test]
+fn test_encoder_rejects_unsupported_sample_rate() {
+    let (_, input_consumer) = RingBuffer::<f32>::new(64);
+    let (output_producer, _) = RingBuffer::<u8>::new(64);
+    let config = encoder::EncoderConfig {
+        sample_rate_hz: 96_000,
+        ..Default::default()
+    };
+
+    let result = encoder::run_encoder_loop_with_config(
+        input_consumer,
+        None,
+        output_producer,
+        Arc::new(AtomicBool::new(true)),
+        config,
+    );
+    assert!(result.is_err());
+}
+
+/// Burst-info (Pc) words of every IEC 61937 burst found in `data`.
+fn burst_data_types(data: &[u8]) -> Vec<u16> {
+    let mut types = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = iec61937::find_preamble(&data[offset..]) {
+        let pc = offset + found + 4;
+        if pc + 2 > data.len() {
+            break;
+        }
+        types.push(u16::from_le_bytes([data[pc], data[pc + 1]]));
+        offset = pc;
+    }
+    types
+}
+
+#[test]
+fn test_encoder_restarts_on_output_rate_change() {
+    let (mut input_producer, input_consumer) = RingBuffer::<f32>::new(48_000 * 6);
+    let (output_producer, mut output_consumer) = RingBuffer::<u8>::new(48_000 * 6 * 4);
+    let output_rate_hz = Arc::new(AtomicU32::new(0));
+    let config = encoder::EncoderConfig {
+        output_rate_hz: output_rate_hz.clone(),
+        ..Default::default()
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let encoder_running = running.clone();
+    let encoder_handle = thread::spawn(move || {
+        encoder::run_encoder_loop_with_config(
+            input_consumer,
+            None,
+            output_producer,
+            encoder_running,
+            config,
+        )
+    });
+
+    let chunk = vec![0.0f32; 1024 * 6];
+    let mut data = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut switched = false;
+    while Instant::now() < deadline {
+        if let Ok(write) = input_producer.write_chunk_uninit(chunk.len()) {
+            write.fill_from_iter(chunk.iter().copied());
+        }
+        let available = output_consumer.slots();
+        if let Ok(read) = output_consumer.read_chunk(available) {
+            data.extend(read);
+        }
+
+        let types = burst_data_types(&data);
+        if !switched && !types.is_empty() {
+            // The playback stream renegotiated to 44.1 kHz.
+            output_rate_hz.store(44_100, Ordering::SeqCst);
+            switched = true;
+        }
+        let pause = types.iter().position(|pc| *pc == iec61937::DATA_TYPE_PAUSE);
+        if pause.is_some_and(|index| types[index..].iter().any(|pc| pc & 0x1f == 0x01)) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    running.store(false, Ordering::SeqCst);
+    let result = encoder_handle.join().unwrap();
+    assert!(result.is_ok(), "encoder failed: {result:?}");
+
+    let types = burst_data_types(&data);
+    let pause = types
+        .iter()
+        .position(|pc| *pc == iec61937::DATA_TYPE_PAUSE)
+        .expect("rate change should emit pause bursts");
+    assert!(
+        types[..pause].iter().any(|pc| pc & 0x1f == 0x01),
+        "AC-3 bursts expected before the restart"
+    );
+    assert!(
+        types[pause..].iter().any(|pc| pc & 0x1f == 0x01),
+        "AC-3 bursts expected after the restart"
+    );
+}
+
+#[test]
+fn test_encoder_auto_layout_switches_to_stereo() {
+    let (mut input_producer, input_consumer) = RingBuffer::<f32>::new(48_000 * 6);
+    let (output_producer, mut output_consumer) = RingBuffer::<u8>::new(48_000 * 6 * 4);
+    let capture_channel_mask = Arc::new(AtomicU32::new(acmod::ALL_CHANNELS_MASK));
+    let config = encoder::EncoderConfig {
+        channel_layout: ChannelLayoutMode::Auto,
+        auto_layout: acmod::AutoLayoutConfig {
+            use_activity: false,
+            hold_ms: 0,
+        },
+        capture_channel_mask: capture_channel_mask.clone(),
+        ..Default::default()
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let encoder_running = running.clone();
+    let encoder_handle = thread::spawn(move || {
+        encoder::run_encoder_loop_with_config(
+            input_consumer,
+            None,
+            output_producer,
+            encoder_running,
+            config,
+        )
+    });
+
+    let chunk = vec![0.0f32; 1024 * 6];
+    let mut data = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut switched = false;
+    while Instant::now() < deadline {
+        if let Ok(write) = input_producer.write_chunk_uninit(chunk.len()) {
+            write.fill_from_iter(chunk.iter().copied());
+        }
+        let available = output_consumer.slots();
+        if let Ok(read) = output_consumer.read_chunk(available) {
+            data.extend(read);
+        }
+
+        let types = burst_data_types(&data);
+        if !switched && !types.is_empty() {
+            // Only a stereo client is connected now.
+            capture_channel_mask.store(acmod::FRONT_PAIR_MASK, Ordering::SeqCst);
+            switched = true;
+        }
+        let pause = types.iter().position(|pc| *pc == iec61937::DATA_TYPE_PAUSE);
+        if pause.is_some_and(|index| types[index..].iter().any(|pc| pc & 0x1f == 0x01)) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    running.store(false, Ordering::SeqCst);
+    let result = encoder_handle.join().unwrap();
+    assert!(result.is_ok(), "encoder failed: {result:?}");
+
+    let types = burst_data_types(&data);
+    let pause = types
+        .iter()
+        .position(|pc| *pc == iec61937::DATA_TYPE_PAUSE)
+        .expect("layout switch should emit pause bursts");
+    assert!(
+        types[pause..].iter().any(|pc| pc & 0x1f == 0x01),
+        "AC-3 bursts expected after the layout switch"
+    );
+}
+
+#[test]
+fn test_encoder_pcm_bypass_follows_surround_activity() {
+    let (mut input_producer, input_consumer) = RingBuffer::<f32>::new(48_000 * 6);
+    let (output_producer, mut output_consumer) = RingBuffer::<u8>::new(48_000 * 6 * 4);
+    let pcm_bypass_active = Arc::new(AtomicBool::new(false));
+    let config = encoder::EncoderConfig {
+        pcm_bypass: encoder::PcmBypassConfig {
+            enabled: true,
+            hold_ms: 0,
+        },
+        pcm_bypass_active: pcm_bypass_active.clone(),
+        ..Default::default()
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let encoder_running = running.clone();
+    let encoder_handle = thread::spawn(move || {
+        encoder::run_encoder_loop_with_config(
+            input_consumer,
+            None,
+            output_producer,
+            encoder_running,
+            config,
+        )
+    });
+
+    // Stereo-only music: FL/FR carry signal, everything else is silent.
+    let stereo: Vec<f32> = [0.25, -0.25, 0.0, 0.0, 0.0, 0.0].repeat(1024);
+    let surround: Vec<f32> = [0.25, -0.25, 0.25, 0.0, 0.0, 0.0].repeat(1024);
+    let mut data = Vec::new();
+    let mut bypassed = false;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        bypassed |= pcm_bypass_active.load(Ordering::SeqCst);
+        let chunk = if bypassed { &surround } else { &stereo };
+        if let Ok(write) = input_producer.write_chunk_uninit(chunk.len()) {
+            write.fill_from_iter(chunk.iter().copied());
+        }
+        let available = output_consumer.slots();
+        if let Ok(read) = output_consumer.read_chunk(available) {
+            data.extend(read);
+        }
+
+        let types = burst_data_types(&data);
+        let pause = types.iter().position(|pc| *pc == iec61937::DATA_TYPE_PAUSE);
+        if bypassed && pause.is_some_and(|index| types[index..].iter().any(|pc| pc & 0x1f == 0x01))
+        {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    running.store(false, Ordering::SeqCst);
+    let result = encoder_handle.join().unwrap();
+    assert!(result.is_ok(), "encoder failed: {result:?}");
+    assert!(bypassed, "stereo-only input should bypass AC-3");
+    assert!(!pcm_bypass_active.load(Ordering::SeqCst));
+
+    // The bypass wrote the FL/FR samples as S16LE PCM.
+    let left = 0.25f32 * f32::from(i16::MAX);
+    let pcm_frame = [
+        (left.round() as i16).to_le_bytes(),
+        ((-left).round() as i16).to_le_bytes(),
+    ]
+    .concat();
+    assert!(
+        data.chunks_exact(4)
+            .any(|frame| frame == pcm_frame.as_slice()),
+        "stereo PCM frames expected while bypassing"
+    );
+    let types = burst_data_types(&data);
+    let pause = types
+        .iter()
+        .position(|pc| *pc == iec61937::DATA_TYPE_PAUSE)
+        .expect("resuming AC-3 should emit pause bursts");
+    assert!(
+        types[pause..].iter().any(|pc| pc & 0x1f == 0x01),
+        "AC-3 bursts expected once the center channel is active"
+    );
+}
+
+#[test]
+fn test_encoder_pcm_bypass_waits_for_iec958_status() {
+    let (mut input_producer, input_consumer) = RingBuffer::<f32>::new(48_000 * 6);
+    let (output_producer, mut output_consumer) = RingBuffer::<u8>::new(48_000 * 6 * 4);
+    let status = Arc::new(Iec958StatusSwitch::new());
+    let config = encoder::EncoderConfig {
+        pcm_bypass: encoder::PcmBypassConfig {
+            enabled: true,
+            hold_ms: 0,
+        },
+        iec958_status: Some(status.clone()),
+        output_latency: Duration::from_millis(20),
+        ..Default::default()
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let encoder_running = running.clone();
+    let encoder_handle = thread::spawn(move || {
+        encoder::run_encoder_loop_with_config(
+            input_consumer,
+            None,
+            output_producer,
+            encoder_running,
+            config,
+        )
+    });
+
+    // A slow status follower: the flag is applied 100 ms after the request.
+    let applying = Arc::new(AtomicBool::new(false));
+    let follower_applying = applying.clone();
+    let follower_status = status.clone();
+    let follower = thread::spawn(move || {
+        while let Some(pcm) = follower_status.next_request() {
+            thread::sleep(Duration::from_millis(100));
+            follower_applying.store(true, Ordering::SeqCst);
+            follower_status.set_applied(pcm);
+        }
+    });
+
+    let left = 0.25f32 * f32::from(i16::MAX);
+    let pcm_frame = [
+        (left.round() as i16).to_le_bytes(),
+        ((-left).round() as i16).to_le_bytes(),
+    ]
+    .concat();
+    let first_pcm = |data: &[u8]| {
+        data.chunks_exact(4)
+            .position(|frame| frame == pcm_frame.as_slice())
+            .map(|index| index * 4)
+    };
+
+    let stereo: Vec<f32> = [0.25, -0.25, 0.0, 0.0, 0.0, 0.0].repeat(1024);
+    let mut data = Vec::new();
+    // Output read before the follower started applying the flag.
+    let mut before_applied = 0;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if let Ok(write) = input_producer.write_chunk_uninit(stereo.len()) {
+            write.fill_from_iter(stereo.iter().copied());
+        }
+        let applied = applying.load(Ordering::SeqCst);
+        let available = output_consumer.slots();
+        if let Ok(read) = output_consumer.read_chunk(available) {
+            data.extend(read);
+        }
+        if !applied {
+            before_applied = data.len();
+        }
+        if first_pcm(&data).is_some() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    running.store(false, Ordering::SeqCst);
+    status.close();
+    let result = encoder_handle.join().unwrap();
+    assert!(result.is_ok(), "encoder failed: {result:?}");
+    follower.join().unwrap();
+
+    assert!(
+        applying.load(Ordering::SeqCst),
+        "the encoder should request the PCM flag"
+    );
+    let pcm = first_pcm(&data).expect("stereo PCM frames expected once the flag is applied");
+    assert!(
+        pcm >= before_applied,
+        "PCM must not reach the output before the IEC958 flag is applied"
+    );
+    // 32 ms of pause bursts plus the 20 ms the device holds.
+    let pauses = burst_data_types(&data[..pcm])
+        .iter()
+        .rev()
+        .take_while(|pc| **pc == iec61937::DATA_TYPE_PAUSE)
+        .count();
+    assert!(
+        pauses * iec61937::PAUSE_BURST_FRAMES >= 48 * 52,
+        "the pause gap should cover the device latency"
+    );
+}
+
+#[test]
+fn test_encoder_passes_iec61937_input_through() {
+    let (mut input_producer, input_consumer) = RingBuffer::<f32>::new(48_000 * 6);
+    let (output_producer, mut output_consumer) = RingBuffer::<u8>::new(48_000 * 6 * 4);
+    let running = Arc::new(AtomicBool::new(true));
+    let encoder_running = running.clone();
+    let encoder_handle = thread::spawn(move || {
+        encoder::run_encoder_loop_with_config(
+            input_consumer,
+            None,
+            output_producer,
+            encoder_running,
+            encoder::EncoderConfig::default(),
+        )
+    });
+
+    // A player in passthrough mode: one AC-3 burst per 1536 frames of 2ch S16,
+    // converted to float by the graph and routed to FL/FR of the 5.1 sink.
+    let mut words = vec![0u16; 1536 * 2];
+    words[..4].copy_from_slice(&[
+        iec61937::PREAMBLE_PA,
+        iec61937::PREAMBLE_PB,
+        iec61937::DATA_TYPE_AC3,
+        64,
+    ]);
+    words[4..8].copy_from_slice(&[0xbeef, 0xcafe, 0x1234, 0x5678]);
+    let burst: Vec<f32> = words
+        .chunks_exact(2)
+        .flat_map(|pair| {
+            let left = f32::from(pair[0] as i16) / 32768.0;
+            let right = f32::from(pair[1] as i16) / 32768.0;
+            [left, right, 0.0, 0.0, 0.0, 0.0]
+        })
+        .collect();
+    let expected: Vec<u8> = words[..8]
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect();
+    let silence = vec![0.0f32; 1536 * 6];
+
+    let mut data = Vec::new();
+    let mut bursts_sent = 0;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        // Send a few bursts, then plain PCM again.
+        let chunk = if bursts_sent < 8 { &burst } else { &silence };
+        if let Ok(write) = input_producer.write_chunk_uninit(chunk.len()) {
+            write.fill_from_iter(chunk.iter().copied());
+            bursts_sent += 1;
+        }
+        let available = output_consumer.slots();
+        if let Ok(read) = output_consumer.read_chunk(available) {
+            data.extend(read);
+        }
+
+        let forwarded = data.windows(expected.len()).position(|w| w == expected);
+        let types = burst_data_types(&data);
+        if forwarded.is_some() && types.contains(&iec61937::DATA_TYPE_PAUSE) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    running.store(false, Ordering::SeqCst);
+    let result = encoder_handle.join().unwrap();
+    assert!(result.is_ok(), "encoder failed: {result:?}");
+
+    let forwarded = data
+        .windows(expected.len())
+        .position(|window| window == expected)
+        .expect("input bursts should be forwarded bit-exactly");
+    assert_eq!(forwarded % 4, 0, "forwarded bursts must stay frame aligned");
+    assert!(
+        burst_data_types(&data[forwarded..]).contains(&iec61937::DATA_TYPE_PAUSE),
+        "encoding should resume once the input is PCM again"
+    );
+}
+
+#[test]
+fn test_encoder_transcodes_non_ac3_iec61937_input() {
+    let (mut input_producer, input_consumer) = RingBuffer::<f32>::new(48_000 * 6);
+    let (output_producer, mut output_consumer) = RingBuffer::<u8>::new(48_000 * 6 * 4);
+    let running = Arc::new(AtomicBool::new(true));
+    let encoder_running = running.clone();
+    let encoder_handle = thread::spawn(move || {
+        encoder::run_encoder_loop_with_config(
+            input_consumer,
+            None,
+            output_producer,
+            encoder_running,
+            encoder::EncoderConfig::default(),
+        )
+    });
+
+    // A player passing E-AC-3 through: one burst per 1536 frames on FL/FR.
+    let mut words = vec![0u16; 1536 * 2];
+    words[..4].copy_from_slice(&[
+        iec61937::PREAMBLE_PA,
+        iec61937::PREAMBLE_PB,
+        iec61937::DATA_TYPE_EAC3,
+        64,
+    ]);
+    let burst: Vec<f32> = words
+        .chunks_exact(2)
+        .flat_map(|pair| {
+            let left = f32::from(pair[0] as i16) / 32768.0;
+            let right = f32::from(pair[1] as i16) / 32768.0;
+            [left, right, 0.0, 0.0, 0.0, 0.0]
+        })
+        .collect();
+
+    let mut data = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if let Ok(write) = input_producer.write_chunk_uninit(burst.len()) {
+            write.fill_from_iter(burst.iter().copied());
+        }
+        let available = output_consumer.slots();
+        if let Ok(read) = output_consumer.read_chunk(available) {
+            data.extend(read);
+        }
+
+        let types = burst_data_types(&data);
+        let pause = types.iter().position(|pc| *pc == iec61937::DATA_TYPE_PAUSE);
+        if pause.is_some_and(|index| types[index..].iter().any(|pc| pc & 0x1f == 0x01)) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    running.store(false, Ordering::SeqCst);
+    let result = encoder_handle.join().unwrap();
+    assert!(result.is_ok(), "encoder failed: {result:?}");
+
+    let types = burst_data_types(&data);
+    assert!(
+        !types.contains(&iec61937::DATA_TYPE_EAC3),
+        "E-AC-3 bursts must not reach an AC-3 output"
+    );
+    let pause = types
+        .iter()
+        .position(|pc| *pc == iec61937::DATA_TYPE_PAUSE)
+        .expect("switching to the transcoder should emit pause bursts");
+    assert!(
+        types[pause..].iter().any(|pc| pc & 0x1f == 0x01),
+        "transcoded AC-3 bursts expected"
+    );
+}
+
+/// One burst of `data_type` per `frames` frames, as a passthrough player's 2ch
+/// S16 words on FL/FR of the 5.1 input.
+fn passthrough_burst(data_type: u16, pd: u16, frames: usize) -> Vec<f32> {
+    let mut words = vec![0u16; frames * 2];
+    words[..4].copy_from_slice(&[iec61937::PREAMBLE_PA, iec61937::PREAMBLE_PB, data_type, pd]);
+    words
+        .chunks_exact(2)
+        .flat_map(|pair| {
+            let left = f32::from(pair[0] as i16) / 32768.0;
+            let right = f32::from(pair[1] as i16) / 32768.0;
+            [left, right, 0.0, 0.0, 0.0, 0.0]
+        })
+        .collect()
+}
+
+/// Feeds `burst` repeatedly until `done` holds for the burst data types seen on
+/// the output (or a timeout), and returns those types.
+fn run_bitstream_input(
+    config: encoder::EncoderConfig,
+    burst: &[f32],
+    done: impl Fn(&[u16]) -> bool,
+) -> Vec<u16> {
+    let (mut input_producer, input_consumer) = RingBuffer::<f32>::new(48_000 * 6);
+    let (output_producer, mut output_consumer) = RingBuffer::<u8>::new(48_000 * 6 * 4);
+    let running = Arc::new(AtomicBool::new(true));
+    let encoder_running = running.clone();
+    let encoder_handle = thread::spawn(move || {
+        encoder::run_encoder_loop_with_config(
+            input_consumer,
+            None,
+            output_producer,
+            encoder_running,
+            config,
+        )
+    });
+
+    let mut data = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if let Ok(write) = input_producer.write_chunk_uninit(burst.len()) {
+            write.fill_from_iter(burst.iter().copied());
+        }
+        let available = output_consumer.slots();
+        if let Ok(read) = output_consumer.read_chunk(available) {
+            data.extend(read);
+        }
+        if done(&burst_data_types(&data)) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    running.store(false, Ordering::SeqCst);
+    let result = encoder_handle.join().unwrap();
+    assert!(result.is_ok(), "encoder failed: {result:?}");
+    burst_data_types(&data)
+}
+
+#[test]
+fn test_encoder_takes_eac3_input_at_192khz() {
+    // E-AC-3 on its usual 4x carrier: one burst per 6144 frames at 192 kHz,
+    // with AC-3 going out at 48 kHz.
+    let config = encoder::EncoderConfig {
+        capture_rate_hz: Arc::new(AtomicU32::new(192_000)),
+        ..Default::default()
+    };
+    let burst = passthrough_burst(iec61937::DATA_TYPE_EAC3, 64, 6144);
+    let types = run_bitstream_input(config, &burst, |types| {
+        types
+            .iter()
+            .position(|pc| *pc == iec61937::DATA_TYPE_PAUSE)
+            .is_some_and(|index| types.len() > index + 16)
+    });
+
+    assert!(
+        !types.contains(&iec61937::DATA_TYPE_EAC3),
+        "E-AC-3 bursts must not reach an AC-3 output"
+    );
+    assert!(
+        types.contains(&iec61937::DATA_TYPE_PAUSE),
+        "the 192 kHz bitstream should be detected and bridged with pause bursts"
+    );
+}
+
+#[test]
+fn test_encoder_mutes_bitstreams_it_cannot_transcode() {
+    // DTS-HD needs a high-bit-rate carrier; ffmpeg cannot read it from FL/FR.
+    let burst = passthrough_burst(iec61937::DATA_TYPE_DTS_HD, 64, 8192);
+    let types = run_bitstream_input(encoder::EncoderConfig::default(), &burst, |types| {
+        types
+            .iter()
+            .position(|pc| *pc == iec61937::DATA_TYPE_PAUSE)
+            .is_some_and(|index| types.len() > index + 64)
+    });
+
+    assert!(
+        !types.contains(&iec61937::DATA_TYPE_DTS_HD),
+        "DTS-HD bursts must not reach an AC-3 output"
+    );
+    let pause = types
+        .iter()
+        .position(|pc| *pc == iec61937::DATA_TYPE_PAUSE)
+        .expect("the muted bitstream should be replaced by pause bursts");
+    assert!(
+        types[pause..]
+            .iter()
+            .all(|pc| *pc == iec61937::DATA_TYPE_PAUSE),
+        "only pause bursts expected while the bitstream is muted"
+    );
+}
+
+#[test]
+fn test_encoder_silences_bursts_until_passthrough_starts() {
+    let (mut input_producer, input_consumer) = RingBuffer::<f32>::new(48_000 * 6);
+    let (output_producer, mut output_consumer) = RingBuffer::<u8>::new(48_000 * 6 * 4);
+    let pcm_bypass_active = Arc::new(AtomicBool::new(false));
+    // The PCM bypass writes the feeder's samples straight to the output, so
+    // whatever the encode path gets shows up there.
+    let config = encoder::EncoderConfig {
+        pcm_bypass: encoder::PcmBypassConfig {
+            enabled: true,
+            hold_ms: 0,
+        },
+        pcm_bypass_active: pcm_bypass_active.clone(),
+        ..Default::default()
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let encoder_running = running.clone();
+    let encoder_handle = thread::spawn(move || {
+        encoder::run_encoder_loop_with_config(
+            input_consumer,
+            None,
+            output_producer,
+            encoder_running,
+            config,
+        )
+    });
+
+    let left = 0.25f32 * f32::from(i16::MAX);
+    let pcm_frame = [
+        (left.round() as i16).to_le_bytes(),
+        ((-left).round() as i16).to_le_bytes(),
+    ]
+    .concat();
+    let first_pcm = |data: &[u8]| {
+        data.chunks_exact(4)
+            .position(|frame| frame == pcm_frame.as_slice())
+            .map(|index| index * 4)
+    };
+
+    // Stereo music until the bypass is on, then a player starts passthrough.
+    let stereo: Vec<f32> = [0.25, -0.25, 0.0, 0.0, 0.0, 0.0].repeat(1024);
+    let burst = passthrough_burst(iec61937::DATA_TYPE_AC3, 64, 1536);
+    let mut data = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        let bypassing = first_pcm(&data);
+        let chunk = if bypassing.is_some() { &burst } else { &stereo };
+        if let Ok(write) = input_producer.write_chunk_uninit(chunk.len()) {
+            write.fill_from_iter(chunk.iter().copied());
+        }
+        let available = output_consumer.slots();
+        if let Ok(read) = output_consumer.read_chunk(available) {
+            data.extend(read);
+        }
+        if bypassing
+            .is_some_and(|pcm| burst_data_types(&data[pcm..]).contains(&iec61937::DATA_TYPE_AC3))
+        {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    running.store(false, Ordering::SeqCst);
+    let result = encoder_handle.join().unwrap();
+    assert!(result.is_ok(), "encoder failed: {result:?}");
+    assert!(!pcm_bypass_active.load(Ordering::SeqCst));
+
+    let pcm = first_pcm(&data).expect("stereo input should bypass AC-3");
+    let switch =
+        pcm + iec61937::find_preamble(&data[pcm..]).expect("passthrough should follow the bypass");
+    assert!(
+        data[pcm..switch]
+            .chunks_exact(4)
+            .all(|frame| frame == pcm_frame.as_slice() || frame == [0u8; 4]),
+        "bursts must not reach the PCM path before they are confirmed"
+    );
+    assert!(
+        burst_data_types(&data[switch..]).contains(&iec61937::DATA_TYPE_AC3),
+        "the bursts should be passed through"
+    );
 }
@@ -0,0 +1,146 @@
+use pw_ac3_live::dsp::DSP_CHANNELS;
+use pw_ac3_live::equalizer::{parse_eq_config, BiquadKind, EqConfig, Equalizer};
+use std::f32::consts::PI;
+
+const SAMPLE_RATE: u32 = 48_000;
+
+/// Feeds a sine on every channel and returns the steady-state peak per channel.
+fn steady_state_peaks(config: &EqConfig, freq_hz: f32) -> [f32; DSP_CHANNELS] {
+    let frames = SAMPLE_RATE as usize / 2;
+    let mut samples = Vec::with_capacity(frames * DSP_CHANNELS);
+    for n in 0..frames {
+        let value = 0.5 * (2.0 * PI * freq_hz * n as f32 / SAMPLE_RATE as f32).sin();
+        samples.extend(std::iter::repeat_n(value, DSP_CHANNELS));
+    }
+
+    Equalizer::new(config, SAMPLE_RATE).process(&mut samples);
+
+    let mut peaks = [0.0f32; DSP_CHANNELS];
+    for frame in samples[samples.len() / 2..].chunks_exact(DSP_CHANNELS) {
+        for (peak, sample) in peaks.iter_mut().zip(frame) {
+            *peak = peak.max(sample.abs());
+        }
+    }
+    peaks
+}
+
+#[test]
+fn parses_rew_export_with_header_lines() {
+    let config = parse_eq_config(
+        "Filter Settings file\n\
+         \n\
+         Room EQ V5.20\n\
+         Preamp: -6.5 dB\n\
+         Filter  1: ON  PK       Fc   50.0 Hz  Gain  -3.0 dB  Q  4.000\n\
+         Filter  2: ON  LSC 12 dB Fc  105 Hz  Gain   5.0 dB\n\
+         Filter  3: OFF PK       Fc 1000 Hz  Gain   2.0 dB  Q  1.000\n\
+         Filter  4: ON  None\n",
+    )
+    .expect("REW export should parse");
+
+    for channel in &config.channels {
+        assert_eq!(channel.preamp_db, -6.5);
+        assert_eq!(channel.filters.len(), 2);
+        assert_eq!(channel.filters[0].kind, BiquadKind::Peaking);
+        assert_eq!(channel.filters[0].freq_hz, 50.0);
+        assert_eq!(channel.filters[0].gain_db, -3.0);
+        assert_eq!(channel.filters[0].q, 4.0);
+        assert_eq!(channel.filters[1].kind, BiquadKind::LowShelf);
+    }
+}
+
+#[test]
+fn channel_command_scopes_following_filters() {
+    let config = parse_eq_config(
+        "Channel: L R\n\
+         Filter: ON HS Fc 8000 Hz Gain -2 dB\n\
+         Channel: SUB\n\
+         Filter: ON LP Fc 120 Hz\n\
+         Channel: RL RR\n\
+         Preamp: -3 dB\n\
+         Channel: all\n\
+         Filter: ON HP Fc 15 Hz\n",
+    )
+    .expect("channel-scoped config should parse");
+
+    let kinds = |ch: usize| -> Vec<BiquadKind> {
+        config.channels[ch].filters.iter().map(|f| f.kind).collect()
+    };
+    assert_eq!(kinds(0), vec![BiquadKind::HighShelf, BiquadKind::HighPass]);
+    assert_eq!(kinds(1), vec![BiquadKind::HighShelf, BiquadKind::HighPass]);
+    assert_eq!(kinds(2), vec![BiquadKind::HighPass]);
+    assert_eq!(kinds(3), vec![BiquadKind::LowPass, BiquadKind::HighPass]);
+    assert_eq!(config.channels[4].preamp_db, -3.0);
+    assert_eq!(config.channels[5].preamp_db, -3.0);
+    assert_eq!(config.channels[0].preamp_db, 0.0);
+}
+
+#[test]
+fn invalid_lines_report_line_number() {
+    let err = parse_eq_config("Preamp: 0 dB\nFilter 1: ON XX Fc 100 Hz\n")
+        .expect_err("unknown filter type must fail");
+    assert!(format!("{err:#}").contains("line 2"), "{err:#}");
+
+    assert!(parse_eq_config("Channel: 9\n").is_err());
+    assert!(parse_eq_config("Filter: ON PK Gain 3 dB Q 1\n").is_err());
+    assert!(parse_eq_config("Filter: ON PK Fc 100 Hz Q 0\n").is_err());
+}
+
+#[test]
+fn empty_config_passes_audio_through() {
+    let config = EqConfig::default();
+    assert!(config.is_empty());
+    let peaks = steady_state_peaks(&config, 1000.0);
+    for peak in peaks {
+        assert!((peak - 0.5).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn peaking_filter_boosts_only_selected_channel_at_centre_frequency() {
+    let config = parse_eq_config("Channel: C\nFilter: ON PK Fc 1000 Hz Gain 6 dB Q 1\n")
+        .expect("should parse");
+    let peaks = steady_state_peaks(&config, 1000.0);
+    // +6 dB ~= x2.
+    assert!((peaks[2] - 1.0).abs() < 0.02, "FC peak {}", peaks[2]);
+    assert!((peaks[0] - 0.5).abs() < 1e-3, "FL peak {}", peaks[0]);
+}
+
+#[test]
+fn low_pass_attenuates_above_cutoff() {
+    let config = parse_eq_config("Channel: SUB\nFilter: ON LP Fc 120 Hz\n").expect("should parse");
+    let peaks = steady_state_peaks(&config, 2000.0);
+    assert!(peaks[3] < 0.01, "LFE peak above cutoff {}", peaks[3]);
+}
+
+#[test]
+fn high_pass_attenuates_below_cutoff() {
+    let config = parse_eq_config("Filter: ON HP Fc 1000 Hz\n").expect("should parse");
+    let peaks = steady_state_peaks(&config, 50.0);
+    assert!(peaks[0] < 0.01, "FL peak below cutoff {}", peaks[0]);
+}
+
+#[test]
+fn shelves_apply_gain_on_their_side_only() {
+    let config = parse_eq_config("Filter: ON LS Fc 200 Hz Gain -6 dB\n").expect("should parse");
+    let low = steady_state_peaks(&config, 40.0);
+    let high = steady_state_peaks(&config, 5000.0);
+    assert!(
+        (low[0] - 0.25).abs() < 0.02,
+        "low-shelf low band {}",
+        low[0]
+    );
+    assert!(
+        (high[0] - 0.5).abs() < 0.01,
+        "low-shelf high band {}",
+        high[0]
+    );
+
+    let config = parse_eq_config("Filter: ON HS Fc 2000 Hz Gain 6 dB\n").expect("should parse");
+    let high = steady_state_peaks(&config, 15000.0);
+    assert!(
+        (high[0] - 1.0).abs() < 0.03,
+        "high-shelf high band {}",
+        high[0]
+    );
+}
@@ -0,0 +1,529 @@
+use pw_ac3_live::graph::{
+    alsa_card_of, card_name_for_sink, is_auto_target, is_default_target, output_profile_for_sink,
+    parse_iec958_codecs, pick_ac3_target, pick_profile, DeviceProfile, Graph, GraphObject,
+    ObjectKind, PortPair, ProfileRequest, TargetIdentity,
+};
+use pw_ac3_live::volume_guard::NodeVolume;
+
+fn sink(id: u32, props: &[(&str, &str)]) -> GraphObject {
+    let mut props = props.to_vec();
+    props.push(("media.class", "Audio/Sink"));
+    GraphObject::new(id, ObjectKind::Node, props)
+}
+
+#[test]
+fn object_kind_follows_registry_type_names() {
+    assert_eq!(
+        ObjectKind::from_type_name("PipeWire:Interface:Node"),
+        ObjectKind::Node
+    );
+    assert_eq!(
+        ObjectKind::from_type_name("PipeWire:Interface:Link"),
+        ObjectKind::Link
+    );
+    assert_eq!(
+        ObjectKind::from_type_name("PipeWire:Interface:Client"),
+        ObjectKind::Other
+    );
+}
+
+#[test]
+fn iec958_codecs_parse_spa_json_lists() {
+    assert_eq!(
+        parse_iec958_codecs("[ PCM AC3 DTS ]"),
+        ["PCM", "AC3", "DTS"]
+    );
+    assert_eq!(
+        parse_iec958_codecs("[\"pcm\",\"ac3\",\"ac3\"]"),
+        ["PCM", "AC3"]
+    );
+    assert!(parse_iec958_codecs("[ ]").is_empty());
+}
+
+#[test]
+fn auto_target_is_case_insensitive() {
+    assert!(is_auto_target("auto"));
+    assert!(is_auto_target(" AUTO "));
+    assert!(!is_auto_target("alsa_output.auto"));
+}
+
+#[test]
+fn sink_candidates_merge_properties_and_enum_formats() {
+    let mut graph = Graph::new();
+    graph.insert(sink(
+        40,
+        &[
+            ("node.name", "alsa_output.pci-0000_00_1f.3.analog-stereo"),
+            ("node.description", "Built-in Audio Analog Stereo"),
+        ],
+    ));
+    graph.insert(sink(
+        41,
+        &[
+            ("node.name", "alsa_output.pci-0000_00_1f.3.hdmi-stereo"),
+            ("iec958.codecs", "[ PCM ]"),
+        ],
+    ));
+    graph.insert(sink(42, &[("node.name", "pw-ac3-live-input")]));
+    graph.insert(GraphObject::new(
+        43,
+        ObjectKind::Node,
+        [("media.class", "Audio/Source"), ("node.name", "hdmi-in")],
+    ));
+    graph.add_enum_codecs(41, &["PCM".to_string(), "AC3".to_string()]);
+
+    let candidates = graph.sink_candidates();
+    assert_eq!(candidates.len(), 2);
+    assert!(!candidates[0].digital);
+    assert_eq!(candidates[0].capabilities(), "analog, codecs unknown");
+    assert!(candidates[1].digital);
+    assert!(candidates[1].supports_ac3());
+    assert_eq!(candidates[1].capabilities(), "digital, codecs PCM AC3");
+
+    graph.remove(41);
+    graph.insert(sink(
+        41,
+        &[("node.name", "alsa_output.pci-0000_00_1f.3.hdmi-stereo")],
+    ));
+    assert!(!graph.sink_candidates()[1].supports_ac3());
+}
+
+#[test]
+fn ac3_capable_digital_sink_is_preferred() {
+    let mut graph = Graph::new();
+    graph.insert(sink(
+        50,
+        &[
+            ("node.name", "alsa_output.analog-stereo"),
+            ("iec958.codecs", "[ PCM AC3 ]"),
+        ],
+    ));
+    graph.insert(sink(51, &[("node.name", "alsa_output.hdmi-stereo")]));
+    graph.insert(sink(
+        52,
+        &[
+            ("node.name", "alsa_output.hdmi-stereo-extra1"),
+            ("iec958.codecs", "[ PCM AC3 ]"),
+        ],
+    ));
+    graph.insert(sink(
+        53,
+        &[
+            ("node.name", "alsa_output.usb"),
+            ("api.alsa.path", "iec958:1"),
+            ("iec958.codecs", "[ PCM AC3 DTS ]"),
+        ],
+    ));
+
+    let candidates = graph.sink_candidates();
+    assert_eq!(pick_ac3_target(&candidates).map(|c| c.id), Some(52));
+
+    graph.remove(52);
+    graph.remove(53);
+    let candidates = graph.sink_candidates();
+    assert_eq!(pick_ac3_target(&candidates).map(|c| c.id), Some(51));
+
+    graph.remove(51);
+    assert!(pick_ac3_target(&graph.sink_candidates()).is_none());
+}
+
+fn port(id: u32, node: u32, direction: &str, channel: &str) -> GraphObject {
+    GraphObject::new(
+        id,
+        ObjectKind::Port,
+        [
+            ("node.id", node.to_string()),
+            ("port.direction", direction.to_string()),
+            ("audio.channel", channel.to_string()),
+        ],
+    )
+}
+
+fn link(
+    id: u32,
+    (output_node, output_port): (u32, u32),
+    (input_node, input_port): (u32, u32),
+) -> GraphObject {
+    GraphObject::new(
+        id,
+        ObjectKind::Link,
+        [
+            ("link.output.node", output_node.to_string()),
+            ("link.output.port", output_port.to_string()),
+            ("link.input.node", input_node.to_string()),
+            ("link.input.port", input_port.to_string()),
+        ],
+    )
+}
+
+/// Playback node 10 (ports 11/12), HDMI sink 20 (ports 21/22, monitors 23/24),
+/// recorder 30 (ports 31/32).
+fn linked_graph() -> Graph {
+    let mut graph = Graph::new();
+    graph.insert(GraphObject::new(
+        10,
+        ObjectKind::Node,
+        [("node.name", "pw-ac3-live-output")],
+    ));
+    graph.insert(sink(20, &[("node.name", "alsa_output.hdmi-stereo")]));
+    graph.insert(GraphObject::new(
+        30,
+        ObjectKind::Node,
+        [("node.name", "pw-record")],
+    ));
+    for (id, node, direction, channel) in [
+        (11, 10, "out", "FL"),
+        (12, 10, "out", "FR"),
+        (21, 20, "in", "FL"),
+        (22, 20, "in", "FR"),
+        (23, 20, "out", "FL"),
+        (24, 20, "out", "FR"),
+        (31, 30, "in", "FL"),
+        (32, 30, "in", "FR"),
+    ] {
+        graph.insert(port(id, node, direction, channel));
+    }
+    graph
+}
+
+const FL: PortPair = PortPair {
+    output_port: 11,
+    input_port: 21,
+};
+const FR: PortPair = PortPair {
+    output_port: 12,
+    input_port: 22,
+};
+
+#[test]
+fn targets_resolve_by_name_or_node_id() {
+    let graph = linked_graph();
+    assert_eq!(graph.resolve_node("alsa_output.hdmi-stereo"), Some(20));
+    assert_eq!(graph.resolve_node(" 20 "), Some(20));
+    assert_eq!(graph.resolve_node("21"), None);
+    assert_eq!(graph.resolve_node("missing"), None);
+}
+
+#[test]
+fn link_plan_pairs_same_channel_ports() {
+    let mut graph = linked_graph();
+    assert_eq!(graph.plan_links(10, 20, &["FL", "FR"]), Some(vec![FL, FR]));
+
+    graph.remove(22);
+    assert_eq!(graph.plan_links(10, 20, &["FL", "FR"]), None);
+}
+
+#[test]
+fn reconcile_creates_missing_links_once() {
+    let mut graph = linked_graph();
+    let plan = [FL, FR];
+
+    let actions = graph.reconcile_links(10, 20, &plan, &[]);
+    assert_eq!(actions.create, plan);
+    assert!(!actions.verified);
+
+    // Created but not yet announced by the registry.
+    let actions = graph.reconcile_links(10, 20, &plan, &plan);
+    assert!(actions.create.is_empty());
+    assert!(actions.destroy.is_empty());
+    assert!(!actions.verified);
+
+    graph.insert(link(40, (10, 11), (20, 21)));
+    graph.insert(link(41, (10, 12), (20, 22)));
+    assert!(graph.reconcile_links(10, 20, &plan, &plan).verified);
+}
+
+#[test]
+fn reconcile_removes_crossed_links_but_keeps_recorders() {
+    let mut graph = linked_graph();
+    let plan = [FL, FR];
+    graph.insert(link(40, (10, 11), (20, 21)));
+    graph.insert(link(41, (10, 12), (20, 22)));
+    graph.insert(link(42, (10, 11), (20, 22)));
+    graph.insert(link(43, (10, 11), (30, 31)));
+
+    let actions = graph.reconcile_links(10, 20, &plan, &plan);
+    assert!(actions.create.is_empty());
+    assert_eq!(actions.destroy, [42]);
+    assert!(!actions.verified);
+
+    graph.remove(42);
+    assert!(graph.reconcile_links(10, 20, &plan, &plan).verified);
+}
+
+#[test]
+fn reconcile_drops_owned_links_outside_the_plan() {
+    let mut graph = linked_graph();
+    let old = PortPair {
+        output_port: 11,
+        input_port: 99,
+    };
+    graph.insert(link(40, (10, 11), (20, 99)));
+
+    let actions = graph.reconcile_links(10, 20, &[FL, FR], &[old, FL]);
+    assert_eq!(actions.create, [FR]);
+    assert_eq!(actions.drop_owned, [old]);
+    assert!(actions.destroy.is_empty());
+}
+
+fn stream(id: u32, props: &[(&str, &str)]) -> GraphObject {
+    let mut props = props.to_vec();
+    props.push(("media.class", "Stream/Output/Audio"));
+    GraphObject::new(id, ObjectKind::Node, props)
+}
+
+#[test]
+fn metadata_entries_follow_property_events() {
+    let mut graph = Graph::new();
+    graph.set_metadata(
+        0,
+        Some("default.audio.sink"),
+        Some("{ \"name\": \"hdmi\" }"),
+    );
+    graph.set_metadata(60, Some("target.object"), Some("42"));
+    assert_eq!(
+        graph.metadata(0, "default.audio.sink"),
+        Some("{ \"name\": \"hdmi\" }")
+    );
+
+    graph.set_metadata(0, Some("default.audio.sink"), None);
+    assert_eq!(graph.metadata(0, "default.audio.sink"), None);
+
+    graph.insert(stream(60, &[]));
+    graph.remove(60);
+    assert_eq!(graph.metadata(60, "target.object"), None);
+
+    graph.set_metadata(0, Some("a"), Some("1"));
+    graph.set_metadata(0, None, None);
+    assert_eq!(graph.metadata(0, "a"), None);
+}
+
+#[test]
+fn default_target_follows_metadata_but_skips_our_sink() {
+    assert!(is_default_target(" Default "));
+    assert!(!is_default_target("alsa_output.default"));
+
+    let mut graph = Graph::new();
+    graph.insert(sink(40, &[("node.name", "hdmi-tv")]));
+    graph.insert(sink(41, &[("node.name", "pw-ac3-live-input")]));
+    graph.insert(stream(42, &[("node.name", "mpv")]));
+    assert_eq!(graph.default_sink_target(), None);
+
+    let set_default = |graph: &mut Graph, name: &str| {
+        let value = format!("{{ \"name\": \"{name}\" }}");
+        graph.set_metadata(0, Some("default.audio.sink"), Some(&value));
+    };
+    set_default(&mut graph, "hdmi-tv");
+    assert_eq!(graph.default_sink_target().as_deref(), Some("hdmi-tv"));
+    set_default(&mut graph, "pw-ac3-live-input");
+    assert_eq!(graph.default_sink_target(), None);
+    set_default(&mut graph, "mpv");
+    assert_eq!(graph.default_sink_target(), None);
+    set_default(&mut graph, "unplugged");
+    assert_eq!(graph.default_sink_target(), None);
+}
+
+#[test]
+fn only_unpinned_foreign_streams_are_moved() {
+    let mut graph = linked_graph();
+    graph.insert(stream(60, &[("application.name", "Firefox")]));
+    graph.insert(stream(
+        61,
+        &[("node.name", "mpv"), ("target.object", "hdmi")],
+    ));
+    graph.insert(stream(62, &[("node.name", "pw-ac3-live-output")]));
+    graph.insert(stream(63, &[("application.name", "pw-ac3-live")]));
+    graph.insert(stream(64, &[("node.name", "game")]));
+    graph.insert(GraphObject::new(
+        65,
+        ObjectKind::Node,
+        [("media.class", "Stream/Input/Audio")],
+    ));
+
+    assert_eq!(graph.streams_to_move(&[]), [60, 64]);
+    assert_eq!(graph.streams_to_move(&[60]), [64]);
+}
+
+fn profile(index: i32, name: &str, priority: i32, available: bool) -> DeviceProfile {
+    DeviceProfile {
+        index,
+        name: name.to_string(),
+        description: name.to_string(),
+        priority,
+        available,
+    }
+}
+
+#[test]
+fn profile_pick_prefers_active_then_priority() {
+    let profiles = [
+        profile(0, "off", 0, true),
+        profile(1, "output:analog-stereo+input:analog-stereo", 6500, true),
+        profile(2, "output:hdmi-stereo+input:analog-stereo", 5900, true),
+        profile(3, "output:hdmi-stereo", 5400, true),
+        profile(4, "output:hdmi-stereo-extra1", 5600, false),
+    ];
+    let hdmi = ProfileRequest::Output("hdmi-stereo".to_string());
+
+    assert_eq!(
+        pick_profile(&profiles, Some(&profiles[1]), &hdmi),
+        Some(&profiles[2])
+    );
+    assert_eq!(
+        pick_profile(&profiles, Some(&profiles[3]), &hdmi),
+        Some(&profiles[3])
+    );
+    assert_eq!(
+        pick_profile(&profiles, Some(&profiles[2]), &ProfileRequest::Off),
+        Some(&profiles[0])
+    );
+    let extra1 = ProfileRequest::Output("hdmi-stereo-extra1".to_string());
+    assert_eq!(pick_profile(&profiles, None, &extra1), None);
+}
+
+#[test]
+fn sink_and_pcm_names_map_to_cards() {
+    assert_eq!(
+        card_name_for_sink("alsa_output.pci-0000_04_00.1.hdmi-stereo-extra2").as_deref(),
+        Some("alsa_card.pci-0000_04_00.1")
+    );
+    assert_eq!(
+        output_profile_for_sink("alsa_output.pci-0000_04_00.1.hdmi-stereo-extra2").as_deref(),
+        Some("hdmi-stereo-extra2")
+    );
+    assert_eq!(card_name_for_sink("pw-ac3-live-input"), None);
+
+    assert_eq!(alsa_card_of("hw:0,8").as_deref(), Some("0"));
+    assert_eq!(alsa_card_of("hw:CARD=PCH,DEV=3").as_deref(), Some("PCH"));
+    assert_eq!(alsa_card_of("default"), None);
+}
+
+#[test]
+fn devices_resolve_from_sinks_and_alsa_cards() {
+    let mut graph = Graph::new();
+    graph.insert(GraphObject::new(
+        5,
+        ObjectKind::Device,
+        [
+            ("device.name", "alsa_card.pci-0000_04_00.1"),
+            ("api.alsa.card", "0"),
+        ],
+    ));
+    graph.insert(sink(
+        50,
+        &[
+            ("node.name", "alsa_output.pci-0000_04_00.1.hdmi-stereo"),
+            ("device.id", "5"),
+        ],
+    ));
+    graph.add_device_profile(5, profile(0, "off", 0, true));
+    graph.add_device_profile(5, profile(0, "off", 0, true));
+    graph.set_active_profile(5, profile(0, "off", 0, true));
+
+    assert_eq!(graph.device_for_sink("50"), Some(5));
+    // Sink missing while the card profile is off.
+    assert_eq!(
+        graph.device_for_sink("alsa_output.pci-0000_04_00.1.hdmi-stereo-extra2"),
+        Some(5)
+    );
+    assert_eq!(graph.device_for_alsa_card("0"), Some(5));
+    assert_eq!(graph.device_for_alsa_card("1"), None);
+    assert_eq!(graph.device_profiles(5).len(), 1);
+
+    graph.remove(5);
+    assert!(graph.device_profiles(5).is_empty());
+    assert!(graph.active_profile(5).is_none());
+}
+
+#[test]
+fn linked_sinks_and_their_volumes_are_tracked() {
+    let mut graph = linked_graph();
+    graph.insert(link(40, (10, 11), (20, 21)));
+    graph.insert(link(41, (10, 12), (20, 22)));
+    graph.insert(link(42, (10, 11), (30, 31)));
+    assert_eq!(graph.linked_sinks(10), [20]);
+    assert!(graph.linked_sinks(20).is_empty());
+
+    graph.update_node_volume(
+        20,
+        NodeVolume {
+            volume: Some(0.5),
+            ..NodeVolume::default()
+        },
+    );
+    graph.update_node_volume(
+        20,
+        NodeVolume {
+            mute: Some(false),
+            ..NodeVolume::default()
+        },
+    );
+    let volume = graph.node_volume(20).expect("volume");
+    assert_eq!((volume.volume, volume.mute), (Some(0.5), Some(false)));
+
+    graph.remove(20);
+    assert!(graph.node_volume(20).is_none());
+}
+
+#[test]
+fn replugged_target_is_found_by_name_or_object_path() {
+    let mut graph = Graph::new();
+    graph.insert(sink(
+        70,
+        &[
+            ("node.name", "alsa_output.hdmi-stereo"),
+            ("object.path", "alsa:pcm:0:hw:0,3:playback"),
+        ],
+    ));
+    let identity = graph.target_identity(70).expect("identity");
+    assert_eq!(
+        identity,
+        TargetIdentity {
+            node_name: Some("alsa_output.hdmi-stereo".to_string()),
+            object_path: Some("alsa:pcm:0:hw:0,3:playback".to_string()),
+        }
+    );
+
+    graph.remove(70);
+    assert_eq!(graph.find_target(&identity), None);
+
+    graph.insert(sink(
+        85,
+        &[
+            ("node.name", "alsa_output.hdmi-stereo-extra1"),
+            ("object.path", "alsa:pcm:0:hw:0,3:playback"),
+        ],
+    ));
+    assert_eq!(graph.find_target(&identity), Some(85));
+    graph.insert(sink(86, &[("node.name", "alsa_output.hdmi-stereo")]));
+    assert_eq!(graph.find_target(&identity), Some(86));
+    assert_eq!(graph.target_identity(99), None);
+}
+
+#[test]
+fn routes_back_into_our_input_are_found() {
+    let mut graph = linked_graph();
+    graph.insert(sink(40, &[("node.name", "pw-ac3-live-input")]));
+    graph.insert(GraphObject::new(
+        50,
+        ObjectKind::Node,
+        [("node.name", "loopback")],
+    ));
+    graph.insert(link(60, (10, 11), (20, 21)));
+    graph.insert(link(61, (10, 12), (30, 32)));
+    graph.insert(link(62, (20, 23), (50, 51)));
+    assert_eq!(graph.route_path(10, 40), None);
+    assert!(graph.feedback_links(10, 40).is_empty());
+
+    graph.insert(link(63, (50, 52), (40, 41)));
+    assert_eq!(graph.route_path(10, 40), Some(vec![10, 20, 50, 40]));
+    assert_eq!(graph.route_path(20, 50), Some(vec![20, 50]));
+    assert_eq!(graph.feedback_links(10, 40), [60]);
+
+    graph.insert(link(64, (10, 12), (40, 42)));
+    assert_eq!(graph.feedback_links(10, 40), [60, 64]);
+    assert_eq!(graph.route_path(10, 40), Some(vec![10, 40]));
+
+    graph.remove(63);
+    assert_eq!(graph.feedback_links(10, 40), [64]);
+}
@@ -0,0 +1,201 @@
+use pw_ac3_live::iec61937::{
+    can_transcode, data_type_name, find_preamble, needs_transcoding, pause_burst, pause_gap,
+    payload_words, sample_to_word, BurstDetector, DATA_TYPE_AC3, DATA_TYPE_DTS_HD, DATA_TYPE_DTS_I,
+    DATA_TYPE_DTS_III, DATA_TYPE_EAC3, DATA_TYPE_MAT, DATA_TYPE_PAUSE, FRAME_BYTES,
+    PASSTHROUGH_TIMEOUT_FRAMES, PAUSE_BURST_FRAMES, PREAMBLE_PA, PREAMBLE_PB,
+};
+
+fn words(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|word| u16::from_le_bytes([word[0], word[1]]))
+        .collect()
+}
+
+#[test]
+fn pause_burst_has_preamble_type_and_gap_length() {
+    let burst = pause_burst(480);
+    assert_eq!(burst.len(), PAUSE_BURST_FRAMES * FRAME_BYTES);
+    assert_eq!(&burst[..4], &[0x72, 0xf8, 0x1f, 0x4e]);
+    assert_eq!(
+        words(&burst),
+        vec![0xf872, 0x4e1f, DATA_TYPE_PAUSE, 32, 480, 0]
+    );
+
+    // The gap length saturates instead of wrapping.
+    assert_eq!(words(&pause_burst(1_000_000))[4], u16::MAX);
+}
+
+#[test]
+fn pause_gap_covers_the_requested_duration() {
+    // 32 ms at 48 kHz is 1536 frames, i.e. 512 bursts.
+    let gap = pause_gap(48_000, 32);
+    assert_eq!(gap.len(), 1536 * FRAME_BYTES);
+    let first = &gap[..PAUSE_BURST_FRAMES * FRAME_BYTES];
+    assert!(gap
+        .chunks_exact(PAUSE_BURST_FRAMES * FRAME_BYTES)
+        .all(|burst| burst == first));
+    assert_eq!(words(first)[4], 1536);
+
+    // 44.1 kHz rounds up to whole bursts.
+    assert_eq!(
+        pause_gap(44_100, 32).len() % (PAUSE_BURST_FRAMES * FRAME_BYTES),
+        0
+    );
+    assert!(pause_gap(44_100, 32).len() >= 1411 * FRAME_BYTES);
+}
+
+#[test]
+fn find_preamble_only_matches_aligned_words() {
+    let burst = pause_burst(3);
+    let mut data = vec![0u8; 6];
+    data.extend_from_slice(&burst);
+    assert_eq!(find_preamble(&data), Some(6));
+
+    let mut misaligned = vec![0u8; 5];
+    misaligned.extend_from_slice(&burst);
+    assert_eq!(find_preamble(&misaligned), None);
+    assert_eq!(find_preamble(&[0x72, 0xf8]), None);
+}
+
+/// Interleaved 5.1 as the capture stream delivers it: the S16 `words` of a 2ch
+/// stream converted to float on FL/FR, the other channels silent.
+fn capture_frames(words: &[u16]) -> Vec<f32> {
+    words
+        .chunks_exact(2)
+        .flat_map(|pair| {
+            [
+                f32::from(pair[0] as i16) / 32768.0,
+                f32::from(pair[1] as i16) / 32768.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn sample_to_word_recovers_s16_words() {
+    for word in [0u16, 1, PREAMBLE_PA, PREAMBLE_PB, 0x7fff, 0x8000, 0xffff] {
+        assert_eq!(sample_to_word(f32::from(word as i16) / 32768.0), word);
+    }
+    assert_eq!(sample_to_word(2.0), 0x7fff);
+    assert_eq!(sample_to_word(-2.0), 0x8000);
+}
+
+/// One burst of `frames` 2ch frames: preamble, Pc, Pd, `payload`, zero stuffing.
+fn burst_words(data_type: u16, pd: u16, payload: &[u16], frames: usize) -> Vec<u16> {
+    let mut words = vec![0u16; frames * 2];
+    words[..4].copy_from_slice(&[PREAMBLE_PA, PREAMBLE_PB, data_type, pd]);
+    words[4..4 + payload.len()].copy_from_slice(payload);
+    words
+}
+
+#[test]
+fn burst_detector_validates_bursts_across_chunks() {
+    let mut detector = BurstDetector::new();
+    assert!(!detector.scan(&capture_frames(&[0x1234, 0x4321, 0, 0]), 6));
+
+    // 64 bits of AC-3 payload, then zero stuffing up to 1536 frames.
+    let ac3 = burst_words(DATA_TYPE_AC3, 64, &[0xbeef, 0xcafe, 0x1234, 0x5678], 1536);
+    // Split inside the header and inside the payload.
+    assert!(!detector.scan(&capture_frames(&ac3[..2]), 6));
+    assert!(!detector.scan(&capture_frames(&ac3[2..6]), 6));
+    assert!(!detector.scan(&capture_frames(&ac3[6..]), 6));
+    assert_eq!(detector.data_type(), None);
+
+    // The next preamble right after clean stuffing confirms the first burst.
+    assert!(detector.scan(&capture_frames(&ac3[..4]), 6));
+    assert_eq!(detector.data_type(), Some(DATA_TYPE_AC3));
+
+    // Pc carries extra bits (bitstream number, error flag) above the type.
+    let mut detector = BurstDetector::new();
+    let eac3 = burst_words(0x0115, 0x1000, &[], 6144);
+    detector.scan(&capture_frames(&eac3), 6);
+    detector.scan(&capture_frames(&eac3[..4]), 6);
+    assert_eq!(detector.data_type(), Some(0x15));
+}
+
+#[test]
+fn burst_detector_ignores_preambles_in_pcm() {
+    // Loud PCM that happens to hit Pa/Pb once, followed by more audio.
+    let mut words: Vec<u16> = (0..4096u16).map(|i| i.wrapping_mul(7919)).collect();
+    words[100..104].copy_from_slice(&[PREAMBLE_PA, PREAMBLE_PB, DATA_TYPE_AC3, 64]);
+    words[400..402].copy_from_slice(&[PREAMBLE_PA, PREAMBLE_PB]);
+    let mut detector = BurstDetector::new();
+    assert!(!detector.scan(&capture_frames(&words), 6));
+
+    // A Pd longer than the repetition period is not a burst either.
+    let mut detector = BurstDetector::new();
+    let too_long = burst_words(DATA_TYPE_AC3, 1536 * 32, &[], 1536);
+    detector.scan(&capture_frames(&too_long), 6);
+    assert!(!detector.scan(&capture_frames(&too_long), 6));
+
+    // Nor is a burst whose stuffing carries data.
+    let mut detector = BurstDetector::new();
+    let mut dirty = burst_words(DATA_TYPE_AC3, 64, &[1, 2, 3, 4], 1536);
+    dirty[1000] = 0x0100;
+    detector.scan(&capture_frames(&dirty), 6);
+    assert!(!detector.scan(&capture_frames(&dirty), 6));
+}
+
+#[test]
+fn payload_length_units_follow_the_data_type() {
+    // AC-3 and DTS count bits, E-AC-3 and MAT count bytes.
+    assert_eq!(payload_words(DATA_TYPE_AC3, 64), Some(4));
+    assert_eq!(payload_words(DATA_TYPE_DTS_I, 17), Some(2));
+    assert_eq!(payload_words(DATA_TYPE_EAC3, 64), Some(32));
+    assert_eq!(payload_words(DATA_TYPE_MAT, 3), Some(2));
+    assert_eq!(payload_words(DATA_TYPE_PAUSE, 32), Some(2));
+    assert_eq!(payload_words(DATA_TYPE_AC3, 1536 * 32), None);
+    assert_eq!(payload_words(DATA_TYPE_EAC3, 6144 * 4), None);
+}
+
+#[test]
+fn burst_detector_returns_to_pcm_after_timeout() {
+    let mut detector = BurstDetector::new();
+    let ac3 = burst_words(DATA_TYPE_AC3, 64, &[1, 2, 3, 4], 1536);
+    detector.scan(&capture_frames(&ac3), 6);
+    assert!(detector.scan(&capture_frames(&ac3), 6));
+
+    let silence = vec![0.0f32; (PASSTHROUGH_TIMEOUT_FRAMES / 2) * 6];
+    assert!(detector.scan(&silence, 6));
+    assert!(!detector.scan(&silence, 6));
+    assert!(!detector.is_active());
+
+    // A preamble needs the exact words on FL and FR; scaled audio does not match.
+    let quiet: Vec<f32> = capture_frames(&[ac3.clone(), ac3].concat())
+        .iter()
+        .map(|sample| sample * 0.5)
+        .collect();
+    assert!(!detector.scan(&quiet, 6));
+}
+
+#[test]
+fn only_non_ac3_bursts_need_transcoding() {
+    assert!(!needs_transcoding(DATA_TYPE_AC3));
+    assert!(!needs_transcoding(DATA_TYPE_PAUSE));
+    for data_type in [DATA_TYPE_EAC3, DATA_TYPE_DTS_I, DATA_TYPE_MAT] {
+        assert!(needs_transcoding(data_type));
+    }
+    assert_eq!(data_type_name(DATA_TYPE_EAC3), "E-AC-3");
+    assert_eq!(data_type_name(DATA_TYPE_DTS_I), "DTS");
+    assert_eq!(data_type_name(DATA_TYPE_MAT), "TrueHD");
+
+    // TrueHD and DTS-HD need a high-bit-rate carrier ffmpeg cannot read from FL/FR.
+    assert!(can_transcode(DATA_TYPE_EAC3) && can_transcode(DATA_TYPE_DTS_III));
+    assert!(!can_transcode(DATA_TYPE_MAT) && !can_transcode(DATA_TYPE_DTS_HD));
+}
+
+#[test]
+fn burst_detector_keeps_codec_across_pause_bursts() {
+    let mut detector = BurstDetector::new();
+    let eac3 = burst_words(DATA_TYPE_EAC3, 0x4000, &[], 6144);
+    let pause = burst_words(DATA_TYPE_PAUSE, 32, &[6, 0], PAUSE_BURST_FRAMES);
+    detector.scan(&capture_frames(&eac3), 6);
+    detector.scan(&capture_frames(&pause), 6);
+    assert!(detector.scan(&capture_frames(&pause), 6));
+    assert_eq!(detector.data_type(), Some(DATA_TYPE_EAC3));
+}
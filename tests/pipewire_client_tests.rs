@@ -1,3 +1,8 @@
+use pw_ac3_live::default_sink;
+use pw_ac3_live::encoder;
+use pw_ac3_live::graph;
+use pw_ac3_live::volume_guard;
+
 mod pipewire_client_impl {
     #![allow(dead_code)]
 
@@ -31,9 +36,19 @@ mod pipewire_client_impl {
             offset: usize,
             size: usize,
             stride: usize,
+            format: SampleFormat,
         ) -> Option<Vec<f32>> {
             let mut samples = Vec::new();
-            parse_interleaved_from_stride_into(raw_data, offset, size, stride, &mut samples)?;
+            parse_interleaved_from_stride_into(
+                raw_data,
+                offset,
+                size,
+                stride,
+                format,
+                &ChannelMap::identity(INPUT_CHANNELS),
+                INPUT_CHANNELS,
+                &mut samples,
+            )?;
             Some(samples)
         }
 
@@ -97,7 +112,8 @@ mod pipewire_client_impl {
             }
 
             let parsed =
-                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 8).expect("should parse");
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 8, SampleFormat::F32Le)
+                    .expect("should parse");
             assert_eq!(parsed.len(), 12);
             assert_eq!(parsed[0], 1.0);
             assert_eq!(parsed[1], -1.0);
@@ -114,7 +130,8 @@ mod pipewire_client_impl {
             }
 
             let parsed =
-                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 4).expect("should parse");
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 4, SampleFormat::S16Le)
+                    .expect("should parse");
             assert_eq!(parsed.len(), 12);
             assert!(parsed[0].is_finite());
             assert!(parsed[1].is_finite());
@@ -129,7 +146,7 @@ mod pipewire_client_impl {
             assert_eq!(target.connect_target_id, Some(42));
             assert_eq!(target.target_object.as_deref(), Some("42"));
 
-            let props = build_playback_properties(&target);
+            let props = build_playback_properties(&target, SAMPLE_RATE_HZ);
             assert_eq!(props.get("target.object"), Some("42"));
             assert_eq!(props.get("node.autoconnect"), Some("false"));
         }
@@ -143,7 +160,7 @@ mod pipewire_client_impl {
                 Some("alsa_output.pci-0000_00_1f.3.hdmi-stereo")
             );
 
-            let props = build_playback_properties(&target);
+            let props = build_playback_properties(&target, SAMPLE_RATE_HZ);
             assert_eq!(
                 props.get("target.object"),
                 Some("alsa_output.pci-0000_00_1f.3.hdmi-stereo")
@@ -157,7 +174,7 @@ mod pipewire_client_impl {
             assert_eq!(target.connect_target_id, None);
             assert_eq!(target.target_object, None);
 
-            let props = build_playback_properties(&target);
+            let props = build_playback_properties(&target, SAMPLE_RATE_HZ);
             assert_eq!(props.get("target.object"), None);
             assert_eq!(props.get("node.autoconnect"), Some("true"));
         }
@@ -200,8 +217,9 @@ mod pipewire_client_impl {
                     bytes.extend_from_slice(&val.to_le_bytes());
                 }
             }
-            let parsed = parse_interleaved_from_stride(&bytes, 0, bytes.len(), 24)
-                .expect("6ch f32 should parse");
+            let parsed =
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 24, SampleFormat::F32Le)
+                    .expect("6ch f32 should parse");
             // 2 frames × 6 channels = 12 samples, no zero-padding needed.
             assert_eq!(parsed.len(), 12);
             assert_eq!(parsed[0], 0.0); // frame0 ch0
@@ -217,8 +235,9 @@ mod pipewire_client_impl {
             for val in [0.5f32, -0.5f32] {
                 bytes.extend_from_slice(&val.to_le_bytes());
             }
-            let parsed = parse_interleaved_from_stride(&bytes, 0, bytes.len(), 4)
-                .expect("mono f32 should parse");
+            let parsed =
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 4, SampleFormat::F32Le)
+                    .expect("mono f32 should parse");
             // 2 frames × 6 channels = 12 samples
             assert_eq!(parsed.len(), 12);
             assert_eq!(parsed[0], 0.5); // frame0 ch0
@@ -235,8 +254,9 @@ mod pipewire_client_impl {
             for val in [i16::MAX, 0i16, i16::MIN, 1000i16, -1000i16, 500i16] {
                 bytes.extend_from_slice(&val.to_le_bytes());
             }
-            let parsed = parse_interleaved_from_stride(&bytes, 0, bytes.len(), 12)
-                .expect("6ch s16 should parse");
+            let parsed =
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 12, SampleFormat::S16Le)
+                    .expect("6ch s16 should parse");
             assert_eq!(parsed.len(), 6);
             // All values should be finite floats in [-1.0, 1.0)
             for sample in &parsed {
@@ -252,8 +272,9 @@ mod pipewire_client_impl {
             for val in [16000i16, -16000i16] {
                 bytes.extend_from_slice(&val.to_le_bytes());
             }
-            let parsed = parse_interleaved_from_stride(&bytes, 0, bytes.len(), 2)
-                .expect("mono s16 should parse");
+            let parsed =
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 2, SampleFormat::S16Le)
+                    .expect("mono s16 should parse");
             assert_eq!(parsed.len(), 12);
             assert!(parsed[0].is_finite() && parsed[0] > 0.0);
             assert_eq!(parsed[1], 0.0); // padded
@@ -266,27 +287,488 @@ mod pipewire_client_impl {
         fn stride_invalid_size_returns_none() {
             // Stride of 3 is not a multiple of sizeof(f32) or sizeof(i16).
             let bytes = vec![0u8; 12];
-            assert!(parse_interleaved_from_stride(&bytes, 0, bytes.len(), 3).is_none());
+            assert!(
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 3, SampleFormat::F32Le)
+                    .is_none()
+            );
         }
 
         #[test]
         fn stride_zero_returns_none() {
             let bytes = vec![0u8; 8];
-            assert!(parse_interleaved_from_stride(&bytes, 0, bytes.len(), 0).is_none());
+            assert!(
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 0, SampleFormat::F32Le)
+                    .is_none()
+            );
         }
 
         #[test]
         fn stride_too_few_bytes_returns_none() {
             // Data shorter than one stride.
             let bytes = vec![0u8; 3];
-            assert!(parse_interleaved_from_stride(&bytes, 0, bytes.len(), 8).is_none());
+            assert!(
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 8, SampleFormat::F32Le)
+                    .is_none()
+            );
         }
 
         #[test]
         fn stride_7ch_f32_exceeds_max_returns_none() {
             // 7-channel f32: stride = 28 bytes. Exceeds INPUT_CHANNELS (6).
             let bytes = vec![0u8; 56]; // 2 frames
-            assert!(parse_interleaved_from_stride(&bytes, 0, bytes.len(), 28).is_none());
+            assert!(
+                parse_interleaved_from_stride(&bytes, 0, bytes.len(), 28, SampleFormat::F32Le)
+                    .is_none()
+            );
+        }
+
+        #[test]
+        fn stride_8ch_f32_parses_for_71_layout() {
+            // 8-channel f32: stride = 32 bytes, accepted when the sink is 7.1.
+            let mut bytes = Vec::new();
+            for val in 0..16u32 {
+                bytes.extend_from_slice(&(val as f32).to_le_bytes());
+            }
+            let mut parsed = Vec::new();
+            parse_interleaved_from_stride_into(
+                &bytes,
+                0,
+                bytes.len(),
+                32,
+                SampleFormat::F32Le,
+                &ChannelMap::identity(MAX_CAPTURE_CHANNELS),
+                MAX_CAPTURE_CHANNELS,
+                &mut parsed,
+            )
+            .expect("8ch f32 should parse");
+            assert_eq!(parsed.len(), 16);
+            assert_eq!(parsed[7], 7.0); // frame0 SR
+            assert_eq!(parsed[15], 15.0); // frame1 SR
+        }
+
+        // ── capture EnumFormat ────────────────────────────────────────
+
+        #[test]
+        fn capture_offers_every_decodable_format_preferred_first() {
+            assert_eq!(
+                "S24-32".parse::<CaptureSampleFormat>(),
+                Ok(CaptureSampleFormat::S24In32)
+            );
+            assert!("u8".parse::<CaptureSampleFormat>().is_err());
+
+            let params =
+                build_capture_format_params(CaptureSampleFormat::S16, 6).expect("capture formats");
+            let formats: Vec<(AudioFormat, u32)> = params
+                .iter()
+                .map(|bytes| {
+                    let pod = pw::spa::pod::Pod::from_bytes(bytes).expect("pod");
+                    let mut info = AudioInfoRaw::new();
+                    info.parse(pod).expect("parse capture format");
+                    (info.format(), info.channels())
+                })
+                .collect();
+            assert_eq!(formats.len(), 19);
+            assert_eq!(formats[0], (AudioFormat::S16LE, 6));
+            assert_eq!(formats[1], (AudioFormat::F32LE, 6));
+            assert_eq!(formats[6], (AudioFormat::S16BE, 6));
+            assert_eq!(formats[12], (AudioFormat::S16P, 6));
+            assert_eq!(formats[18], (AudioFormat::S16LE, 2));
+            for (format, _) in &formats {
+                assert!(SampleFormat::from_audio_format(*format).is_some());
+            }
+
+            // Every format the parsers decode is offered, interleaved or planar.
+            let decodable = (0..0x500)
+                .map(AudioFormat::from_raw)
+                .filter(|format| SampleFormat::from_audio_format(*format).is_some())
+                .collect::<Vec<_>>();
+            assert_eq!(decodable.len(), 18);
+            for format in decodable {
+                assert!(
+                    formats.contains(&(format, 6)),
+                    "{format:?} is decodable but not offered"
+                );
+            }
+        }
+
+        // ── 7.1 input layout ──────────────────────────────────────────
+
+        #[test]
+        fn input_layout_parses_51_and_71() {
+            assert_eq!("5.1".parse::<InputLayout>(), Ok(InputLayout::Surround51));
+            assert_eq!(" 7.1 ".parse::<InputLayout>(), Ok(InputLayout::Surround71));
+            assert!("7.2".parse::<InputLayout>().is_err());
+            assert_eq!(InputLayout::Surround51.channels(), 6);
+            assert_eq!(InputLayout::Surround71.channels(), 8);
+            assert_eq!(
+                InputLayout::Surround71.audio_position(),
+                "FL,FR,FC,LFE,RL,RR,SL,SR"
+            );
+        }
+
+        #[test]
+        fn downmix_71_folds_back_and_side_into_surrounds() {
+            // FL, FR, FC, LFE, RL, RR, SL, SR
+            let input = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8];
+            let downmix = SurroundDownmix {
+                side_gain: 1.0,
+                back_gain: 0.5,
+            };
+            let mut out = vec![9.0; 3];
+            downmix_71_into(&input, &downmix, &mut out);
+
+            assert_eq!(out.len(), INPUT_CHANNELS);
+            assert_eq!(&out[..4], &[0.1, 0.2, 0.3, 0.4]);
+            assert!((out[4] - (0.5 * 0.5 + 0.7)).abs() < 1.0e-6);
+            assert!((out[5] - (0.6 * 0.5 + 0.8)).abs() < 1.0e-6);
+        }
+
+        #[test]
+        fn downmix_71_default_gains_are_minus_3_db() {
+            let downmix = SurroundDownmix::default();
+            // -3 dB
+            assert!((20.0 * downmix.side_gain.log10() + 3.01).abs() < 0.01);
+            assert!((20.0 * downmix.back_gain.log10() + 3.01).abs() < 0.01);
+
+            // Two frames, partial trailing frame ignored.
+            let mut input = vec![0.0f32; 17];
+            input[4] = 1.0; // frame0 RL
+            input[8 + 7] = 1.0; // frame1 SR
+            let mut out = Vec::new();
+            downmix_71_into(&input, &downmix, &mut out);
+            assert_eq!(out.len(), 2 * INPUT_CHANNELS);
+            assert!((out[4] - downmix.back_gain).abs() < 1.0e-6);
+            assert!((out[6 + 5] - downmix.side_gain).abs() < 1.0e-6);
+        }
+
+        // ── channel position mapping ──────────────────────────────────
+
+        use libspa::sys::{
+            SPA_AUDIO_CHANNEL_FC, SPA_AUDIO_CHANNEL_FL, SPA_AUDIO_CHANNEL_FR,
+            SPA_AUDIO_CHANNEL_LFE, SPA_AUDIO_CHANNEL_RL, SPA_AUDIO_CHANNEL_RR,
+            SPA_AUDIO_CHANNEL_SL, SPA_AUDIO_CHANNEL_SR, SPA_AUDIO_CHANNEL_UNKNOWN,
+        };
+
+        fn f32_frames(frames: &[&[f32]]) -> Vec<u8> {
+            frames
+                .iter()
+                .flat_map(|frame| frame.iter())
+                .flat_map(|sample| sample.to_le_bytes())
+                .collect()
+        }
+
+        #[test]
+        fn channel_map_reports_fed_encoder_channels() {
+            let stereo = [SPA_AUDIO_CHANNEL_FL, SPA_AUDIO_CHANNEL_FR];
+            let map = ChannelMap::from_positions(&stereo, InputLayout::Surround51);
+            assert_eq!(map.encoder_mask(InputLayout::Surround51), 0b00_0011);
+
+            let quad = [
+                SPA_AUDIO_CHANNEL_FL,
+                SPA_AUDIO_CHANNEL_FR,
+                SPA_AUDIO_CHANNEL_RL,
+                SPA_AUDIO_CHANNEL_RR,
+            ];
+            let map = ChannelMap::from_positions(&quad, InputLayout::Surround51);
+            assert_eq!(map.encoder_mask(InputLayout::Surround51), 0b11_0011);
+
+            // 7.1 back and side pairs both fold into the 5.1 surrounds.
+            let map = ChannelMap::from_positions(&quad, InputLayout::Surround71);
+            assert_eq!(map.encoder_mask(InputLayout::Surround71), 0b11_0011);
+
+            // Unpositioned streams are assumed to feed the whole layout.
+            let map = ChannelMap::from_positions(&[], InputLayout::Surround71);
+            assert_eq!(map.encoder_mask(InputLayout::Surround71), 0b11_1111);
+        }
+
+        #[test]
+        fn channel_map_routes_quad_rears_to_surrounds() {
+            let positions = [
+                SPA_AUDIO_CHANNEL_FL,
+                SPA_AUDIO_CHANNEL_FR,
+                SPA_AUDIO_CHANNEL_RL,
+                SPA_AUDIO_CHANNEL_RR,
+            ];
+            let map = ChannelMap::from_positions(&positions, InputLayout::Surround51);
+            assert_eq!(map.sources(), 4);
+            assert_eq!(
+                (0..4).map(|ch| map.slot(ch)).collect::<Vec<_>>(),
+                vec![Some(0), Some(1), Some(4), Some(5)]
+            );
+            assert_eq!(
+                map.describe(&positions, InputLayout::Surround51),
+                "FL->FL, FR->FR, RL->SL, RR->SR"
+            );
+
+            let bytes = f32_frames(&[&[0.1, 0.2, 0.3, 0.4]]);
+            let mut parsed = Vec::new();
+            parse_interleaved_from_stride_into(
+                &bytes,
+                0,
+                bytes.len(),
+                16,
+                SampleFormat::F32Le,
+                &map,
+                INPUT_CHANNELS,
+                &mut parsed,
+            )
+            .expect("quad f32 should parse");
+            assert_eq!(parsed, vec![0.1, 0.2, 0.0, 0.0, 0.3, 0.4]);
+        }
+
+        #[test]
+        fn channel_map_follows_reordered_51_positions() {
+            // ALSA-style order: FL,FR,RL,RR,FC,LFE.
+            let positions = [
+                SPA_AUDIO_CHANNEL_FL,
+                SPA_AUDIO_CHANNEL_FR,
+                SPA_AUDIO_CHANNEL_RL,
+                SPA_AUDIO_CHANNEL_RR,
+                SPA_AUDIO_CHANNEL_FC,
+                SPA_AUDIO_CHANNEL_LFE,
+            ];
+            let map = ChannelMap::from_positions(&positions, InputLayout::Surround51);
+            let bytes = f32_frames(&[
+                &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+                &[7.0, 8.0, 9.0, 10.0, 11.0, 12.0],
+            ]);
+            let mut out = Vec::new();
+            parse_interleaved_from_stride_into(
+                &bytes,
+                0,
+                bytes.len(),
+                24,
+                SampleFormat::F32Le,
+                &map,
+                INPUT_CHANNELS,
+                &mut out,
+            )
+            .expect("reordered 5.1 should parse");
+            assert_eq!(
+                out,
+                vec![1.0, 2.0, 5.0, 6.0, 3.0, 4.0, 7.0, 8.0, 11.0, 12.0, 9.0, 10.0]
+            );
+        }
+
+        #[test]
+        fn channel_map_prefers_exact_positions_over_aliases() {
+            // 7.1 source into a 5.1 sink: SL/SR win, RL/RR have no slot left.
+            let positions = [
+                SPA_AUDIO_CHANNEL_FL,
+                SPA_AUDIO_CHANNEL_FR,
+                SPA_AUDIO_CHANNEL_FC,
+                SPA_AUDIO_CHANNEL_LFE,
+                SPA_AUDIO_CHANNEL_RL,
+                SPA_AUDIO_CHANNEL_RR,
+                SPA_AUDIO_CHANNEL_SL,
+                SPA_AUDIO_CHANNEL_SR,
+            ];
+            let map = ChannelMap::from_positions(&positions, InputLayout::Surround51);
+            assert_eq!(map.slot(4), None);
+            assert_eq!(map.slot(5), None);
+            assert_eq!(map.slot(6), Some(4));
+            assert_eq!(map.slot(7), Some(5));
+
+            // The same source into a 7.1 sink keeps every channel.
+            let map = ChannelMap::from_positions(&positions, InputLayout::Surround71);
+            assert_eq!(map, ChannelMap::identity(MAX_CAPTURE_CHANNELS));
+
+            // A 5.1 source into a 7.1 sink lands on the side pair.
+            let surround_51 = [
+                SPA_AUDIO_CHANNEL_FL,
+                SPA_AUDIO_CHANNEL_FR,
+                SPA_AUDIO_CHANNEL_FC,
+                SPA_AUDIO_CHANNEL_LFE,
+                SPA_AUDIO_CHANNEL_SL,
+                SPA_AUDIO_CHANNEL_SR,
+            ];
+            let map = ChannelMap::from_positions(&surround_51, InputLayout::Surround71);
+            assert_eq!(map.slot(4), Some(6));
+            assert_eq!(map.slot(5), Some(7));
+        }
+
+        #[test]
+        fn channel_map_without_positions_maps_by_index() {
+            let unknown = [SPA_AUDIO_CHANNEL_UNKNOWN; 6];
+            assert_eq!(
+                ChannelMap::from_positions(&unknown, InputLayout::Surround51),
+                ChannelMap::identity(INPUT_CHANNELS)
+            );
+            assert_eq!(
+                ChannelMap::from_positions(&[], InputLayout::Surround51),
+                ChannelMap::identity(INPUT_CHANNELS)
+            );
+        }
+
+        // ── sample formats ────────────────────────────────────────────
+
+        const ALL_FORMATS: [SampleFormat; 12] = [
+            SampleFormat::S16Le,
+            SampleFormat::S16Be,
+            SampleFormat::S24Le,
+            SampleFormat::S24Be,
+            SampleFormat::S24In32Le,
+            SampleFormat::S24In32Be,
+            SampleFormat::S32Le,
+            SampleFormat::S32Be,
+            SampleFormat::F32Le,
+            SampleFormat::F32Be,
+            SampleFormat::F64Le,
+            SampleFormat::F64Be,
+        ];
+
+        // Exactly representable in every format, including the integer minimum.
+        const FORMAT_TEST_VALUES: [f32; 4] = [0.5, -0.25, -1.0, 0.0];
+
+        fn encode_sample(format: SampleFormat, value: f32) -> Vec<u8> {
+            let s24 = (value * 8_388_608.0) as i32;
+            match format {
+                SampleFormat::S16Le => ((value * 32_768.0) as i16).to_le_bytes().to_vec(),
+                SampleFormat::S16Be => ((value * 32_768.0) as i16).to_be_bytes().to_vec(),
+                SampleFormat::S24Le => s24.to_le_bytes()[..3].to_vec(),
+                SampleFormat::S24Be => s24.to_be_bytes()[1..].to_vec(),
+                // Upper byte left clear so decoding has to sign-extend bit 23.
+                SampleFormat::S24In32Le => (s24 & 0x00ff_ffff).to_le_bytes().to_vec(),
+                SampleFormat::S24In32Be => (s24 & 0x00ff_ffff).to_be_bytes().to_vec(),
+                SampleFormat::S32Le => ((f64::from(value) * 2_147_483_648.0) as i32)
+                    .to_le_bytes()
+                    .to_vec(),
+                SampleFormat::S32Be => ((f64::from(value) * 2_147_483_648.0) as i32)
+                    .to_be_bytes()
+                    .to_vec(),
+                SampleFormat::F32Le => value.to_le_bytes().to_vec(),
+                SampleFormat::F32Be => value.to_be_bytes().to_vec(),
+                SampleFormat::F64Le => f64::from(value).to_le_bytes().to_vec(),
+                SampleFormat::F64Be => f64::from(value).to_be_bytes().to_vec(),
+            }
+        }
+
+        fn encode_samples(format: SampleFormat, values: &[f32]) -> Vec<u8> {
+            values
+                .iter()
+                .flat_map(|value| encode_sample(format, *value))
+                .collect()
+        }
+
+        #[test]
+        fn sample_format_decodes_every_encoding() {
+            for format in ALL_FORMATS {
+                for value in FORMAT_TEST_VALUES {
+                    let bytes = encode_sample(format, value);
+                    assert_eq!(bytes.len(), format.bytes(), "{format:?}");
+                    assert_eq!(format.decode(&bytes), value, "{format:?}");
+                }
+            }
+        }
+
+        #[test]
+        fn s24_formats_sign_extend_and_ignore_padding() {
+            assert_eq!(SampleFormat::S24Le.decode(&[0x00, 0x00, 0x80]), -1.0);
+            assert_eq!(
+                SampleFormat::S24Be.decode(&[0x7f, 0xff, 0xff]),
+                8_388_607.0 / 8_388_608.0
+            );
+            // Garbage in the padding byte of S24_32 must not leak into the sample.
+            assert_eq!(
+                SampleFormat::S24In32Le.decode(&[0x00, 0x00, 0x80, 0xab]),
+                -1.0
+            );
+            assert_eq!(
+                SampleFormat::S24In32Be.decode(&[0x12, 0x40, 0x00, 0x00]),
+                0.5
+            );
+        }
+
+        #[test]
+        fn interleaved_parser_handles_every_format() {
+            for format in ALL_FORMATS {
+                // Two stereo frames, padded to 5.1.
+                let bytes = encode_samples(format, &FORMAT_TEST_VALUES);
+                let parsed = parse_interleaved_from_stride(
+                    &bytes,
+                    0,
+                    bytes.len(),
+                    2 * format.bytes(),
+                    format,
+                )
+                .unwrap_or_else(|| panic!("{format:?} should parse"));
+                assert_eq!(
+                    parsed,
+                    vec![0.5, -0.25, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                    "{format:?}"
+                );
+
+                // A stride that is not a whole number of samples is rejected.
+                assert!(
+                    parse_interleaved_from_stride(
+                        &bytes,
+                        0,
+                        bytes.len(),
+                        format.bytes() + 1,
+                        format
+                    )
+                    .is_none(),
+                    "{format:?}"
+                );
+            }
+        }
+
+        #[test]
+        fn planar_parser_handles_every_format() {
+            for format in ALL_FORMATS {
+                let bytes = encode_samples(format, &FORMAT_TEST_VALUES);
+                let mut parsed = Vec::new();
+                parse_plane_into(&bytes, 0, bytes.len(), format, &mut parsed)
+                    .unwrap_or_else(|| panic!("{format:?} should parse"));
+                assert_eq!(parsed, FORMAT_TEST_VALUES, "{format:?}");
+
+                // A trailing partial sample is rejected.
+                assert!(
+                    parse_plane_into(&bytes, 0, bytes.len() - 1, format, &mut parsed).is_none(),
+                    "{format:?}"
+                );
+            }
+        }
+
+        #[test]
+        fn planar_parser_offset_alignment_depends_on_format() {
+            let mut bytes = vec![0u8];
+            bytes.extend_from_slice(&encode_samples(SampleFormat::S24Le, &[0.5]));
+            let mut parsed = Vec::new();
+            // Packed 24-bit samples have no alignment requirement.
+            parse_plane_into(&bytes, 1, 3, SampleFormat::S24Le, &mut parsed)
+                .expect("packed S24 at odd offset should parse");
+            assert_eq!(parsed, vec![0.5]);
+
+            let mut bytes = vec![0u8; 4];
+            bytes.extend_from_slice(&encode_samples(SampleFormat::F64Le, &[0.5]));
+            assert!(parse_plane_into(&bytes, 4, 8, SampleFormat::F64Le, &mut parsed).is_none());
+        }
+
+        #[test]
+        fn sample_format_follows_negotiated_audio_format() {
+            assert_eq!(
+                SampleFormat::from_audio_format(AudioFormat::S24_32BE),
+                Some(SampleFormat::S24In32Be)
+            );
+            assert_eq!(
+                SampleFormat::from_audio_format(AudioFormat::S24LE),
+                Some(SampleFormat::S24Le)
+            );
+            assert_eq!(
+                SampleFormat::from_audio_format(AudioFormat::F64BE),
+                Some(SampleFormat::F64Be)
+            );
+            let native_f32 = if cfg!(target_endian = "big") {
+                SampleFormat::F32Be
+            } else {
+                SampleFormat::F32Le
+            };
+            assert_eq!(
+                SampleFormat::from_audio_format(AudioFormat::F32P),
+                Some(native_f32)
+            );
+            assert_eq!(SampleFormat::from_audio_format(AudioFormat::U8), None);
         }
 
         // ── resolve_playback_target ───────────────────────────────────
@@ -297,11 +779,291 @@ mod pipewire_client_impl {
             assert_eq!(target.connect_target_id, None);
             assert_eq!(target.target_object, None);
 
-            let props = build_playback_properties(&target);
+            let props = build_playback_properties(&target, SAMPLE_RATE_HZ);
             assert_eq!(props.get("target.object"), None);
             assert_eq!(props.get("node.autoconnect"), Some("true"));
         }
 
+        #[test]
+        fn playback_target_default_follows_without_target_object() {
+            let target = resolve_playback_target(Some("default"));
+            assert!(target.follow_default);
+            assert_eq!(target.connect_target_id, None);
+            assert_eq!(target.target_object, None);
+
+            let props = build_playback_properties(&target, SAMPLE_RATE_HZ);
+            assert_eq!(props.get("target.object"), None);
+            assert_eq!(props.get("node.autoconnect"), Some("false"));
+        }
+
+        #[test]
+        fn playback_properties_use_configured_output_rate() {
+            let target = resolve_playback_target(None);
+            assert_eq!(
+                build_playback_properties(&target, 44_100).get("audio.rate"),
+                Some("44100")
+            );
+            assert_eq!(
+                build_playback_properties(&target, SAMPLE_RATE_HZ).get("audio.rate"),
+                Some("48000")
+            );
+        }
+
+        // ── iec958_codecs_from_param ──────────────────────────────────
+
+        #[test]
+        fn enum_format_iec958_codecs_are_named() {
+            use pw::spa::pod::{ChoiceValue, Object, Property, Value};
+            use pw::spa::utils::{Choice, ChoiceEnum, ChoiceFlags, Id};
+
+            let codecs = Value::Choice(ChoiceValue::Id(Choice(
+                ChoiceFlags::empty(),
+                ChoiceEnum::Enum {
+                    default: Id(libspa::sys::SPA_AUDIO_IEC958_CODEC_PCM),
+                    alternatives: vec![
+                        Id(libspa::sys::SPA_AUDIO_IEC958_CODEC_PCM),
+                        Id(libspa::sys::SPA_AUDIO_IEC958_CODEC_AC3),
+                        Id(libspa::sys::SPA_AUDIO_IEC958_CODEC_DTS),
+                    ],
+                },
+            )));
+            let object = Value::Object(Object {
+                type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+                id: pw::spa::param::ParamType::EnumFormat.as_raw(),
+                properties: vec![Property::new(
+                    pw::spa::param::format::FormatProperties::AudioIec958Codec.as_raw(),
+                    codecs,
+                )],
+            });
+            let bytes = pw::spa::pod::serialize::PodSerializer::serialize(
+                std::io::Cursor::new(Vec::new()),
+                &object,
+            )
+            .expect("serialize")
+            .0
+            .into_inner();
+            let pod = pw::spa::pod::Pod::from_bytes(&bytes).expect("pod");
+            assert_eq!(iec958_codecs_from_param(pod), ["PCM", "AC3", "DTS"]);
+
+            let raw = build_audio_raw_format_param(AudioFormat::S16LE, SAMPLE_RATE_HZ, 2)
+                .expect("raw format");
+            let pod = pw::spa::pod::Pod::from_bytes(&raw).expect("pod");
+            assert!(iec958_codecs_from_param(pod).is_empty());
+        }
+
+        #[test]
+        fn iec958_format_offers_ac3_passthrough() {
+            let bytes = build_iec958_format_param(44_100).expect("iec958 format");
+            let pod = pw::spa::pod::Pod::from_bytes(&bytes).expect("pod");
+            assert_eq!(iec958_codecs_from_param(pod), ["AC3"]);
+            assert_eq!(parse_iec958_format(pod), Some(("AC3".to_string(), 44_100)));
+
+            let raw = build_audio_raw_format_param(AudioFormat::S16LE, SAMPLE_RATE_HZ, 2)
+                .expect("raw format");
+            let pod = pw::spa::pod::Pod::from_bytes(&raw).expect("pod");
+            assert_eq!(parse_iec958_format(pod), None);
+        }
+
+        #[test]
+        fn playback_formats_prefer_configured_rate_and_offer_the_others() {
+            use pw::spa::param::format::FormatProperties;
+            use pw::spa::pod::{deserialize::PodDeserializer, ChoiceValue, Value};
+            use pw::spa::utils::{Choice, ChoiceEnum};
+
+            let property = |bytes: &[u8], key: FormatProperties| {
+                let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(bytes)
+                else {
+                    panic!("format pod expected");
+                };
+                object
+                    .properties
+                    .into_iter()
+                    .find(|prop| prop.key == key.as_raw())
+                    .map(|prop| prop.value)
+            };
+            let expected_rates = Some(Value::Choice(ChoiceValue::Int(Choice(
+                pw::spa::utils::ChoiceFlags::empty(),
+                ChoiceEnum::Enum {
+                    default: 44_100,
+                    alternatives: vec![32_000, 44_100, 48_000],
+                },
+            ))));
+
+            let raw = build_playback_format_param(44_100).expect("raw format");
+            assert_eq!(property(&raw, FormatProperties::AudioRate), expected_rates);
+            assert_eq!(
+                property(&raw, FormatProperties::AudioChannels),
+                Some(Value::Int(2))
+            );
+            assert_eq!(
+                property(&raw, FormatProperties::AudioFormat),
+                Some(Value::Id(pw::spa::utils::Id(AudioFormat::S16LE.as_raw())))
+            );
+
+            let iec958 = build_iec958_format_param(44_100).expect("iec958 format");
+            assert_eq!(
+                property(&iec958, FormatProperties::AudioRate),
+                expected_rates
+            );
+        }
+
+        // ── device profile params ─────────────────────────────────────
+
+        #[test]
+        fn device_profiles_parse_from_profile_params() {
+            use pw::spa::pod::{deserialize::PodDeserializer, Object, Property, Value};
+            use pw::spa::utils::Id;
+
+            let object = Value::Object(Object {
+                type_: pw::spa::utils::SpaTypes::ObjectParamProfile.as_raw(),
+                id: pw::spa::param::ParamType::EnumProfile.as_raw(),
+                properties: vec![
+                    Property::new(libspa::sys::SPA_PARAM_PROFILE_index, Value::Int(3)),
+                    Property::new(
+                        libspa::sys::SPA_PARAM_PROFILE_name,
+                        Value::String("output:hdmi-stereo-extra2".to_string()),
+                    ),
+                    Property::new(
+                        libspa::sys::SPA_PARAM_PROFILE_description,
+                        Value::String("Digital Stereo (HDMI 3) Output".to_string()),
+                    ),
+                    Property::new(libspa::sys::SPA_PARAM_PROFILE_priority, Value::Int(5700)),
+                    Property::new(
+                        libspa::sys::SPA_PARAM_PROFILE_available,
+                        Value::Id(Id(libspa::sys::SPA_PARAM_AVAILABILITY_no)),
+                    ),
+                ],
+            });
+            let bytes = pw::spa::pod::serialize::PodSerializer::serialize(
+                std::io::Cursor::new(Vec::new()),
+                &object,
+            )
+            .expect("serialize")
+            .0
+            .into_inner();
+            let pod = pw::spa::pod::Pod::from_bytes(&bytes).expect("pod");
+            let profile = device_profile_from_param(pod).expect("profile");
+            assert_eq!(profile.index, 3);
+            assert_eq!(profile.name, "output:hdmi-stereo-extra2");
+            assert_eq!(profile.priority, 5700);
+            assert!(!profile.available);
+
+            // The pod we send carries only the index, so it is not a listing entry.
+            let bytes = build_profile_param(0).expect("profile pod");
+            let pod = pw::spa::pod::Pod::from_bytes(&bytes).expect("pod");
+            assert!(device_profile_from_param(pod).is_none());
+            let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(&bytes)
+            else {
+                panic!("profile pod is not an object");
+            };
+            assert_eq!(object.id, pw::spa::param::ParamType::Profile.as_raw());
+            assert!(object.properties.iter().any(|prop| {
+                prop.key == libspa::sys::SPA_PARAM_PROFILE_index && prop.value == Value::Int(0)
+            }));
+        }
+
+        // ── volume props ──────────────────────────────────────────────
+
+        #[test]
+        fn props_volume_round_trips_through_pods() {
+            let volume = NodeVolume {
+                volume: Some(0.5),
+                mute: Some(true),
+                channel_volumes: Some(vec![0.5, 1.0]),
+                soft_volumes: None,
+                soft_mute: Some(false),
+            };
+            let bytes = build_props_param(&volume).expect("props pod");
+            let pod = pw::spa::pod::Pod::from_bytes(&bytes).expect("pod");
+            assert_eq!(node_volume_from_param(pod), volume);
+
+            let bytes = build_props_param(&volume.to_unity()).expect("props pod");
+            let pod = pw::spa::pod::Pod::from_bytes(&bytes).expect("pod");
+            assert!(node_volume_from_param(pod).deviations().is_empty());
+
+            let raw = build_audio_raw_format_param(AudioFormat::S16LE, SAMPLE_RATE_HZ, 2)
+                .expect("raw format");
+            let pod = pw::spa::pod::Pod::from_bytes(&raw).expect("pod");
+            assert!(node_volume_from_param(pod).is_empty());
+        }
+
+        #[test]
+        fn discard_output_empties_the_ring_for_the_encoder() {
+            let (mut producer, mut consumer) = RingBuffer::<u8>::new(16);
+            for byte in 0..12 {
+                producer.push(byte).expect("push");
+            }
+            discard_output(&mut consumer);
+            assert_eq!(consumer.slots(), 0);
+            assert_eq!(producer.slots(), 16);
+            discard_output(&mut consumer);
+        }
+
+        #[test]
+        fn reconnect_delay_backs_off_to_five_seconds() {
+            let delays: Vec<u64> = (0..7)
+                .map(|attempt| reconnect_delay(attempt).as_millis() as u64)
+                .collect();
+            assert_eq!(delays, [500, 1000, 2000, 4000, 5000, 5000, 5000]);
+            assert_eq!(reconnect_delay(u32::MAX), Duration::from_secs(5));
+        }
+
+        #[test]
+        fn shared_playback_output_survives_sessions() {
+            let (mut producer, consumer) = RingBuffer::<u8>::new(8);
+            let (voice_producer, _voice_consumer) = RingBuffer::<f32>::new(8);
+            let (input_producer, _input_consumer) = RingBuffer::<f32>::new(8);
+            let mut shared = SharedEnds {
+                input: Arc::new(Mutex::new(input_producer)),
+                voice: Some(Arc::new(Mutex::new(voice_producer))),
+                output: Some(consumer),
+                playback_output: None,
+                alsa_thread: None,
+            };
+            let first = shared.playback_output();
+            let second = shared.playback_output();
+            assert!(Arc::ptr_eq(&first, &second));
+            producer.push(7).expect("push");
+            assert_eq!(second.lock().expect("lock").pop(), Ok(7));
+        }
+
+        #[test]
+        fn routes_into_our_capture_nodes_are_described() {
+            let node = |id: u32, name: &str| {
+                GraphObject::new(id, ObjectKind::Node, [("node.name", name.to_string())])
+            };
+            let link = |id: u32, from: u32, to: u32| {
+                GraphObject::new(
+                    id,
+                    ObjectKind::Link,
+                    [
+                        ("link.output.node", from.to_string()),
+                        ("link.input.node", to.to_string()),
+                    ],
+                )
+            };
+            let mut graph = Graph::new();
+            graph.insert(node(10, PLAYBACK_NODE_NAME));
+            graph.insert(node(20, "hdmi"));
+            graph.insert(node(30, INPUT_NODE_NAME));
+            graph.insert(GraphObject::new(
+                40,
+                ObjectKind::Node,
+                [("media.class", "Audio/Sink")],
+            ));
+            graph.insert(link(50, 10, 20));
+            assert_eq!(route_into_input(&graph, 20), None);
+            assert_eq!(route_into_input(&graph, 30), Some(vec![30]));
+
+            graph.insert(link(51, 20, 40));
+            graph.insert(link(52, 40, 30));
+            let route = route_into_input(&graph, 20).expect("route back");
+            assert_eq!(
+                route_description(&graph, &route),
+                "hdmi -> 40 -> pw-ac3-live-input"
+            );
+        }
+
         // ── run_stdout_output_loop edge cases ─────────────────────────
 
         #[test]
@@ -379,8 +1141,8 @@ mod pipewire_client_impl {
                 bytes.extend_from_slice(&val.to_le_bytes());
             }
 
-            let parsed =
-                parse_interleaved_from_stride(&bytes, 8, 16, 8).expect("offset 8 should parse");
+            let parsed = parse_interleaved_from_stride(&bytes, 8, 16, 8, SampleFormat::F32Le)
+                .expect("offset 8 should parse");
             assert_eq!(parsed.len(), 12); // 2 frames × 6 channels
             assert_eq!(parsed[0], 0.25);
             assert_eq!(parsed[1], -0.25);
@@ -392,7 +1154,10 @@ mod pipewire_client_impl {
         fn stride_offset_overflow_returns_none() {
             let bytes = vec![0u8; 16];
             // offset + size overflows usize via checked_add.
-            assert!(parse_interleaved_from_stride(&bytes, usize::MAX, 1, 4).is_none());
+            assert!(
+                parse_interleaved_from_stride(&bytes, usize::MAX, 1, 4, SampleFormat::F32Le)
+                    .is_none()
+            );
         }
     }
 }
@@ -0,0 +1,86 @@
+use pw_ac3_live::resampler::Resampler;
+use std::f64::consts::PI;
+
+fn sine(rate: u32, hz: f64, frames: usize, channels: usize) -> Vec<f32> {
+    (0..frames)
+        .flat_map(|n| {
+            // f64 phase: f32 loses too much precision over a second of samples.
+            let value = (0.5 * (2.0 * PI * hz * n as f64 / f64::from(rate)).sin()) as f32;
+            std::iter::repeat_n(value, channels)
+        })
+        .collect()
+}
+
+#[test]
+fn equal_rates_are_passthrough() {
+    let mut resampler = Resampler::new(48_000, 48_000, 6);
+    assert!(resampler.is_passthrough());
+    assert_eq!(resampler.latency_frames(), 0);
+
+    let input = sine(48_000, 1_000.0, 480, 6);
+    let mut output = Vec::new();
+    resampler.process(&input, &mut output);
+    assert_eq!(output, input);
+}
+
+#[test]
+fn upsampling_44k1_reproduces_a_sine_at_the_output_rate() {
+    let mut resampler = Resampler::new(44_100, 48_000, 2);
+    let input = sine(44_100, 1_000.0, 44_100, 2);
+    let mut output = Vec::new();
+    resampler.process(&input, &mut output);
+
+    // Output instants are aligned with the input, so frame n is the sine at n / 48000.
+    let expected = sine(48_000, 1_000.0, output.len() / 2, 2);
+    let max_error = output
+        .iter()
+        .zip(&expected)
+        .skip(200 * 2)
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0f32, f32::max);
+    assert!(max_error < 1.0e-3, "max error {max_error}");
+}
+
+#[test]
+fn downsampling_96k_removes_content_above_the_new_nyquist() {
+    let mut resampler = Resampler::new(96_000, 48_000, 1);
+    let mut output = Vec::new();
+    resampler.process(&sine(96_000, 30_000.0, 96_000, 1), &mut output);
+    let peak = output[1_000..]
+        .iter()
+        .map(|sample| sample.abs())
+        .fold(0.0f32, f32::max);
+    assert!(peak < 1.0e-3, "30 kHz leaked through at {peak}");
+
+    let mut resampler = Resampler::new(96_000, 48_000, 1);
+    let mut output = Vec::new();
+    resampler.process(&sine(96_000, 5_000.0, 96_000, 1), &mut output);
+    let peak = output[1_000..]
+        .iter()
+        .map(|sample| sample.abs())
+        .fold(0.0f32, f32::max);
+    assert!((peak - 0.5).abs() < 5.0e-3, "5 kHz passband peak {peak}");
+}
+
+#[test]
+fn chunked_processing_matches_one_shot_and_keeps_the_rate_ratio() {
+    let input = sine(44_100, 440.0, 44_100, 6);
+
+    let mut one_shot = Vec::new();
+    Resampler::new(44_100, 48_000, 6).process(&input, &mut one_shot);
+
+    let mut resampler = Resampler::new(44_100, 48_000, 6);
+    let mut chunked = Vec::new();
+    for chunk in input.chunks(6 * 97) {
+        resampler.process(chunk, &mut chunked);
+    }
+    assert_eq!(chunked, one_shot);
+
+    // One second in, one second out (minus the filter look-ahead).
+    let frames = chunked.len() / 6;
+    let latency = resampler.latency_frames();
+    assert!(
+        frames + latency >= 47_999 && frames + latency <= 48_001,
+        "{frames} frames + {latency} latency"
+    );
+}
@@ -0,0 +1,99 @@
+use pw_ac3_live::dsp::DSP_CHANNELS;
+use pw_ac3_live::voice::{VoiceDuckingConfig, VoiceMixer, VOICE_CHANNELS};
+
+const SAMPLE_RATE: u32 = 48_000;
+const MAIN_LEVEL: f32 = 0.5;
+
+fn main_frames(frames: usize) -> Vec<f32> {
+    vec![MAIN_LEVEL; frames * DSP_CHANNELS]
+}
+
+fn voice_frames(left: f32, right: f32, frames: usize) -> Vec<f32> {
+    [left, right]
+        .iter()
+        .copied()
+        .cycle()
+        .take(frames * VOICE_CHANNELS)
+        .collect()
+}
+
+fn last_frame(samples: &[f32]) -> &[f32] {
+    &samples[samples.len() - DSP_CHANNELS..]
+}
+
+#[test]
+fn silent_voice_leaves_main_input_untouched() {
+    let mut mixer = VoiceMixer::new(SAMPLE_RATE, &VoiceDuckingConfig::default());
+    let mut main = main_frames(4_800);
+    mixer.process(&mut main, &voice_frames(0.0, 0.0, 4_800));
+    assert!(main.iter().all(|sample| *sample == MAIN_LEVEL));
+
+    // A voice ring that has not caught up yet is treated as silence.
+    mixer.process(&mut main, &[]);
+    assert!(main.iter().all(|sample| *sample == MAIN_LEVEL));
+}
+
+#[test]
+fn voice_ducks_main_input_and_mixes_into_center_and_fronts() {
+    let config = VoiceDuckingConfig {
+        duck_db: 12.0,
+        attack_ms: 10.0,
+        release_ms: 500.0,
+    };
+    let mut mixer = VoiceMixer::new(SAMPLE_RATE, &config);
+    let frames = SAMPLE_RATE as usize / 2;
+    let mut main = main_frames(frames);
+    mixer.process(&mut main, &voice_frames(0.2, 0.1, frames));
+
+    assert!((mixer.reduction_db() - 12.0).abs() < 0.01);
+    let ducked = MAIN_LEVEL * 10.0f32.powf(-12.0 / 20.0);
+    let frame = last_frame(&main);
+    assert!(
+        (frame[0] - (ducked + 0.2 * 0.5)).abs() < 1.0e-3,
+        "FL {}",
+        frame[0]
+    );
+    assert!(
+        (frame[1] - (ducked + 0.1 * 0.5)).abs() < 1.0e-3,
+        "FR {}",
+        frame[1]
+    );
+    assert!(
+        (frame[2] - (ducked + 0.15)).abs() < 1.0e-3,
+        "FC {}",
+        frame[2]
+    );
+    for (ch, sample) in frame.iter().enumerate().skip(3) {
+        assert!((sample - ducked).abs() < 1.0e-3, "channel {ch}: {sample}");
+    }
+}
+
+#[test]
+fn ducking_ramps_in_with_attack_and_out_with_release() {
+    let config = VoiceDuckingConfig {
+        duck_db: 10.0,
+        attack_ms: 50.0,
+        release_ms: 200.0,
+    };
+    let mut mixer = VoiceMixer::new(SAMPLE_RATE, &config);
+
+    // After one attack time constant the reduction is ~63% of the target.
+    let attack_frames = SAMPLE_RATE as usize / 20;
+    let mut main = main_frames(attack_frames);
+    mixer.process(&mut main, &voice_frames(0.3, 0.3, attack_frames));
+    assert!(
+        (mixer.reduction_db() - 6.3).abs() < 0.2,
+        "reduction {}",
+        mixer.reduction_db()
+    );
+
+    let mut main = main_frames(SAMPLE_RATE as usize / 2);
+    mixer.process(&mut main, &voice_frames(0.3, 0.3, SAMPLE_RATE as usize / 2));
+    assert!(mixer.reduction_db() > 9.9);
+
+    // Voice stops: the main input recovers within a few release times.
+    let mut main = main_frames(SAMPLE_RATE as usize * 3);
+    mixer.process(&mut main, &[]);
+    assert!(mixer.reduction_db() < 0.01);
+    assert!((last_frame(&main)[0] - MAIN_LEVEL).abs() < 1.0e-3);
+}
@@ -0,0 +1,84 @@
+use pw_ac3_live::volume_guard::{NodeVolume, VolumeGuardMode};
+
+#[test]
+fn volume_guard_mode_parses_cli_values() {
+    assert_eq!("off".parse::<VolumeGuardMode>(), Ok(VolumeGuardMode::Off));
+    assert_eq!(
+        " WARN ".parse::<VolumeGuardMode>(),
+        Ok(VolumeGuardMode::Warn)
+    );
+    assert_eq!(
+        "force".parse::<VolumeGuardMode>(),
+        Ok(VolumeGuardMode::Force)
+    );
+    assert!("loud".parse::<VolumeGuardMode>().is_err());
+    assert_eq!(VolumeGuardMode::default().as_str(), "warn");
+}
+
+#[test]
+fn unity_props_report_no_deviation() {
+    let volume = NodeVolume {
+        volume: Some(1.0),
+        mute: Some(false),
+        channel_volumes: Some(vec![1.0, 1.0]),
+        soft_volumes: Some(vec![1.0, 1.0]),
+        soft_mute: Some(false),
+    };
+    assert!(volume.deviations().is_empty());
+    assert!(NodeVolume::default().deviations().is_empty());
+    assert!(NodeVolume::default().is_empty());
+}
+
+#[test]
+fn every_non_unity_entry_is_reported() {
+    let volume = NodeVolume {
+        volume: Some(0.74),
+        mute: Some(true),
+        channel_volumes: Some(vec![1.0, 0.5]),
+        soft_volumes: Some(vec![0.25, 0.25]),
+        soft_mute: Some(true),
+    };
+    assert_eq!(
+        volume.deviations(),
+        [
+            "volume 0.740",
+            "mute",
+            "channelVolumes [1.000, 0.500]",
+            "softVolumes [0.250, 0.250]",
+            "softMute",
+        ]
+    );
+}
+
+#[test]
+fn merge_keeps_entries_missing_from_the_update() {
+    let mut volume = NodeVolume {
+        volume: Some(1.0),
+        channel_volumes: Some(vec![0.5, 0.5]),
+        ..NodeVolume::default()
+    };
+    volume.merge(NodeVolume {
+        mute: Some(true),
+        channel_volumes: Some(vec![1.0, 1.0]),
+        ..NodeVolume::default()
+    });
+    assert_eq!(volume.volume, Some(1.0));
+    assert_eq!(volume.mute, Some(true));
+    assert_eq!(volume.channel_volumes, Some(vec![1.0, 1.0]));
+}
+
+#[test]
+fn unity_props_cover_only_reported_entries() {
+    let volume = NodeVolume {
+        volume: Some(0.3),
+        mute: Some(true),
+        channel_volumes: Some(vec![0.3; 6]),
+        ..NodeVolume::default()
+    };
+    let unity = volume.to_unity();
+    assert_eq!(unity.volume, Some(1.0));
+    assert_eq!(unity.mute, Some(false));
+    assert_eq!(unity.channel_volumes, Some(vec![1.0; 6]));
+    assert_eq!(unity.soft_volumes, None);
+    assert!(unity.deviations().is_empty());
+}